@@ -0,0 +1,345 @@
+//! Benchmarking setup for bottle-tracking
+
+use super::*;
+
+// The `benchmarks!` macro of the standalone release of FRAME expects the pallet
+// configuration trait to be named `Trait`.
+#[cfg(not(feature = "parachain"))]
+use crate::Config as Trait;
+
+use codec::Encode;
+use frame_system::RawOrigin;
+use frame_benchmarking::{benchmarks, account};
+use frame_support::sp_runtime::{traits::{Bounded, IdentifyAccount}, RuntimeAppPublic};
+use registrar::Members;
+
+const SEED: u32 = 0;
+
+struct Parties<T: Config> {
+	sender: T::AccountId,
+	carrier: T::AccountId,
+	receiver: T::AccountId,
+	customs: T::AccountId,
+}
+
+fn member<T: Config>(name: &'static str, member_type: MemberType) -> T::AccountId {
+	let who: T::AccountId = account(name, 0, SEED);
+	Members::<T>::append(member_type, &who);
+	T::Currency::make_free_balance_be(&who, BalanceOf::<T>::max_value() / 2u32.into());
+	who
+}
+
+fn parties<T: Config>() -> Parties<T> {
+	Parties {
+		sender: member::<T>("sender", MemberType::Manufacturer),
+		carrier: member::<T>("carrier", MemberType::Carrier),
+		receiver: member::<T>("receiver", MemberType::Retailer),
+		customs: member::<T>("customs", MemberType::Customs),
+	}
+}
+
+fn bottle_id(i: u32) -> BottleId {
+	let mut id = b"bottle-".to_vec();
+	id.extend_from_slice(&i.to_le_bytes());
+	id
+}
+
+fn shipment_id() -> ShipmentId {
+	b"shipment".to_vec()
+}
+
+fn payment<T: Config>() -> ShipmentPayment<BalanceOf<T>> {
+	ShipmentPayment {
+		price: 1_000_000u32.into(),
+		carrier_fee: 1_000u32.into(),
+	}
+}
+
+fn hashes<T: Config>(n: usize) -> Vec<T::Hash> {
+	(0..n).map(|_| T::Hash::default()).collect()
+}
+
+/// Registers a shipment of `b` bottles going through customs and paid through escrow.
+fn create_shipment<T: Config>(parties: &Parties<T>, b: u32) -> Result<Vec<BottleId>, &'static str> {
+	let bottles: Vec<BottleId> = (0..b).map(bottle_id).collect();
+
+	for bottle in &bottles {
+		registrar::Module::<T>::register_bottle(
+			RawOrigin::Signed(parties.sender.clone()).into(),
+			bottle.clone(),
+			vec![],
+		)?;
+	}
+
+	Module::<T>::register_shipment(
+		RawOrigin::Signed(parties.sender.clone()).into(),
+		shipment_id(),
+		parties.carrier.clone(),
+		parties.receiver.clone(),
+		Some(parties.customs.clone()),
+		Some(<frame_system::Module<T>>::block_number() + 1000u32.into()),
+		bottles.clone(),
+		Some(payment::<T>()),
+		vec![],
+	)?;
+
+	Ok(bottles)
+}
+
+fn fund_and_pickup<T: Config>(parties: &Parties<T>) -> Result<(), &'static str> {
+	Module::<T>::fund_shipment(RawOrigin::Signed(parties.receiver.clone()).into(), shipment_id())?;
+	Module::<T>::track_shipment(
+		RawOrigin::Signed(parties.carrier.clone()).into(),
+		shipment_id(),
+		ShipmentOperation::Pickup,
+	)?;
+	Ok(())
+}
+
+fn clear<T: Config>(parties: &Parties<T>) -> Result<(), &'static str> {
+	Module::<T>::hold_shipment(RawOrigin::Signed(parties.customs.clone()).into(), shipment_id())?;
+	Module::<T>::clear_shipment(
+		RawOrigin::Signed(parties.customs.clone()).into(),
+		shipment_id(),
+		b"reference".to_vec(),
+		hashes::<T>(1),
+	)?;
+	Ok(())
+}
+
+fn deliver<T: Config>(parties: &Parties<T>) -> Result<(), &'static str> {
+	fund_and_pickup::<T>(parties)?;
+	clear::<T>(parties)?;
+	Module::<T>::track_shipment(
+		RawOrigin::Signed(parties.carrier.clone()).into(),
+		shipment_id(),
+		ShipmentOperation::Deliver,
+	)?;
+	Ok(())
+}
+
+/// Documents of `s` bytes in total, as few as they can be.
+fn documents(s: u32) -> Vec<Vec<u8>> {
+	vec![b'd'; s as usize].chunks(registrar::DOCUMENT_MAX_SIZE).map(|chunk| chunk.to_vec()).collect()
+}
+
+fn claims(bottles: &[BottleId], c: u32) -> Vec<(BottleId, BottleIncident)> {
+	bottles.iter().take(c as usize).map(|bottle| (bottle.clone(), BottleIncident::Damaged)).collect()
+}
+
+benchmarks! {
+	_ { }
+
+	register_shipment {
+		let b in 1 .. SHIPMENT_MAX_BOTTLES as u32;
+		let s in 0 .. (registrar::MAX_DOCUMENTS * registrar::DOCUMENT_MAX_SIZE) as u32;
+		let documents = documents(s);
+		let parties = parties::<T>();
+		let bottles: Vec<BottleId> = (0..b).map(bottle_id).collect();
+		for bottle in &bottles {
+			registrar::Module::<T>::register_bottle(
+				RawOrigin::Signed(parties.sender.clone()).into(),
+				bottle.clone(),
+				vec![],
+			)?;
+		}
+		let deadline = <frame_system::Module<T>>::block_number() + 1000u32.into();
+	}: _(
+		RawOrigin::Signed(parties.sender.clone()),
+		shipment_id(),
+		parties.carrier.clone(),
+		parties.receiver.clone(),
+		Some(parties.customs.clone()),
+		Some(deadline),
+		bottles,
+		Some(payment::<T>()),
+		documents.clone()
+	)
+	verify {
+		assert!(Shipments::<T>::contains_key(shipment_id()));
+		assert_eq!(ShipmentDocuments::<T>::get(shipment_id()).len(), documents.len());
+	}
+
+	track_shipment {
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		fund_and_pickup::<T>(&parties)?;
+		clear::<T>(&parties)?;
+	}: _(RawOrigin::Signed(parties.carrier.clone()), shipment_id(), ShipmentOperation::Deliver)
+	verify {
+		assert!(Shipments::<T>::get(shipment_id()).unwrap().is_completed());
+	}
+
+	receive_shipment {
+		let b in 0 .. SHIPMENT_MAX_BOTTLES as u32;
+		let parties = parties::<T>();
+		let bottles = create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		fund_and_pickup::<T>(&parties)?;
+		clear::<T>(&parties)?;
+		let received: Vec<BottleId> = bottles.into_iter().take(b as usize).collect();
+	}: _(RawOrigin::Signed(parties.receiver.clone()), shipment_id(), received)
+	verify {
+		assert!(Shipments::<T>::get(shipment_id()).unwrap().is_completed());
+	}
+
+	fund_shipment {
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+	}: _(RawOrigin::Signed(parties.receiver.clone()), shipment_id())
+	verify {
+		assert!(ShipmentEscrows::<T>::get(shipment_id()).unwrap().funded);
+	}
+
+	cancel_shipment {
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		Module::<T>::fund_shipment(RawOrigin::Signed(parties.receiver.clone()).into(), shipment_id())?;
+	}: _(RawOrigin::Signed(parties.sender.clone()), shipment_id())
+	verify {
+		assert!(Shipments::<T>::get(shipment_id()).unwrap().is_cancelled());
+	}
+
+	reject_shipment {
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		Module::<T>::fund_shipment(RawOrigin::Signed(parties.receiver.clone()).into(), shipment_id())?;
+	}: _(RawOrigin::Signed(parties.receiver.clone()), shipment_id())
+	verify {
+		assert!(Shipments::<T>::get(shipment_id()).unwrap().is_cancelled());
+	}
+
+	open_dispute {
+		let c in 1 .. SHIPMENT_MAX_BOTTLES as u32;
+		let parties = parties::<T>();
+		let bottles = create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		deliver::<T>(&parties)?;
+	}: _(
+		RawOrigin::Signed(parties.receiver.clone()),
+		shipment_id(),
+		claims(&bottles, c),
+		hashes::<T>(DISPUTE_MAX_EVIDENCE)
+	)
+	verify {
+		assert!(Disputes::<T>::contains_key(shipment_id()));
+	}
+
+	respond_to_dispute {
+		let parties = parties::<T>();
+		let bottles = create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		deliver::<T>(&parties)?;
+		Module::<T>::open_dispute(
+			RawOrigin::Signed(parties.receiver.clone()).into(),
+			shipment_id(),
+			claims(&bottles, 1),
+			hashes::<T>(1),
+		)?;
+	}: _(RawOrigin::Signed(parties.carrier.clone()), shipment_id(), hashes::<T>(DISPUTE_MAX_EVIDENCE))
+	verify {
+		assert_eq!(Disputes::<T>::get(shipment_id()).unwrap().responses.len(), 1);
+	}
+
+	rule_dispute {
+		let c in 1 .. SHIPMENT_MAX_BOTTLES as u32;
+		let parties = parties::<T>();
+		let bottles = create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		deliver::<T>(&parties)?;
+		Module::<T>::open_dispute(
+			RawOrigin::Signed(parties.receiver.clone()).into(),
+			shipment_id(),
+			claims(&bottles, c),
+			hashes::<T>(1),
+		)?;
+		let origin = T::ArbitratorOrigin::successful_origin();
+	}: {
+		Module::<T>::rule_dispute(origin, shipment_id(), Verdict::CarrierLiable)?;
+	}
+	verify {
+		assert!(!Disputes::<T>::contains_key(shipment_id()));
+	}
+
+	inspect_shipment {
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		fund_and_pickup::<T>(&parties)?;
+	}: _(RawOrigin::Signed(parties.customs.clone()), shipment_id())
+
+	hold_shipment {
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		fund_and_pickup::<T>(&parties)?;
+	}: _(RawOrigin::Signed(parties.customs.clone()), shipment_id())
+	verify {
+		assert_eq!(Shipments::<T>::get(shipment_id()).unwrap().status, ShipmentStatus::Customs);
+	}
+
+	clear_shipment {
+		let d in 1 .. CLEARANCE_MAX_DOCUMENTS as u32;
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		fund_and_pickup::<T>(&parties)?;
+		Module::<T>::hold_shipment(RawOrigin::Signed(parties.customs.clone()).into(), shipment_id())?;
+		let reference = vec![b'r'; CLEARANCE_REFERENCE_MAX_LENGTH];
+	}: _(RawOrigin::Signed(parties.customs.clone()), shipment_id(), reference, hashes::<T>(d as usize))
+	verify {
+		assert!(ShipmentClearances::<T>::contains_key(shipment_id()));
+	}
+
+	sell_to_customer {
+		let b in 1 .. SHIPMENT_MAX_BOTTLES as u32;
+		let parties = parties::<T>();
+		let bottles = create_shipment::<T>(&parties, b)?;
+		deliver::<T>(&parties)?;
+		let customer = member::<T>("customer", MemberType::Customer);
+	}: _(RawOrigin::Signed(parties.receiver.clone()), customer.clone(), bottles.clone())
+	verify {
+		assert_eq!(BottlesOfCustomer::<T>::get(&customer), bottles);
+	}
+
+	report_telemetry {
+		let r in 1 .. TELEMETRY_MAX_REPORTS as u32;
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		fund_and_pickup::<T>(&parties)?;
+		let reports: Vec<(ShipmentId, Telemetry)> = (1..=r).map(|i| {
+			(shipment_id(), Telemetry { latitude: 0, longitude: 0, temperature: 0, recorded: i as u64 })
+		}).collect();
+	}: _(RawOrigin::Signed(parties.carrier.clone()), reports)
+	verify {
+		assert_eq!(LastTelemetry::get(shipment_id()).unwrap().recorded, r as u64);
+	}
+
+	register_device {
+		let parties = parties::<T>();
+		let device: T::AccountId = account("device", 0, SEED);
+	}: _(RawOrigin::Signed(parties.carrier.clone()), device.clone())
+	verify {
+		assert!(Devices::<T>::contains_key(&device));
+	}
+
+	remove_device {
+		let parties = parties::<T>();
+		let device: T::AccountId = account("device", 0, SEED);
+		Module::<T>::register_device(RawOrigin::Signed(parties.carrier.clone()).into(), device.clone())?;
+	}: _(RawOrigin::Signed(parties.carrier.clone()), device.clone())
+	verify {
+		assert!(!Devices::<T>::contains_key(&device));
+	}
+
+	submit_scan_unsigned {
+		let parties = parties::<T>();
+		create_shipment::<T>(&parties, SHIPMENT_MAX_BOTTLES as u32)?;
+		fund_and_pickup::<T>(&parties)?;
+		let key = <T::DeviceId as AppCrypto<T::Public, T::Signature>>::RuntimeAppPublic::generate_pair(None);
+		let public: <T::DeviceId as AppCrypto<T::Public, T::Signature>>::GenericPublic = key.clone().into();
+		let public: T::Public = public.into();
+		let device = public.clone().into_account();
+		Module::<T>::register_device(RawOrigin::Signed(parties.carrier.clone()).into(), device.clone())?;
+		let genesis = frame_system::Module::<T>::block_hash(T::BlockNumber::zero());
+		let payload = ScanPayload { device: public, shipment: shipment_id(), nonce: 0, genesis };
+		let signature = key.sign(&payload.encode()).ok_or("cannot sign the scan")?;
+		let signature: <T::DeviceId as AppCrypto<T::Public, T::Signature>>::GenericSignature = signature.into();
+	}: _(RawOrigin::None, payload, signature.into())
+	verify {
+		assert_eq!(DeviceNonces::<T>::get(&device), 1);
+	}
+}
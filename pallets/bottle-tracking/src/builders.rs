@@ -6,31 +6,35 @@ use registrar::BottleId;
 // Shipment Builder
 
 #[derive(Default)]
-pub struct ShipmentBuilder<AccountId, Moment> 
+pub struct ShipmentBuilder<AccountId, Moment, BlockNumber> 
 where 
     AccountId: Default,
     Moment: Default,
+    BlockNumber: Default,
 {
     pub id: ShipmentId,
-    pub manufacturer: AccountId,
+    pub sender: AccountId,
     pub carrier: AccountId,
-    pub retailer: AccountId,
+    pub receiver: AccountId,
     pub bottles: Vec<BottleId>,
+    pub customs: Option<AccountId>,
     pub registered: Moment,
+    pub deadline: Option<BlockNumber>,
 }
 
-impl<AccountId, Moment> ShipmentBuilder<AccountId, Moment> 
+impl<AccountId, Moment, BlockNumber> ShipmentBuilder<AccountId, Moment, BlockNumber> 
 where 
     AccountId: Default,
     Moment: Default,
+    BlockNumber: Default,
 {
     pub fn identified_by(mut self, id: ShipmentId) -> Self {
         self.id = id;
         self
     }
 
-    pub fn manufactured_by(mut self, account: AccountId) -> Self {
-        self.manufacturer = account;
+    pub fn shipped_by(mut self, account: AccountId) -> Self {
+        self.sender = account;
         self
     }
 
@@ -40,7 +44,7 @@ where
     }
 
     pub fn sent_to(mut self, account: AccountId) -> Self {
-        self.retailer = account;
+        self.receiver = account;
         self
     }
 
@@ -49,21 +53,35 @@ where
         self
     }
 
+    pub fn through_customs(mut self, customs: Option<AccountId>) -> Self {
+        self.customs = customs;
+        self
+    }
+
     pub fn registered_at(mut self, registered_at: Moment) -> Self {
         self.registered = registered_at;
         self
     }
 
-    pub fn build(self) -> Shipment<AccountId, Moment> {
-        Shipment::<AccountId, Moment> {
+    pub fn due_by(mut self, deadline: Option<BlockNumber>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn build(self) -> Shipment<AccountId, Moment, BlockNumber> {
+        Shipment::<AccountId, Moment, BlockNumber> {
             id: self.id,
-            manufacturer: self.manufacturer,
+            sender: self.sender,
             carrier: self.carrier,
-            retailer: self.retailer,
+            receiver: self.receiver,
             bottles: self.bottles,
+            missing: Vec::new(),
+            customs: self.customs,
             status: ShipmentStatus::Pending,
             registered: self.registered,
             delivered: None,
+            deadline: self.deadline,
+            overdue: false,
         }
     }
 
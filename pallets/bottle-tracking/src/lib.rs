@@ -0,0 +1,1340 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Edit this file to define custom logic or remove it if it is not needed.
+/// Learn more about FRAME and the core library of Substrate FRAME pallets:
+/// https://substrate.dev/docs/en/knowledgebase/runtime/frame
+
+use frame_support::{decl_module, decl_storage, decl_event, decl_error, ensure,
+	dispatch, traits::{Get, Currency, ReservableCurrency, BalanceStatus, EnsureOrigin}, weights::Weight,
+	sp_runtime::{Perbill, SaturatedConversion, traits::{IdentifyAccount, Saturating, Zero},
+		transaction_validity::{
+			InvalidTransaction, TransactionPriority, TransactionSource, TransactionValidity, ValidTransaction,
+		}},
+	sp_std::prelude::*, sp_std::vec::Vec, storage::{with_transaction, TransactionOutcome},
+	unsigned::ValidateUnsigned};
+use frame_system::{ensure_none, ensure_signed,
+	offchain::{AppCrypto, CreateSignedTransaction, SignedPayload, SigningTypes}};
+use registrar::{self as registrar, BottleId, BottleStatus, MemberType, SystemConfig, TimestampConfig};
+
+// The mock runtime is written against the standalone release of FRAME.
+#[cfg(all(test, not(feature = "parachain")))]
+mod mock;
+
+#[cfg(all(test, not(feature = "parachain")))]
+mod tests;
+
+#[cfg(all(test, not(feature = "parachain")))]
+mod proptests;
+
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+pub mod migrations;
+
+pub mod types;
+use crate::types::*;
+
+pub mod offchain;
+
+mod builders;
+use crate::builders::*;
+
+pub const SHIPMENT_ID_MAX_LENGTH: usize = 36;
+pub const SHIPMENT_MAX_BOTTLES: usize = 5;
+pub const CLEARANCE_REFERENCE_MAX_LENGTH: usize = 64;
+pub const CLEARANCE_MAX_DOCUMENTS: usize = 5;
+pub const DISPUTE_MAX_EVIDENCE: usize = 5;
+pub const TELEMETRY_MAX_REPORTS: usize = 20;
+
+type BalanceOf<T> = <<T as Config>::Currency as Currency<<T as SystemConfig>::AccountId>>::Balance;
+
+/// Runs a dispatch in a storage transaction, which is reverted if the dispatch fails. The
+/// `#[transactional]` attribute of the crates.io release of FRAME rejects bodies using `?`.
+fn transactional<R, E>(dispatch: impl FnOnce() -> Result<R, E>) -> Result<R, E> {
+	with_transaction(|| {
+		let result = dispatch();
+		if result.is_ok() {
+			TransactionOutcome::Commit(result)
+		} else {
+			TransactionOutcome::Rollback(result)
+		}
+	})
+}
+
+/// Configure the pallet by specifying the parameters and types on which it depends.
+pub trait Config: SystemConfig + TimestampConfig + registrar::Config + CreateSignedTransaction<Call<Self>> {
+	/// Because this pallet emits events, it depends on the runtime's definition of an event.
+	type Event: From<Event<Self>> + Into<<Self as SystemConfig>::Event>;
+
+	/// Currency used to escrow shipment payments.
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// Origin allowed to rule on shipment disputes.
+	type ArbitratorOrigin: EnsureOrigin<Self::Origin>;
+
+	/// Number of blocks after delivery during which the receiver can open a dispute
+	/// and the escrowed payment stays reserved.
+	type DisputePeriod: Get<Self::BlockNumber>;
+
+	/// Keys of the carrier devices the offchain worker reports telemetry with.
+	type DeviceId: AppCrypto<Self::Public, Self::Signature>;
+
+	/// Number of blocks between two polls of the device feeds by the offchain worker.
+	type FeedPollInterval: Get<Self::BlockNumber>;
+
+	/// Maximum number of unsigned scans a device can submit per `DeviceScanWindow`.
+	type MaxDeviceScans: Get<u32>;
+
+	/// Number of blocks the scans of a device are counted over. Unsigned scans stay in the
+	/// transaction pool for as long.
+	type DeviceScanWindow: Get<Self::BlockNumber>;
+
+	/// Priority of unsigned scans in the transaction pool.
+	type UnsignedScanPriority: Get<TransactionPriority>;
+
+	/// Weight information for extrinsics in this pallet.
+	type WeightInfo: WeightInfo;
+}
+
+// The pallet's runtime storage items.
+// https://substrate.dev/docs/en/knowledgebase/runtime/storage
+decl_storage! {
+	// A unique name is used to ensure that the pallet's storage items are isolated.
+	// This name may be updated, but each pallet in the runtime must use a unique name.
+	// ---------------------------------vvvvvvvvvvvvvv
+	trait Store for Module<T: Config> as BottleTracking {
+		pub Shipments: map hasher(blake2_128_concat) ShipmentId => Option<Shipment<T::AccountId, T::Moment, T::BlockNumber>>;
+		pub ShipmentsOfSender: map hasher(blake2_128_concat) T::AccountId => Vec<ShipmentId>;
+		pub ShipmentsOfCarrier: map hasher(blake2_128_concat) T::AccountId => Vec<ShipmentId>;
+		pub ShipmentsOfReceiver: map hasher(blake2_128_concat) T::AccountId => Vec<ShipmentId>;
+		pub BottleOfShipment: map hasher(blake2_128_concat) BottleId => Option<ShipmentId>;
+		pub ShipmentsDueAt: map hasher(twox_64_concat) T::BlockNumber => Vec<ShipmentId>;
+		pub ShipmentClearances: map hasher(blake2_128_concat) ShipmentId => Option<Clearance<T::AccountId, T::Hash, T::Moment>>;
+		pub ShipmentEscrows: map hasher(blake2_128_concat) ShipmentId => Option<Escrow<BalanceOf<T>, T::BlockNumber>>;
+		pub EscrowsHeldUntil: map hasher(twox_64_concat) T::BlockNumber => Vec<ShipmentId>;
+		pub Disputes: map hasher(blake2_128_concat) ShipmentId => Option<Dispute<T::AccountId, T::Hash, T::BlockNumber>>;
+		/// Block at which the dispute period of a delivered shipment ends. Ruling a dispute
+		/// ends it early.
+		pub DisputableUntil: map hasher(blake2_128_concat) ShipmentId => Option<T::BlockNumber>;
+		pub BottleIncidents: map hasher(blake2_128_concat) BottleId => Option<BottleIncident>;
+		pub LastCustodianOf: map hasher(blake2_128_concat) BottleId => Option<T::AccountId>;
+		pub LastTelemetry: map hasher(blake2_128_concat) ShipmentId => Option<Telemetry>;
+		/// Hashes of the documents of a shipment, which are kept in the offchain index.
+		pub ShipmentDocuments: map hasher(blake2_128_concat) ShipmentId => Vec<T::Hash>;
+		pub Devices: map hasher(blake2_128_concat) T::AccountId => Option<Device<T::AccountId, T::BlockNumber>>;
+		/// Nonce of the next scan of a device. It outlives the device, so that its scans
+		/// cannot be replayed when it is registered again.
+		pub DeviceNonces: map hasher(blake2_128_concat) T::AccountId => u64;
+
+		pub BottlesOfCustomer: map hasher(blake2_128_concat) T::AccountId => Vec<BottleId>;
+		pub BottleSoldTo: map hasher(blake2_128_concat) BottleId => Option<T::AccountId>;
+
+		/// Storage layout in use. Chains started before it was introduced are on `V1_0_0`.
+		pub StorageVersion get(fn storage_version) build(|_: &GenesisConfig<T>| Releases::V2_0_0): Releases;
+	}
+	add_extra_genesis {
+		/// Shipments waiting for pickup at genesis, of bottles registered in the registrar
+		/// genesis. [shipment_id, sender, carrier, receiver, bottles]
+		config(shipments): Vec<(ShipmentId, T::AccountId, T::AccountId, T::AccountId, Vec<BottleId>)>;
+		build(|config: &GenesisConfig<T>| {
+			for (id, sender, carrier, receiver, bottles) in &config.shipments {
+				Module::<T>::add_shipment(id.clone(), sender, carrier.clone(), receiver.clone(), None, None, bottles.clone())
+					.expect("genesis shipment is valid; qed");
+			}
+		})
+	}
+}
+
+// Pallets use events to inform users when important changes are made.
+// https://substrate.dev/docs/en/knowledgebase/runtime/events
+decl_event!(
+	pub enum Event<T> where
+		AccountId = <T as SystemConfig>::AccountId,
+		BlockNumber = <T as SystemConfig>::BlockNumber,
+		Balance = BalanceOf<T>,
+	{
+		/// Shipment registered. [shipment_id, sender]
+		ShipmentRegistered(ShipmentId, AccountId),
+		/// Shipment status updated. [shipment_id, carrier or receiver, status]
+		ShipmentStatusUpdated(ShipmentId, AccountId, ShipmentStatus),
+		/// Receiver reserved the purchase price of the shipment. [shipment_id, receiver, price]
+		ShipmentFunded(ShipmentId, AccountId, Balance),
+		/// Escrowed payment paid to sender and carrier, held until the dispute period ends. [shipment_id, held_until]
+		EscrowHeld(ShipmentId, BlockNumber),
+		/// Escrowed payment released to sender and carrier. [shipment_id, price, carrier_fee]
+		EscrowReleased(ShipmentId, Balance, Balance),
+		/// Shipment cancelled by its sender. [shipment_id, sender]
+		ShipmentCancelled(ShipmentId, AccountId),
+		/// Shipment rejected by its receiver. [shipment_id, receiver]
+		ShipmentRejected(ShipmentId, AccountId),
+		/// Bottles missing on receipt, lost in the custody of the carrier. [shipment_id, carrier, bottles]
+		BottlesLostInTransit(ShipmentId, AccountId, Vec<BottleId>),
+		/// Shipment was not delivered by its deadline. [shipment_id, deadline]
+		ShipmentOverdue(ShipmentId, BlockNumber),
+		/// Shipment inspected by customs. [shipment_id, customs]
+		ShipmentInspected(ShipmentId, AccountId),
+		/// Shipment held by customs. [shipment_id, customs]
+		ShipmentHeldByCustoms(ShipmentId, AccountId),
+		/// Shipment cleared and released by customs. [shipment_id, customs, clearance_reference]
+		ShipmentClearedByCustoms(ShipmentId, AccountId, ClearanceReference),
+		/// Dispute opened by the shipment receiver. [shipment_id, receiver]
+		DisputeOpened(ShipmentId, AccountId),
+		/// Shipment sender or carrier responded to a dispute. [shipment_id, account]
+		DisputeResponded(ShipmentId, AccountId),
+		/// Arbitrator ruled on a dispute. [shipment_id, verdict, refund]
+		DisputeRuled(ShipmentId, Verdict, Balance),
+		/// Bottles sold to customer. [customer]
+		BottlesSoldToCustomer(AccountId),
+		/// Carrier device reported where a shipment is. [shipment_id, carrier, telemetry]
+		ShipmentTelemetryReported(ShipmentId, AccountId, Telemetry),
+		/// Carrier registered a device. [carrier, device]
+		DeviceRegistered(AccountId, AccountId),
+		/// Carrier removed a device. [carrier, device]
+		DeviceRemoved(AccountId, AccountId),
+		/// Device of a carrier scanned a shipment. [shipment_id, carrier, device]
+		ShipmentScanned(ShipmentId, AccountId, AccountId),
+	}
+);
+
+// Errors inform users that something went wrong.
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		ShipmentIdExists,
+		ShipmentIdMissing,
+		ShipmentIdTooLong,
+		ShipmentHasNoBottles,
+		ShipmentHasTooManyBottles,
+		BottleAlreadyShipped,
+		ShipmentDoesNotExist,
+		ShipmentHasBeenDelivered,
+		ShipmentInTransit,
+		NotShipmentCarrier,
+		BottleNotShipped,
+		ShipmentPending,
+		NotBottleOwner,
+		BottleAlreadySold,
+		ShipmentToSelf,
+		NotStockHolder,
+		InvalidShipmentReceiver,
+		ShipmentInCustoms,
+		ShipmentNotCleared,
+		ShipmentNotHeldByCustoms,
+		NotShipmentCustoms,
+		ShipmentHasNoCustoms,
+		ShipmentHasBeenCleared,
+		DeadlineInPast,
+		ShipmentHasBeenCancelled,
+		ShipmentHasNoEscrow,
+		ShipmentAlreadyFunded,
+		ShipmentNotFunded,
+		NotShipmentSender,
+		NotShipmentReceiver,
+		DisputeExists,
+		DisputeDoesNotExist,
+		DisputePeriodOver,
+		DisputeHasNoClaims,
+		DisputeHasTooManyClaims,
+		DisputeHasNoEvidence,
+		DisputeHasTooMuchEvidence,
+		BottleNotInShipment,
+		BottleClaimedTwice,
+		NotDisputeParty,
+		DisputeAlreadyAnswered,
+		BottleLostOrDamaged,
+		ShipmentNotDelivered,
+		BottleReceivedTwice,
+		BottleListedTwice,
+		ClearanceReferenceMissing,
+		ClearanceReferenceTooLong,
+		ClearanceHasNoDocuments,
+		ClearanceHasTooManyDocuments,
+		TelemetryHasNoReports,
+		TelemetryHasTooManyReports,
+		InvalidTelemetry,
+		DeviceAlreadyRegistered,
+		DeviceNotRegistered,
+		NotDeviceCarrier,
+		InvalidScanNonce,
+		TooManyScans,
+		BottleMissingOnReceipt,
+		ScanForAnotherChain,
+	}
+}
+
+// Dispatchable functions allows users to interact with the pallet and invoke state changes.
+// These functions materialize as "extrinsics", which are often compared to transactions.
+// Dispatchable functions must be annotated with a weight and must return a DispatchResult.
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		// Errors must be initialized if they are used by the pallet.
+		type Error = Error<T>;
+
+		// Events must be initialized if they are used by the pallet.
+		fn deposit_event() = default;
+
+		fn on_runtime_upgrade() -> Weight {
+			#[cfg(feature = "try-runtime")]
+			migrations::pre_migrate::<T>().expect("bottle-tracking pre-migration checks failed");
+
+			let weight = migrations::migrate::<T>();
+
+			#[cfg(feature = "try-runtime")]
+			migrations::post_migrate::<T>().expect("bottle-tracking post-migration checks failed");
+
+			weight
+		}
+
+		fn on_initialize(now: T::BlockNumber) -> Weight {
+			Self::check_overdue_shipments(now)
+				.saturating_add(Self::release_held_escrows(now))
+		}
+
+		fn offchain_worker(now: T::BlockNumber) {
+			offchain::poll_device_feeds::<T>(now);
+		}
+
+		/// Registers a shipment with its documents, such as the bill of lading and the
+		/// invoice. Only the hashes of the documents are stored on chain, the documents go to
+		/// the offchain index.
+		#[weight = <T as Config>::WeightInfo::register_shipment(
+			bottles.len() as u32,
+			registrar::documents_size(documents),
+		)]
+		pub fn register_shipment(
+			origin,
+			id: ShipmentId,
+			carrier: T::AccountId,
+			receiver: T::AccountId,
+			customs: Option<T::AccountId>,
+			deadline: Option<T::BlockNumber>,
+			bottles: Vec<BottleId>,
+			payment: Option<ShipmentPayment<BalanceOf<T>>>,
+			documents: Vec<Vec<u8>>,
+		) -> dispatch::DispatchResult {
+			transactional(|| {
+				// Check that the extrinsic was signed and get the signer.
+				let sender = ensure_signed(origin)?;
+
+				registrar::Module::<T>::validate_documents(&documents)?;
+
+				Self::add_shipment(id.clone(), &sender, carrier, receiver, customs, deadline, bottles)?;
+
+				if let Some(payment) = payment {
+					T::Currency::reserve(&sender, payment.carrier_fee)?;
+					ShipmentEscrows::<T>::insert(&id, Escrow { payment, funded: false, held_until: None });
+				}
+
+				if !documents.is_empty() {
+					ShipmentDocuments::<T>::insert(&id, registrar::Module::<T>::index_documents(&documents));
+				}
+
+				// Emit an event.
+				Self::deposit_event(RawEvent::ShipmentRegistered(id, sender));
+				// Return a successful DispatchResult
+				Ok(())
+			})
+		}
+
+		#[weight = <T as Config>::WeightInfo::track_shipment()]
+		pub fn track_shipment(
+			origin,
+			id: ShipmentId,
+			operation: ShipmentOperation,	
+		) -> dispatch::DispatchResult {
+			transactional(|| {
+				let who = ensure_signed(origin)?;
+
+				registrar::Module::<T>::validate_carrier(&who)?;
+
+				Self::validate_shipment_id(&id)?;
+
+				let mut shipment = match Shipments::<T>::get(&id) {
+					None => Err(Error::<T>::ShipmentDoesNotExist),
+					Some(sp) => match sp.status {
+						ShipmentStatus::Delivered | ShipmentStatus::Stored =>
+							Err(Error::<T>::ShipmentHasBeenDelivered),
+						ShipmentStatus::Cancelled | ShipmentStatus::Rejected =>
+							Err(Error::<T>::ShipmentHasBeenCancelled),
+						ShipmentStatus::InTransit if operation == ShipmentOperation::Pickup => 
+							Err(Error::<T>::ShipmentInTransit),
+						ShipmentStatus::Pending if operation == ShipmentOperation::Deliver =>
+							Err(Error::<T>::ShipmentPending),
+						ShipmentStatus::Customs if operation != ShipmentOperation::Scan =>
+							Err(Error::<T>::ShipmentInCustoms),
+						_ => Ok(sp),
+					}
+				}?;
+
+				ensure!(shipment.carrier == who, Error::<T>::NotShipmentCarrier);
+
+				if operation == ShipmentOperation::Pickup {
+					if let Some(escrow) = ShipmentEscrows::<T>::get(&id) {
+						ensure!(escrow.funded, Error::<T>::ShipmentNotFunded);
+					}
+				}
+
+				if operation == ShipmentOperation::Pickup {
+					for bottle in &shipment.bottles {
+						registrar::Module::<T>::update_bottle_status(bottle, operation.clone().into())?;
+					}
+				}
+
+				shipment = match operation {
+					ShipmentOperation::Pickup => shipment.pickup(),
+					ShipmentOperation::Deliver => Self::complete_delivery(shipment)?,
+					_ => shipment,
+				};
+
+				if operation != ShipmentOperation::Scan {
+					let status = shipment.status.clone();
+					Shipments::<T>::insert(&id, shipment);
+					Self::deposit_event(RawEvent::ShipmentStatusUpdated(id, who, status));
+				}
+
+				Ok(())
+			})
+		}
+
+		#[weight = <T as Config>::WeightInfo::receive_shipment(SHIPMENT_MAX_BOTTLES as u32)]
+		pub fn receive_shipment(
+			origin,
+			id: ShipmentId,
+			received: Vec<BottleId>,
+		) -> dispatch::DispatchResult {
+			transactional(|| {
+				let who = ensure_signed(origin)?;
+
+				Self::validate_shipment_id(&id)?;
+
+				let shipment = Shipments::<T>::get(&id).ok_or(Error::<T>::ShipmentDoesNotExist)?;
+
+				ensure!(shipment.receiver == who, Error::<T>::NotShipmentReceiver);
+
+				match shipment.status {
+					ShipmentStatus::InTransit => (),
+					ShipmentStatus::Pending => Err(Error::<T>::ShipmentPending)?,
+					ShipmentStatus::Customs => Err(Error::<T>::ShipmentInCustoms)?,
+					ShipmentStatus::Cancelled | ShipmentStatus::Rejected => Err(Error::<T>::ShipmentHasBeenCancelled)?,
+					_ => Err(Error::<T>::ShipmentHasBeenDelivered)?,
+				};
+
+				for (index, bottle) in received.iter().enumerate() {
+					ensure!(shipment.bottles.contains(bottle), Error::<T>::BottleNotInShipment);
+					ensure!(!received[..index].contains(bottle), Error::<T>::BottleReceivedTwice);
+				}
+
+				let missing: Vec<BottleId> = shipment.bottles.iter()
+					.filter(|bottle| !received.contains(*bottle))
+					.cloned()
+					.collect();
+
+				for bottle in &missing {
+					BottleIncidents::insert(bottle, BottleIncident::Lost);
+					LastCustodianOf::<T>::insert(bottle, &shipment.carrier);
+				}
+
+				let carrier = shipment.carrier.clone();
+				let shipment = Self::complete_delivery(shipment.with_missing(missing.clone()))?;
+				let status = shipment.status.clone();
+				Shipments::<T>::insert(&id, shipment);
+
+				if !missing.is_empty() {
+					Self::deposit_event(RawEvent::BottlesLostInTransit(id.clone(), carrier, missing));
+				}
+				Self::deposit_event(RawEvent::ShipmentStatusUpdated(id, who, status));
+
+				Ok(())
+			})
+		}
+
+		#[weight = <T as Config>::WeightInfo::fund_shipment()]
+		pub fn fund_shipment(
+			origin,
+			id: ShipmentId,
+		) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			Self::validate_shipment_id(&id)?;
+
+			let shipment = Self::validate_pending_shipment(&id)?;
+
+			ensure!(shipment.receiver == who, Error::<T>::NotShipmentReceiver);
+
+			let mut escrow = ShipmentEscrows::<T>::get(&id).ok_or(Error::<T>::ShipmentHasNoEscrow)?;
+
+			ensure!(!escrow.funded, Error::<T>::ShipmentAlreadyFunded);
+
+			T::Currency::reserve(&who, escrow.payment.price)?;
+
+			let price = escrow.payment.price;
+			escrow.funded = true;
+			ShipmentEscrows::<T>::insert(&id, escrow);
+
+			Self::deposit_event(RawEvent::ShipmentFunded(id, who, price));
+
+			Ok(())
+		}
+
+		#[weight = <T as Config>::WeightInfo::cancel_shipment()]
+		pub fn cancel_shipment(
+			origin,
+			id: ShipmentId,
+		) -> dispatch::DispatchResult {
+			transactional(|| {
+				let who = ensure_signed(origin)?;
+
+				Self::validate_shipment_id(&id)?;
+
+				let shipment = Self::validate_pending_shipment(&id)?;
+
+				ensure!(shipment.sender == who, Error::<T>::NotShipmentSender);
+
+				Self::refund_escrow(&shipment);
+				Self::return_bottles_to_sender(&shipment)?;
+
+				Shipments::<T>::insert(&id, shipment.cancelled());
+
+				Self::deposit_event(RawEvent::ShipmentCancelled(id, who));
+
+				Ok(())
+			})
+		}
+
+		#[weight = <T as Config>::WeightInfo::reject_shipment()]
+		pub fn reject_shipment(
+			origin,
+			id: ShipmentId,
+		) -> dispatch::DispatchResult {
+			transactional(|| {
+				let who = ensure_signed(origin)?;
+
+				Self::validate_shipment_id(&id)?;
+
+				let shipment = Self::validate_pending_shipment(&id)?;
+
+				ensure!(shipment.receiver == who, Error::<T>::NotShipmentReceiver);
+
+				Self::refund_escrow(&shipment);
+				Self::return_bottles_to_sender(&shipment)?;
+
+				Shipments::<T>::insert(&id, shipment.rejected());
+
+				Self::deposit_event(RawEvent::ShipmentRejected(id, who));
+
+				Ok(())
+			})
+		}
+
+		#[weight = <T as Config>::WeightInfo::open_dispute(claims.len() as u32)]
+		pub fn open_dispute(
+			origin,
+			id: ShipmentId,
+			claims: Vec<(BottleId, BottleIncident)>,
+			evidence: Vec<T::Hash>,
+		) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			Self::validate_shipment_id(&id)?;
+
+			let shipment = Shipments::<T>::get(&id).ok_or(Error::<T>::ShipmentDoesNotExist)?;
+
+			ensure!(shipment.receiver == who, Error::<T>::NotShipmentReceiver);
+			ensure!(shipment.is_completed(), Error::<T>::ShipmentNotDelivered);
+			ensure!(!Disputes::<T>::contains_key(&id), Error::<T>::DisputeExists);
+
+			// Shipments delivered before every shipment got a dispute period only have the
+			// holding period of their payment, if they were paid for.
+			let disputable_until = DisputableUntil::<T>::get(&id)
+				.or_else(|| ShipmentEscrows::<T>::get(&id).and_then(|escrow| escrow.held_until));
+			let now = <frame_system::Module<T>>::block_number();
+			ensure!(disputable_until.map_or(false, |until| now < until), Error::<T>::DisputePeriodOver);
+
+			Self::validate_dispute_claims(&shipment, &claims)?;
+			Self::validate_dispute_evidence(&evidence)?;
+
+			let dispute = Dispute {
+				shipment: id.clone(),
+				claimant: who.clone(),
+				claims,
+				evidence,
+				responses: Vec::new(),
+				opened: <frame_system::Module<T>>::block_number(),
+			};
+
+			Disputes::<T>::insert(&id, dispute);
+
+			Self::deposit_event(RawEvent::DisputeOpened(id, who));
+
+			Ok(())
+		}
+
+		#[weight = <T as Config>::WeightInfo::respond_to_dispute()]
+		pub fn respond_to_dispute(
+			origin,
+			id: ShipmentId,
+			evidence: Vec<T::Hash>,
+		) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			Self::validate_shipment_id(&id)?;
+
+			let shipment = Shipments::<T>::get(&id).ok_or(Error::<T>::ShipmentDoesNotExist)?;
+			let mut dispute = Disputes::<T>::get(&id).ok_or(Error::<T>::DisputeDoesNotExist)?;
+
+			ensure!(who == shipment.sender || who == shipment.carrier, Error::<T>::NotDisputeParty);
+			ensure!(
+				!dispute.responses.iter().any(|(account, _)| *account == who),
+				Error::<T>::DisputeAlreadyAnswered
+			);
+
+			Self::validate_dispute_evidence(&evidence)?;
+
+			dispute.responses.push((who.clone(), evidence));
+			Disputes::<T>::insert(&id, dispute);
+
+			Self::deposit_event(RawEvent::DisputeResponded(id, who));
+
+			Ok(())
+		}
+
+		#[weight = <T as Config>::WeightInfo::rule_dispute(SHIPMENT_MAX_BOTTLES as u32)]
+		pub fn rule_dispute(
+			origin,
+			id: ShipmentId,
+			verdict: Verdict,
+		) -> dispatch::DispatchResult {
+			transactional(|| {
+				T::ArbitratorOrigin::ensure_origin(origin)?;
+
+				Self::validate_shipment_id(&id)?;
+
+				let shipment = Shipments::<T>::get(&id).ok_or(Error::<T>::ShipmentDoesNotExist)?;
+				let dispute = Disputes::<T>::take(&id).ok_or(Error::<T>::DisputeDoesNotExist)?;
+				DisputableUntil::<T>::remove(&id);
+
+				if verdict != Verdict::Dismissed {
+					for (bottle, incident) in &dispute.claims {
+						BottleIncidents::insert(bottle, incident);
+					}
+				}
+
+				let refund = Self::redistribute_escrow(&shipment, &dispute, verdict)?;
+
+				Self::deposit_event(RawEvent::DisputeRuled(id, verdict, refund));
+
+				Ok(())
+			})
+		}
+
+		#[weight = <T as Config>::WeightInfo::inspect_shipment()]
+		pub fn inspect_shipment(
+			origin,
+			id: ShipmentId,
+		) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			Self::validate_shipment_id(&id)?;
+
+			let shipment = Self::validate_shipment_customs(&id, &who)?;
+
+			match shipment.status {
+				ShipmentStatus::InTransit | ShipmentStatus::Customs => (),
+				ShipmentStatus::Pending => Err(Error::<T>::ShipmentPending)?,
+				ShipmentStatus::Cancelled | ShipmentStatus::Rejected => Err(Error::<T>::ShipmentHasBeenCancelled)?,
+				_ => Err(Error::<T>::ShipmentHasBeenDelivered)?,
+			};
+
+			Self::deposit_event(RawEvent::ShipmentInspected(id, who));
+
+			Ok(())
+		}
+
+		#[weight = <T as Config>::WeightInfo::hold_shipment()]
+		pub fn hold_shipment(
+			origin,
+			id: ShipmentId,
+		) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			Self::validate_shipment_id(&id)?;
+
+			let shipment = Self::validate_shipment_customs(&id, &who)?;
+
+			ensure!(
+				!ShipmentClearances::<T>::contains_key(&id),
+				Error::<T>::ShipmentHasBeenCleared
+			);
+
+			match shipment.status {
+				ShipmentStatus::InTransit => (),
+				ShipmentStatus::Pending => Err(Error::<T>::ShipmentPending)?,
+				ShipmentStatus::Customs => Err(Error::<T>::ShipmentInCustoms)?,
+				ShipmentStatus::Cancelled | ShipmentStatus::Rejected => Err(Error::<T>::ShipmentHasBeenCancelled)?,
+				_ => Err(Error::<T>::ShipmentHasBeenDelivered)?,
+			};
+
+			Shipments::<T>::insert(&id, shipment.held_by_customs());
+
+			Self::deposit_event(RawEvent::ShipmentHeldByCustoms(id, who));
+
+			Ok(())
+		}
+
+		#[weight = <T as Config>::WeightInfo::clear_shipment(documents.len() as u32)]
+		pub fn clear_shipment(
+			origin,
+			id: ShipmentId,
+			reference: ClearanceReference,
+			documents: Vec<T::Hash>,
+		) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			Self::validate_shipment_id(&id)?;
+
+			Self::validate_clearance(&reference, &documents)?;
+
+			let shipment = Self::validate_shipment_customs(&id, &who)?;
+
+			ensure!(
+				shipment.status == ShipmentStatus::Customs,
+				Error::<T>::ShipmentNotHeldByCustoms
+			);
+
+			let clearance = Clearance {
+				reference: reference.clone(),
+				documents,
+				cleared_by: who.clone(),
+				cleared: <timestamp::Module<T>>::now(),
+			};
+
+			ShipmentClearances::<T>::insert(&id, clearance);
+			Shipments::<T>::insert(&id, shipment.released_by_customs());
+
+			Self::deposit_event(RawEvent::ShipmentClearedByCustoms(id, who, reference));
+
+			Ok(())
+		}
+
+		#[weight = <T as Config>::WeightInfo::sell_to_customer(bottles.len() as u32)]
+		pub fn sell_to_customer(
+			origin,
+			customer: T::AccountId,
+			bottles: Vec<BottleId>,
+		) -> dispatch::DispatchResult {
+			transactional(|| {
+				let who = ensure_signed(origin)?;
+
+				registrar::Module::<T>::validate_retailer(&who)?;
+
+				registrar::Module::<T>::validate_customer(&customer)?;
+
+				for (index, bottle) in bottles.iter().enumerate() {
+					ensure!(!bottles[..index].contains(bottle), Error::<T>::BottleListedTwice);
+					Self::is_bottle_sold(&bottle)?;
+					Self::is_bottle_intact(&bottle)?;
+					Self::validate_bottle_owner(bottle, &who)?;
+				}
+
+				for bottle in &bottles {
+					BottlesOfCustomer::<T>::append(&customer, &bottle);
+					BottleSoldTo::<T>::insert(&bottle, &customer);
+					registrar::Module::<T>::update_bottle_status(bottle, BottleStatus::SoldToCustomer)?;
+					registrar::Module::<T>::update_bottle_owner(bottle, customer.clone())?;
+				}
+
+				Self::deposit_event(RawEvent::BottlesSoldToCustomer(customer));
+
+				Ok(())
+			})
+		}
+
+		/// Records readings of the carrier devices travelling with shipments, which also
+		/// serve as scans of the shipments.
+		#[weight = <T as Config>::WeightInfo::report_telemetry(reports.len() as u32)]
+		pub fn report_telemetry(
+			origin,
+			reports: Vec<(ShipmentId, Telemetry)>,
+		) -> dispatch::DispatchResult {
+			transactional(|| {
+				let who = ensure_signed(origin)?;
+
+				registrar::Module::<T>::validate_carrier(&who)?;
+
+				ensure!(!reports.is_empty(), Error::<T>::TelemetryHasNoReports);
+				ensure!(reports.len() <= TELEMETRY_MAX_REPORTS, Error::<T>::TelemetryHasTooManyReports);
+
+				for (id, telemetry) in reports {
+					Self::validate_shipment_id(&id)?;
+					ensure!(telemetry.is_valid(), Error::<T>::InvalidTelemetry);
+
+					let shipment = Shipments::<T>::get(&id).ok_or(Error::<T>::ShipmentDoesNotExist)?;
+					ensure!(shipment.carrier == who, Error::<T>::NotShipmentCarrier);
+					match shipment.status {
+						ShipmentStatus::InTransit | ShipmentStatus::Customs => {},
+						ShipmentStatus::Pending => Err(Error::<T>::ShipmentPending)?,
+						ShipmentStatus::Delivered | ShipmentStatus::Stored => Err(Error::<T>::ShipmentHasBeenDelivered)?,
+						ShipmentStatus::Cancelled | ShipmentStatus::Rejected => Err(Error::<T>::ShipmentHasBeenCancelled)?,
+					}
+
+					// Readings may reach the chain out of order, keep the latest one.
+					if LastTelemetry::get(&id).map_or(true, |last| last.recorded < telemetry.recorded) {
+						LastTelemetry::insert(&id, &telemetry);
+					}
+					Self::deposit_event(RawEvent::ShipmentTelemetryReported(id, who.clone(), telemetry));
+				}
+
+				Ok(())
+			})
+		}
+
+		#[weight = <T as Config>::WeightInfo::register_device()]
+		pub fn register_device(origin, device: T::AccountId) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			registrar::Module::<T>::validate_carrier(&who)?;
+
+			ensure!(!Devices::<T>::contains_key(&device), Error::<T>::DeviceAlreadyRegistered);
+
+			Devices::<T>::insert(&device, Device { carrier: who.clone(), window: Zero::zero(), scans: 0 });
+
+			Self::deposit_event(RawEvent::DeviceRegistered(who, device));
+
+			Ok(())
+		}
+
+		#[weight = <T as Config>::WeightInfo::remove_device()]
+		pub fn remove_device(origin, device: T::AccountId) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			let registered = Devices::<T>::get(&device).ok_or(Error::<T>::DeviceNotRegistered)?;
+			ensure!(registered.carrier == who, Error::<T>::NotDeviceCarrier);
+
+			Devices::<T>::remove(&device);
+
+			Self::deposit_event(RawEvent::DeviceRemoved(who, device));
+
+			Ok(())
+		}
+
+		/// Records a scan of a shipment by a carrier device. The transaction is unsigned, so
+		/// that devices need no funds, and `validate_unsigned` checks the signature of the
+		/// device over the payload instead.
+		#[weight = <T as Config>::WeightInfo::submit_scan_unsigned()]
+		pub fn submit_scan_unsigned(
+			origin,
+			payload: ScanPayload<T::Public, T::Hash>,
+			_signature: T::Signature,
+		) -> dispatch::DispatchResult {
+			ensure_none(origin)?;
+
+			let (device, counted) = Self::count_scan(&payload)?;
+			ensure!(payload.nonce == DeviceNonces::<T>::get(&device), Error::<T>::InvalidScanNonce);
+
+			DeviceNonces::<T>::insert(&device, payload.nonce.saturating_add(1));
+			let carrier = counted.carrier.clone();
+			Devices::<T>::insert(&device, counted);
+
+			Self::deposit_event(RawEvent::ShipmentScanned(payload.shipment, carrier, device));
+
+			Ok(())
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Registers a shipment of the sender's bottles, handing them over to the carrier.
+	fn add_shipment(
+		id: ShipmentId,
+		sender: &T::AccountId,
+		carrier: T::AccountId,
+		receiver: T::AccountId,
+		customs: Option<T::AccountId>,
+		deadline: Option<T::BlockNumber>,
+		bottles: Vec<BottleId>,
+	) -> dispatch::DispatchResult {
+		Self::validate_shipment_id(&id)?;
+
+		Self::validate_new_shipment(&id)?;
+
+		Self::validate_shipment_parties(sender, &receiver)?;
+
+		registrar::Module::<T>::validate_carrier(&carrier)?;
+
+		if let Some(customs) = &customs {
+			registrar::Module::<T>::validate_customs(customs)?;
+		}
+
+		if let Some(deadline) = deadline {
+			ensure!(deadline > <frame_system::Module<T>>::block_number(), Error::<T>::DeadlineInPast);
+		}
+
+		Self::validate_shipment_bottles(&bottles, sender)?;
+
+		let shipment = Self::new_shipment()
+			.identified_by(id.clone())
+			.shipped_by(sender.clone())
+			.carried_by(carrier.clone())
+			.sent_to(receiver.clone())
+			.with_bottles(bottles)
+			.through_customs(customs)
+			.due_by(deadline)
+			.registered_at(<timestamp::Module<T>>::now())
+			.build();
+
+		for bottle in &shipment.bottles {
+			BottleOfShipment::insert(&bottle, &id);
+			registrar::Module::<T>::update_bottle_status(bottle, BottleStatus::ShipmentRegistered)?;
+			registrar::Module::<T>::update_bottle_owner(bottle, carrier.clone())?;
+		}
+
+		Shipments::<T>::insert(&id, shipment);
+		ShipmentsOfSender::<T>::append(sender, &id);
+		ShipmentsOfCarrier::<T>::append(&carrier, &id);
+		ShipmentsOfReceiver::<T>::append(&receiver, &id);
+
+		if let Some(deadline) = deadline {
+			ShipmentsDueAt::<T>::append(deadline, &id);
+		}
+
+		Ok(())
+	}
+
+	pub fn new_shipment() -> ShipmentBuilder<T::AccountId, T::Moment, T::BlockNumber> {
+		ShipmentBuilder::<T::AccountId, T::Moment, T::BlockNumber>::default()
+	}
+
+	pub fn validate_new_shipment(id: &ShipmentId) -> dispatch::DispatchResult {
+		ensure!(
+			!Shipments::<T>::contains_key(id.clone()), 
+			Error::<T>::ShipmentIdExists
+		);
+		Ok(())
+	}
+
+	/// Checks that the device that signed the scan may scan the shipment, and returns the
+	/// device account and the device with the scan counted.
+	fn count_scan(
+		payload: &ScanPayload<T::Public, T::Hash>,
+	) -> Result<(T::AccountId, Device<T::AccountId, T::BlockNumber>), dispatch::DispatchError> {
+		ensure!(
+			payload.genesis == <frame_system::Module<T>>::block_hash(T::BlockNumber::zero()),
+			Error::<T>::ScanForAnotherChain
+		);
+		let account = payload.device.clone().into_account();
+		let mut device = Devices::<T>::get(&account).ok_or(Error::<T>::DeviceNotRegistered)?;
+
+		let now = <frame_system::Module<T>>::block_number();
+		if now >= device.window.saturating_add(T::DeviceScanWindow::get()) {
+			device.window = now;
+			device.scans = 0;
+		}
+		ensure!(device.scans < T::MaxDeviceScans::get(), Error::<T>::TooManyScans);
+		device.scans += 1;
+
+		Self::validate_shipment_id(&payload.shipment)?;
+		let shipment = Shipments::<T>::get(&payload.shipment).ok_or(Error::<T>::ShipmentDoesNotExist)?;
+		match shipment.status {
+			ShipmentStatus::Delivered | ShipmentStatus::Stored => Err(Error::<T>::ShipmentHasBeenDelivered)?,
+			ShipmentStatus::Cancelled | ShipmentStatus::Rejected => Err(Error::<T>::ShipmentHasBeenCancelled)?,
+			_ => {},
+		}
+		ensure!(shipment.carrier == device.carrier, Error::<T>::NotShipmentCarrier);
+
+		Ok((account, device))
+	}
+
+	pub fn validate_shipment_id(id: &[u8]) -> dispatch::DispatchResult {
+		ensure!(!id.is_empty(), Error::<T>::ShipmentIdMissing);
+		ensure!(id.len() <= SHIPMENT_ID_MAX_LENGTH, Error::<T>::ShipmentIdTooLong);
+		Ok(())
+	}
+
+	/// Flags every shipment due at `now` that has not been delivered yet.
+	pub fn check_overdue_shipments(now: T::BlockNumber) -> Weight {
+		let due = ShipmentsDueAt::<T>::take(now);
+		let mut overdue: u64 = 0;
+
+		for id in &due {
+			if let Some(shipment) = Shipments::<T>::get(id) {
+				if !shipment.is_completed() && !shipment.is_cancelled() {
+					Shipments::<T>::insert(id, shipment.mark_overdue());
+					Self::deposit_event(RawEvent::ShipmentOverdue(id.clone(), now));
+					overdue += 1;
+				}
+			}
+		}
+
+		T::DbWeight::get().reads_writes(1 + due.len() as u64, 1 + overdue)
+	}
+
+	/// Returns the shipment if it has not been picked up yet.
+	pub fn validate_pending_shipment(
+		id: &ShipmentId,
+	) -> Result<Shipment<T::AccountId, T::Moment, T::BlockNumber>, dispatch::DispatchError> {
+		let shipment = Shipments::<T>::get(id).ok_or(Error::<T>::ShipmentDoesNotExist)?;
+
+		match shipment.status {
+			ShipmentStatus::Pending => Ok(shipment),
+			ShipmentStatus::Delivered | ShipmentStatus::Stored => Err(Error::<T>::ShipmentHasBeenDelivered)?,
+			ShipmentStatus::Cancelled | ShipmentStatus::Rejected => Err(Error::<T>::ShipmentHasBeenCancelled)?,
+			_ => Err(Error::<T>::ShipmentInTransit)?,
+		}
+	}
+
+	/// Checks customs clearance, settles the escrow, opens the dispute period and marks the
+	/// shipment as delivered, or as stored when the receiver is a warehouse.
+	pub fn complete_delivery(
+		shipment: Shipment<T::AccountId, T::Moment, T::BlockNumber>,
+	) -> Result<Shipment<T::AccountId, T::Moment, T::BlockNumber>, dispatch::DispatchError> {
+		if shipment.customs.is_some() {
+			ensure!(ShipmentClearances::<T>::contains_key(&shipment.id), Error::<T>::ShipmentNotCleared);
+		}
+
+		Self::settle_escrow(&shipment)?;
+
+		let disputable_until = <frame_system::Module<T>>::block_number().saturating_add(T::DisputePeriod::get());
+		DisputableUntil::<T>::insert(&shipment.id, disputable_until);
+
+		// Bottles missing on receipt stay with the carrier, in transit.
+		for bottle in shipment.bottles.iter().filter(|b| !shipment.missing.contains(b)) {
+			registrar::Module::<T>::update_bottle_status(bottle, BottleStatus::ShipmentDelivered)?;
+			registrar::Module::<T>::update_bottle_owner(bottle, shipment.receiver.clone())?;
+		}
+
+		let now = <timestamp::Module<T>>::now();
+
+		match registrar::Module::<T>::validate_warehouse(&shipment.receiver) {
+			Ok(()) => Ok(shipment.stored(now)),
+			Err(_) => Ok(shipment.delivered(now)),
+		}
+	}
+
+	pub fn return_bottles_to_sender(shipment: &Shipment<T::AccountId, T::Moment, T::BlockNumber>) -> dispatch::DispatchResult {
+		for bottle in &shipment.bottles {
+			registrar::Module::<T>::return_bottle(bottle, shipment.sender.clone())?;
+		}
+
+		Ok(())
+	}
+
+	/// Pays the receiver's reserved price to the sender and the sender's reserved fee to the carrier.
+	/// Payments stay reserved on the payees until the dispute period is over. The share of the
+	/// price matching bottles missing on receipt is returned to the receiver. Fails if the
+	/// receiver has not reserved the price.
+	pub fn settle_escrow(shipment: &Shipment<T::AccountId, T::Moment, T::BlockNumber>) -> dispatch::DispatchResult {
+		if let Some(mut escrow) = ShipmentEscrows::<T>::get(&shipment.id) {
+			ensure!(escrow.funded, Error::<T>::ShipmentNotFunded);
+
+			let ShipmentPayment { price, carrier_fee } = escrow.payment.clone();
+			let missing_share = Perbill::from_rational_approximation(
+				shipment.missing.len() as u32,
+				shipment.bottles.len() as u32,
+			);
+			let refund = missing_share * price;
+			let price = price.saturating_sub(refund);
+
+			// Reserves may have been slashed since they were made, so the escrow records what
+			// was actually moved rather than what was agreed. The fee is moved before the price
+			// lands in the sender's reserve, and the price before the refund, so that what a
+			// slash leaves short is missing from the payment of the slashed account.
+			let unpaid = T::Currency::repatriate_reserved(
+				&shipment.sender, &shipment.carrier, carrier_fee, BalanceStatus::Reserved,
+			)?;
+			escrow.payment.carrier_fee = carrier_fee.saturating_sub(unpaid);
+
+			let unpaid = T::Currency::repatriate_reserved(
+				&shipment.receiver, &shipment.sender, price, BalanceStatus::Reserved,
+			)?;
+			escrow.payment.price = price.saturating_sub(unpaid);
+			T::Currency::unreserve(&shipment.receiver, refund);
+
+			let held_until = <frame_system::Module<T>>::block_number().saturating_add(T::DisputePeriod::get());
+			escrow.held_until = Some(held_until);
+
+			ShipmentEscrows::<T>::insert(&shipment.id, escrow);
+			EscrowsHeldUntil::<T>::append(held_until, &shipment.id);
+
+			Self::deposit_event(RawEvent::EscrowHeld(shipment.id.clone(), held_until));
+		}
+		Ok(())
+	}
+
+	/// Releases held payments whose dispute period ends at `now`, unless a dispute is open.
+	pub fn release_held_escrows(now: T::BlockNumber) -> Weight {
+		let held = EscrowsHeldUntil::<T>::take(now);
+		let mut released: u64 = 0;
+
+		for id in &held {
+			if Disputes::<T>::contains_key(id) {
+				continue;
+			}
+			if let (Some(shipment), Some(escrow)) = (Shipments::<T>::get(id), ShipmentEscrows::<T>::get(id)) {
+				if escrow.held_until.is_some() {
+					Self::release_escrow(&shipment, escrow);
+					released += 1;
+				}
+			}
+		}
+
+		T::DbWeight::get().reads_writes(1 + 3 * held.len() as u64, 1 + 3 * released)
+	}
+
+	/// Unreserves the held payment on the payees. The escrow is kept, without a holding
+	/// period, as a record that the shipment was paid for and can no longer be disputed.
+	fn release_escrow(
+		shipment: &Shipment<T::AccountId, T::Moment, T::BlockNumber>,
+		escrow: Escrow<BalanceOf<T>, T::BlockNumber>,
+	) {
+		let ShipmentPayment { price, carrier_fee } = escrow.payment.clone();
+
+		if escrow.funded {
+			T::Currency::unreserve(&shipment.sender, price);
+		}
+		T::Currency::unreserve(&shipment.carrier, carrier_fee);
+
+		ShipmentEscrows::<T>::insert(&shipment.id, Escrow { held_until: None, ..escrow });
+
+		Self::deposit_event(RawEvent::EscrowReleased(shipment.id.clone(), price, carrier_fee));
+	}
+
+	/// Applies a dispute ruling to the held payment of a shipment, if any. The receiver is
+	/// refunded the share of the price matching the claimed bottles, and a liable carrier
+	/// forfeits its fee to the sender. Returns the refunded amount.
+	pub fn redistribute_escrow(
+		shipment: &Shipment<T::AccountId, T::Moment, T::BlockNumber>,
+		dispute: &Dispute<T::AccountId, T::Hash, T::BlockNumber>,
+		verdict: Verdict,
+	) -> Result<BalanceOf<T>, dispatch::DispatchError> {
+		let mut escrow = match ShipmentEscrows::<T>::get(&shipment.id) {
+			Some(escrow) if escrow.held_until.is_some() => escrow,
+			_ => return Ok(0u32.into()),
+		};
+
+		let mut refund: BalanceOf<T> = 0u32.into();
+
+		if verdict != Verdict::Dismissed && escrow.funded {
+			// The price held is that of the bottles delivered, the missing ones were refunded.
+			let share = Perbill::from_rational_approximation(
+				dispute.claims.len() as u32,
+				(shipment.bottles.len() - shipment.missing.len()) as u32,
+			);
+			refund = share * escrow.payment.price;
+
+			T::Currency::repatriate_reserved(&shipment.sender, &shipment.receiver, refund, BalanceStatus::Free)?;
+			escrow.payment.price = escrow.payment.price.saturating_sub(refund);
+		}
+
+		if verdict == Verdict::CarrierLiable {
+			let carrier_fee = escrow.payment.carrier_fee;
+			T::Currency::repatriate_reserved(&shipment.carrier, &shipment.sender, carrier_fee, BalanceStatus::Free)?;
+			escrow.payment.carrier_fee = 0u32.into();
+		}
+
+		Self::release_escrow(shipment, escrow);
+
+		Ok(refund)
+	}
+
+	pub fn validate_dispute_claims(
+		shipment: &Shipment<T::AccountId, T::Moment, T::BlockNumber>,
+		claims: &[(BottleId, BottleIncident)],
+	) -> dispatch::DispatchResult {
+		ensure!(!claims.is_empty(), Error::<T>::DisputeHasNoClaims);
+		ensure!(claims.len() <= shipment.bottles.len(), Error::<T>::DisputeHasTooManyClaims);
+
+		for (index, (bottle, _)) in claims.iter().enumerate() {
+			ensure!(shipment.bottles.contains(bottle), Error::<T>::BottleNotInShipment);
+			// Missing bottles were refunded on receipt already.
+			ensure!(!shipment.missing.contains(bottle), Error::<T>::BottleMissingOnReceipt);
+			ensure!(
+				!claims[..index].iter().any(|(claimed, _)| claimed == bottle),
+				Error::<T>::BottleClaimedTwice
+			);
+		}
+
+		Ok(())
+	}
+
+	pub fn validate_dispute_evidence(evidence: &[T::Hash]) -> dispatch::DispatchResult {
+		ensure!(!evidence.is_empty(), Error::<T>::DisputeHasNoEvidence);
+		ensure!(evidence.len() <= DISPUTE_MAX_EVIDENCE, Error::<T>::DisputeHasTooMuchEvidence);
+		Ok(())
+	}
+
+	/// Returns reserved funds to the sender and, if the shipment was funded, to the receiver.
+	pub fn refund_escrow(shipment: &Shipment<T::AccountId, T::Moment, T::BlockNumber>) {
+		if let Some(escrow) = ShipmentEscrows::<T>::take(&shipment.id) {
+			T::Currency::unreserve(&shipment.sender, escrow.payment.carrier_fee);
+
+			if escrow.funded {
+				T::Currency::unreserve(&shipment.receiver, escrow.payment.price);
+			}
+		}
+	}
+
+	/// Returns the shipment if `account` is the customs authority designated for it.
+	pub fn validate_shipment_customs(
+		id: &ShipmentId,
+		account: &T::AccountId,
+	) -> Result<Shipment<T::AccountId, T::Moment, T::BlockNumber>, dispatch::DispatchError> {
+		let shipment = Shipments::<T>::get(id).ok_or(Error::<T>::ShipmentDoesNotExist)?;
+
+		match &shipment.customs {
+			None => Err(Error::<T>::ShipmentHasNoCustoms)?,
+			Some(customs) => ensure!(customs == account, Error::<T>::NotShipmentCustoms),
+		};
+
+		Ok(shipment)
+	}
+
+	pub fn validate_clearance(reference: &[u8], documents: &[T::Hash]) -> dispatch::DispatchResult {
+		ensure!(!reference.is_empty(), Error::<T>::ClearanceReferenceMissing);
+		ensure!(reference.len() <= CLEARANCE_REFERENCE_MAX_LENGTH, Error::<T>::ClearanceReferenceTooLong);
+		ensure!(!documents.is_empty(), Error::<T>::ClearanceHasNoDocuments);
+		ensure!(documents.len() <= CLEARANCE_MAX_DOCUMENTS, Error::<T>::ClearanceHasTooManyDocuments);
+		Ok(())
+	}
+
+	pub fn validate_shipment_parties(sender: &T::AccountId, receiver: &T::AccountId) -> dispatch::DispatchResult {
+		ensure!(sender != receiver, Error::<T>::ShipmentToSelf);
+		ensure!(Self::is_stock_holder(sender), Error::<T>::NotStockHolder);
+		ensure!(Self::is_stock_holder(receiver), Error::<T>::InvalidShipmentReceiver);
+		Ok(())
+	}
+
+	/// Members that can hold bottles in stock, and thus send and receive shipments.
+	/// Carriers only move bottles, customs only inspect them and customers buy them.
+	pub fn is_stock_holder(account: &T::AccountId) -> bool {
+		match registrar::Module::<T>::member_type_of(account) {
+			Some(MemberType::Manufacturer)
+			| Some(MemberType::Distributor)
+			| Some(MemberType::Warehouse)
+			| Some(MemberType::Retailer) => true,
+			_ => false,
+		}
+	}
+
+	pub fn validate_shipment_bottles(bottles: &[BottleId], sender: &T::AccountId) -> dispatch::DispatchResult {
+
+		ensure!(
+			bottles.len() > 0,
+			Error::<T>::ShipmentHasNoBottles,
+		);
+
+		ensure!(
+            bottles.len() <= SHIPMENT_MAX_BOTTLES,
+            Error::<T>::ShipmentHasTooManyBottles,
+        );
+
+		for (index, bottle) in bottles.iter().enumerate() {
+			ensure!(!bottles[..index].contains(bottle), Error::<T>::BottleListedTwice);
+			registrar::Module::<T>::check_bottle_id_present(&bottle)?;
+			Self::is_bottle_sold(&bottle)?;
+			Self::is_bottle_intact(&bottle)?;
+			Self::check_bottle_custody(&bottle, sender)?;
+		}
+
+        Ok(())
+    }
+
+	/// Bottles on an open shipment can't be shipped again, otherwise the account
+	/// has to be the bottle's owner in the registrar.
+	pub fn check_bottle_custody(bottle_id: &BottleId, account: &T::AccountId) -> dispatch::DispatchResult {
+		if let Some(shipment_id) = BottleOfShipment::get(bottle_id) {
+			match Shipments::<T>::get(&shipment_id) {
+				None => Err(Error::<T>::ShipmentDoesNotExist)?,
+				Some(sp) if sp.holder().is_none() => Err(Error::<T>::BottleAlreadyShipped)?,
+				Some(_) => (),
+			}
+		}
+
+		registrar::Module::<T>::check_bottle_owner(bottle_id, account)
+	}
+
+	pub fn is_bottle_intact(bottle_id: &BottleId) -> dispatch::DispatchResult {
+		ensure!(!BottleIncidents::contains_key(bottle_id), Error::<T>::BottleLostOrDamaged);
+		Ok(())
+	}
+
+	pub fn is_bottle_sold(bottle_id: &BottleId) -> dispatch::DispatchResult{
+		match BottleSoldTo::<T>::get(bottle_id) {
+			None => Ok(()),
+			_ => Err(Error::<T>::BottleAlreadySold)?,
+		}
+	}
+
+
+	pub fn validate_bottle_owner(bottle_id: &BottleId, account: &T::AccountId) -> dispatch::DispatchResult {
+		
+		registrar::Module::<T>::check_bottle_id_present(bottle_id)?;
+		
+		let shipment_id: ShipmentId = match BottleOfShipment::get(bottle_id) {
+			None => Err(Error::<T>::BottleNotShipped),
+			Some(sp) => Ok(sp),
+		}?;
+
+		match Shipments::<T>::get(&shipment_id) {
+			None => Err(Error::<T>::ShipmentDoesNotExist)?,
+			Some(sp) => match sp.status {
+				ShipmentStatus::Pending => Err(Error::<T>::ShipmentPending)?,
+				ShipmentStatus::InTransit | ShipmentStatus::Customs => Err(Error::<T>::ShipmentInTransit)?,
+				_ => registrar::Module::<T>::check_bottle_owner(bottle_id, account),
+			}
+		}
+	}
+}
+
+impl<T: SigningTypes> SignedPayload<T> for ScanPayload<T::Public, T::Hash> {
+	fn public(&self) -> T::Public {
+		self.device.clone()
+	}
+}
+
+impl<T: Config> ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	/// Accepts the scans signed by registered devices. Scans of a device chain by nonce, so
+	/// that a burst of scans can wait in the pool for the earlier ones to get in.
+	fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+		let (payload, signature) = match call {
+			Call::submit_scan_unsigned(payload, signature) => (payload, signature),
+			_ => return InvalidTransaction::Call.into(),
+		};
+
+		if !SignedPayload::<T>::verify::<T::DeviceId>(payload, signature.clone()) {
+			return InvalidTransaction::BadProof.into();
+		}
+
+		let (device, _) = Self::count_scan(payload).map_err(|e| {
+			if e == Error::<T>::TooManyScans.into() {
+				InvalidTransaction::ExhaustsResources
+			} else if e == Error::<T>::ScanForAnotherChain.into() {
+				InvalidTransaction::BadProof
+			} else {
+				InvalidTransaction::Call
+			}
+		})?;
+
+		let next = DeviceNonces::<T>::get(&device);
+		if payload.nonce < next {
+			return InvalidTransaction::Stale.into();
+		}
+		// A device cannot scan more than its window allows, so further nonces are not pooled.
+		if payload.nonce - next > T::MaxDeviceScans::get() as u64 {
+			return InvalidTransaction::Future.into();
+		}
+
+		let mut valid = ValidTransaction::with_tag_prefix("BottleTrackingScan")
+			.priority(T::UnsignedScanPriority::get())
+			.and_provides((device.clone(), payload.nonce))
+			.longevity(T::DeviceScanWindow::get().saturated_into::<u64>())
+			.propagate(true);
+		if payload.nonce > next {
+			valid = valid.and_requires((device, payload.nonce - 1));
+		}
+		valid.build()
+	}
+}
@@ -0,0 +1,200 @@
+//! Storage migrations of the bottle-tracking pallet.
+//!
+//! The registrar is migrated first, as putting bottles back where the shipments left them
+//! relies on its current layout.
+
+use frame_support::{
+	traits::Get, weights::Weight, storage::{IterableStorageMap, migration::remove_storage_prefix},
+	sp_std::prelude::*, StorageMap, StorageValue,
+};
+use registrar::{BottleId, BottleStatus, MemberType};
+use crate::{
+	Config, Shipments, ShipmentsOfSender, ShipmentsOfCarrier, ShipmentsOfReceiver, BottleOfShipment,
+	BottlesOfCustomer, BottleSoldTo, StorageVersion, types::*,
+};
+
+/// Layout of the first release.
+pub mod v1 {
+	use codec::{Encode, Decode};
+	use frame_support::sp_std::prelude::*;
+	use registrar::BottleId;
+	use crate::types::{ShipmentId, ShipmentStatus};
+
+	/// Shipments went from a manufacturer to a retailer. `ShipmentStatus` only had its first
+	/// three variants, which encode the same today.
+	#[derive(Debug, PartialEq, Encode, Decode)]
+	pub struct Shipment<AccountId, Moment> {
+		pub id: ShipmentId,
+		pub manufacturer: AccountId,
+		pub carrier: AccountId,
+		pub retailer: AccountId,
+		pub bottles: Vec<BottleId>,
+		pub status: ShipmentStatus,
+		pub registered: Moment,
+		pub delivered: Option<Moment>,
+	}
+}
+
+pub fn migrate<T: Config>() -> Weight {
+	let weight = registrar::migrations::migrate::<T>();
+
+	weight.saturating_add(match StorageVersion::get() {
+		Releases::V1_0_0 => migrate_to_v2::<T>(),
+		Releases::V2_0_0 => T::DbWeight::get().reads(1),
+	})
+}
+
+/// Upgrades the shipments, rebuilds the shipment indexes under their new names and hands
+/// every bottle to whoever its last shipment or sale left it with.
+fn migrate_to_v2<T: Config>() -> Weight {
+	Shipments::<T>::translate::<v1::Shipment<T::AccountId, T::Moment>, _>(|_, old| Some(upgrade_shipment::<T>(old)));
+
+	remove_storage_prefix(b"BottleTracking", b"ShipmentsOfManufacturer", &[]);
+	remove_storage_prefix(b"BottleTracking", b"ShipmentsOfRetailer", &[]);
+
+	// Indexes keep the shipments in the order they were registered.
+	let mut shipments: Vec<_> = Shipments::<T>::iter().map(|(_, shipment)| shipment).collect();
+	shipments.sort_by(|a, b| a.registered.cmp(&b.registered).then_with(|| a.id.cmp(&b.id)));
+
+	let mut writes: Weight = 0;
+	for shipment in &shipments {
+		ShipmentsOfSender::<T>::append(&shipment.sender, &shipment.id);
+		ShipmentsOfCarrier::<T>::append(&shipment.carrier, &shipment.id);
+		ShipmentsOfReceiver::<T>::append(&shipment.receiver, &shipment.id);
+		for bottle in &shipment.bottles {
+			BottleOfShipment::insert(bottle, &shipment.id);
+		}
+		writes = writes.saturating_add(3).saturating_add(shipment.bottles.len() as Weight);
+	}
+
+	let bottles: Vec<(BottleId, T::AccountId)> = registrar::Bottles::<T>::iter()
+		.map(|(id, bottle)| (id, bottle.owner().clone()))
+		.collect();
+
+	let mut weight: Weight = 0;
+	for (id, owner) in &bottles {
+		if let Some((owner, status)) = custody_of::<T>(id, owner) {
+			weight = weight.saturating_add(registrar::migrations::restore_bottle::<T>(id, owner, status));
+		}
+	}
+
+	StorageVersion::put(Releases::V2_0_0);
+
+	let reads = (shipments.len() as Weight).saturating_add(bottles.len() as Weight).saturating_mul(3);
+	weight.saturating_add(T::DbWeight::get().reads_writes(reads.saturating_add(1), writes.saturating_add(3)))
+}
+
+fn upgrade_shipment<T: Config>(
+	old: v1::Shipment<T::AccountId, T::Moment>,
+) -> Shipment<T::AccountId, T::Moment, T::BlockNumber> {
+	Shipment {
+		id: old.id,
+		sender: old.manufacturer,
+		carrier: old.carrier,
+		receiver: old.retailer,
+		bottles: old.bottles,
+		missing: Vec::new(),
+		customs: None,
+		status: old.status,
+		registered: old.registered,
+		delivered: old.delivered,
+		deadline: None,
+		overdue: false,
+	}
+}
+
+/// Where the bottle is, if it left its manufacturer's stock. The parachain did not record
+/// sales apart from the owner, so a bottle owned by a customer is recorded as sold to it.
+fn custody_of<T: Config>(id: &BottleId, owner: &T::AccountId) -> Option<(T::AccountId, BottleStatus)> {
+	if let Some(customer) = BottleSoldTo::<T>::get(id) {
+		return Some((customer, BottleStatus::SoldToCustomer));
+	}
+
+	if registrar::Module::<T>::member_type_of(owner) == Some(MemberType::Customer) {
+		BottleSoldTo::<T>::insert(id, owner);
+		BottlesOfCustomer::<T>::append(owner, id);
+		return Some((owner.clone(), BottleStatus::SoldToCustomer));
+	}
+
+	let shipment = Shipments::<T>::get(BottleOfShipment::get(id)?)?;
+	match shipment.status {
+		ShipmentStatus::Pending => Some((shipment.carrier, BottleStatus::ShipmentRegistered)),
+		ShipmentStatus::InTransit => Some((shipment.carrier, BottleStatus::ShipmentInTransit)),
+		ShipmentStatus::Delivered | ShipmentStatus::Stored => Some((shipment.receiver, BottleStatus::ShipmentDelivered)),
+		_ => None,
+	}
+}
+
+/// Checks that every shipment a bottle is on is stored in the layout the migration expects.
+#[cfg(any(test, feature = "try-runtime"))]
+pub fn pre_migrate<T: Config>() -> Result<(), &'static str> {
+	use codec::Encode;
+	use frame_support::{storage::migration::get_storage_value, Blake2_128Concat, StorageHasher};
+
+	// The registrar only exports its checks to runtimes trying the upgrade.
+	#[cfg(feature = "try-runtime")]
+	registrar::migrations::pre_migrate::<T>()?;
+
+	if StorageVersion::get() != Releases::V1_0_0 {
+		return Ok(());
+	}
+
+	for (_, shipment_id) in BottleOfShipment::iter() {
+		let old = get_storage_value::<v1::Shipment<T::AccountId, T::Moment>>(
+			b"BottleTracking", b"Shipments", &Blake2_128Concat::hash(&shipment_id.encode()),
+		);
+		if old.is_none() {
+			return Err("shipment not stored in the v1 layout");
+		}
+	}
+
+	Ok(())
+}
+
+/// Checks that shipments are indexed under their parties and that the registrar agrees with
+/// the shipments on where every bottle is.
+#[cfg(any(test, feature = "try-runtime"))]
+pub fn post_migrate<T: Config>() -> Result<(), &'static str> {
+	use frame_support::storage::migration::StorageIterator;
+
+	#[cfg(feature = "try-runtime")]
+	registrar::migrations::post_migrate::<T>()?;
+
+	if StorageVersion::get() != Releases::V2_0_0 {
+		return Err("storage version not bumped");
+	}
+
+	if StorageIterator::<Vec<ShipmentId>>::new(b"BottleTracking", b"ShipmentsOfManufacturer").next().is_some()
+		|| StorageIterator::<Vec<ShipmentId>>::new(b"BottleTracking", b"ShipmentsOfRetailer").next().is_some()
+	{
+		return Err("v1 shipment indexes left behind");
+	}
+
+	for (id, shipment) in Shipments::<T>::iter() {
+		if !ShipmentsOfSender::<T>::get(&shipment.sender).contains(&id)
+			|| !ShipmentsOfCarrier::<T>::get(&shipment.carrier).contains(&id)
+			|| !ShipmentsOfReceiver::<T>::get(&shipment.receiver).contains(&id)
+		{
+			return Err("shipment not indexed under its parties");
+		}
+	}
+
+	for (bottle_id, shipment_id) in BottleOfShipment::iter() {
+		let shipment = Shipments::<T>::get(&shipment_id).ok_or("bottle on a missing shipment")?;
+		if !shipment.bottles.contains(&bottle_id) {
+			return Err("bottle indexed under a shipment it is not on");
+		}
+	}
+
+	for (id, bottle) in registrar::Bottles::<T>::iter() {
+		let sold_to = BottleSoldTo::<T>::get(&id);
+		if sold_to.is_some() != (bottle.status() == &BottleStatus::SoldToCustomer) {
+			return Err("sale not reflected in the bottle status");
+		}
+		if sold_to.map_or(false, |customer| &customer != bottle.owner()) {
+			return Err("sold bottle not owned by its customer");
+		}
+	}
+
+	Ok(())
+}
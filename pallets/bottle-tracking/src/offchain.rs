@@ -0,0 +1,202 @@
+//! Offchain worker reporting the telemetry of carrier devices.
+//!
+//! A carrier puts its account key into the keystore of a node under the `cdev` key type,
+//! and the URL of the feed of its devices into the persistent offchain storage of the node
+//! under `feed_key(carrier)`. Every `FeedPollInterval` blocks, the worker fetches the feed,
+//! keeps the readings of the shipments the carrier has on the road that it has not reported
+//! yet, and reports them in a `report_telemetry` transaction signed with the carrier key.
+//!
+//! Feeds answer with a JSON array of readings:
+//!
+//! ```json
+//! [{"shipment": "shipment-1", "latitude": 49.2583, "longitude": 4.0317, "temperature": 11.5, "timestamp": 1700000000000}]
+//! ```
+
+use core::convert::TryFrom;
+use codec::Encode;
+use frame_support::{debug, traits::Get, sp_std::prelude::*, StorageMap};
+use frame_system::offchain::{AppCrypto, SendSignedTransaction, Signer};
+use lite_json::{JsonValue, NumberValue};
+use sp_core::crypto::KeyTypeId;
+use sp_runtime::{
+	offchain::{http, storage::StorageValueRef, Duration, StorageKind},
+	traits::{IdentifyAccount, Zero},
+	RuntimeAppPublic, RuntimeDebug,
+};
+
+use crate::{types::*, Call, Config, Shipments, TELEMETRY_MAX_REPORTS};
+
+/// Key type of the carrier keys the devices report with.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"cdev");
+
+const FEED_PREFIX: &[u8] = b"bottle-tracking::feed::";
+const CURSOR_PREFIX: &[u8] = b"bottle-tracking::feed-cursor::";
+const FEED_TIMEOUT_MILLIS: u64 = 2_000;
+
+pub mod crypto {
+	use super::KEY_TYPE;
+	use sp_runtime::{app_crypto::{app_crypto, sr25519}, MultiSignature, MultiSigner};
+
+	app_crypto!(sr25519, KEY_TYPE);
+
+	/// Carrier keys of the runtimes with `MultiSignature` accounts.
+	pub struct DeviceId;
+
+	impl frame_system::offchain::AppCrypto<MultiSigner, MultiSignature> for DeviceId {
+		type RuntimeAppPublic = Public;
+		type GenericSignature = sp_core::sr25519::Signature;
+		type GenericPublic = sp_core::sr25519::Public;
+	}
+}
+
+/// Key of the feed URL of the carrier in the persistent offchain storage. The URL is stored
+/// as is, not SCALE-encoded.
+pub fn feed_key<AccountId: Encode>(carrier: &AccountId) -> Vec<u8> {
+	[FEED_PREFIX, &carrier.encode()[..]].concat()
+}
+
+/// Key of the time of the latest reading reported for the carrier.
+fn cursor_key<AccountId: Encode>(carrier: &AccountId) -> Vec<u8> {
+	[CURSOR_PREFIX, &carrier.encode()[..]].concat()
+}
+
+/// A reading of a device, as its feed reports it.
+#[derive(Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Reading {
+	pub shipment: ShipmentId,
+	pub telemetry: Telemetry,
+}
+
+/// Reports the readings of the feeds of the carrier keys in the keystore.
+pub fn poll_device_feeds<T: Config>(now: T::BlockNumber) {
+	let interval = T::FeedPollInterval::get();
+	if interval.is_zero() || !(now % interval).is_zero() {
+		return;
+	}
+
+	for key in <T::DeviceId as AppCrypto<T::Public, T::Signature>>::RuntimeAppPublic::all() {
+		let generic: <T::DeviceId as AppCrypto<T::Public, T::Signature>>::GenericPublic = key.into();
+		let public: T::Public = generic.into();
+		let carrier = public.clone().into_account();
+		if let Err(e) = poll_feed::<T>(public, &carrier) {
+			debug::warn!("Cannot report the telemetry of carrier {:?}: {}", carrier, e);
+		}
+	}
+}
+
+fn poll_feed<T: Config>(public: T::Public, carrier: &T::AccountId) -> Result<(), &'static str> {
+	let url = match sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &feed_key(carrier)) {
+		Some(url) => url,
+		None => return Ok(()),
+	};
+	let url = core::str::from_utf8(&url).map_err(|_| "the feed URL is not UTF-8")?;
+	let body = fetch(url).map_err(|_| "cannot fetch the feed")?;
+	let readings = parse_readings(&body).ok_or("the feed is not an array of readings")?;
+
+	let cursor_key = cursor_key(carrier);
+	let cursor = StorageValueRef::persistent(&cursor_key);
+	let reported = cursor.get::<u64>().flatten().unwrap_or_default();
+	let reports = unreported(readings, reported, |id| {
+		Shipments::<T>::get(id).map_or(false, |shipment| {
+			shipment.carrier == *carrier
+				&& matches!(shipment.status, ShipmentStatus::InTransit | ShipmentStatus::Customs)
+		})
+	});
+	let latest = match reports.last() {
+		Some((_, telemetry)) => telemetry.recorded,
+		None => return Ok(()),
+	};
+
+	let signer = Signer::<T, T::DeviceId>::any_account().with_filter(vec![public]);
+	match signer.send_signed_transaction(|_| Call::report_telemetry(reports.clone())) {
+		Some((_, Ok(()))) => {
+			cursor.set(&latest);
+			Ok(())
+		},
+		_ => Err("cannot submit the report"),
+	}
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>, http::Error> {
+	let deadline = sp_io::offchain::timestamp().add(Duration::from_millis(FEED_TIMEOUT_MILLIS));
+	let pending = http::Request::get(url).deadline(deadline).send().map_err(|_| http::Error::IoError)?;
+	let response = pending.try_wait(deadline).map_err(|_| http::Error::DeadlineReached)??;
+	if response.code != 200 {
+		return Err(http::Error::Unknown);
+	}
+	Ok(response.body().collect())
+}
+
+/// The readings of the shipments on the road taken after `reported`, oldest first and no
+/// more than a report holds.
+pub fn unreported(
+	mut readings: Vec<Reading>,
+	reported: u64,
+	on_the_road: impl Fn(&ShipmentId) -> bool,
+) -> Vec<(ShipmentId, Telemetry)> {
+	readings.retain(|reading| {
+		reading.telemetry.recorded > reported && reading.telemetry.is_valid() && on_the_road(&reading.shipment)
+	});
+	readings.sort_by_key(|reading| reading.telemetry.recorded);
+	readings.into_iter()
+		.take(TELEMETRY_MAX_REPORTS)
+		.map(|reading| (reading.shipment, reading.telemetry))
+		.collect()
+}
+
+/// Readings of the feed, skipping those it cannot make sense of.
+pub fn parse_readings(body: &[u8]) -> Option<Vec<Reading>> {
+	let body = core::str::from_utf8(body).ok()?;
+	match lite_json::parse_json(body).ok()? {
+		JsonValue::Array(readings) => Some(readings.iter().filter_map(parse_reading).collect()),
+		_ => None,
+	}
+}
+
+fn parse_reading(reading: &JsonValue) -> Option<Reading> {
+	let fields = match reading {
+		JsonValue::Object(fields) => fields,
+		_ => return None,
+	};
+	let field = |name: &str| fields.iter()
+		.find(|(key, _)| key.iter().copied().eq(name.chars()))
+		.map(|(_, value)| value);
+	let number = |name: &str, decimals: u32| match field(name)? {
+		JsonValue::Number(number) => scaled(number, decimals),
+		_ => None,
+	};
+
+	let shipment = match field("shipment")? {
+		JsonValue::String(chars) => chars.iter().fold(Vec::new(), |mut id, c| {
+			id.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+			id
+		}),
+		_ => return None,
+	};
+	Some(Reading {
+		shipment,
+		telemetry: Telemetry {
+			latitude: i32::try_from(number("latitude", 6)?).ok()?,
+			longitude: i32::try_from(number("longitude", 6)?).ok()?,
+			temperature: i16::try_from(number("temperature", 2)?).ok()?,
+			recorded: u64::try_from(number("timestamp", 0)?).ok()?,
+		},
+	})
+}
+
+/// The number times `10^decimals`, dropping the decimals beyond. The sign is that of the
+/// integer part, so the parser loses it between -1 and 0.
+fn scaled(number: &NumberValue, decimals: u32) -> Option<i64> {
+	if number.exponent != 0 {
+		return None;
+	}
+	let fraction = if number.fraction_length > decimals {
+		number.fraction / 10u64.checked_pow(number.fraction_length - decimals)?
+	} else {
+		number.fraction.checked_mul(10u64.checked_pow(decimals - number.fraction_length)?)?
+	};
+	let magnitude = number.integer.checked_abs()?
+		.checked_mul(10i64.checked_pow(decimals)?)?
+		.checked_add(i64::try_from(fraction).ok()?)?;
+	Some(if number.integer < 0 { -magnitude } else { magnitude })
+}
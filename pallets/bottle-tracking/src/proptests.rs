@@ -0,0 +1,299 @@
+//! State-machine fuzzing of the shipment lifecycle over the mock runtime.
+//!
+//! Random sequences of calls from random members are applied to the runtime and to a
+//! simple model of bottle ownership. After every call the runtime storage is checked
+//! against the model and against the invariants the pallet must keep.
+
+use crate::{Shipments, BottleOfShipment, BottleSoldTo, BottlesOfCustomer, mock::*, types::*};
+use frame_support::{assert_ok, dispatch::DispatchResult, storage::IterableStorageMap, StorageMap};
+use proptest::{prelude::*, collection::vec, sample::select};
+use registrar::{BottleId, BottleStatus};
+use sp_core::H256;
+use std::collections::{BTreeMap, BTreeSet};
+
+const BOTTLES: u8 = 6;
+const SHIPMENTS: u8 = 4;
+
+const STATUSES: [BottleStatus; 5] = [
+	BottleStatus::Manufactured,
+	BottleStatus::ShipmentRegistered,
+	BottleStatus::ShipmentInTransit,
+	BottleStatus::ShipmentDelivered,
+	BottleStatus::SoldToCustomer,
+];
+
+const ACCOUNTS: [u64; 11] = [
+	MANUFACTURER, CARRIER, RETAILER, CUSTOMER, DISTRIBUTOR, WAREHOUSE,
+	CUSTOMS, CARRIER_2, RETAILER_2, CUSTOMS_2, STRANGER,
+];
+
+#[derive(Clone, Debug)]
+enum Action {
+	Register { id: u8, sender: u64, carrier: u64, receiver: u64, customs: Option<u64>, bottles: Vec<u8> },
+	Track { id: u8, carrier: u64, operation: ShipmentOperation },
+	Receive { id: u8, receiver: u64, bottles: Vec<u8> },
+	Cancel { id: u8, who: u64 },
+	Reject { id: u8, who: u64 },
+	Hold { id: u8, customs: u64 },
+	Clear { id: u8, customs: u64 },
+	Sell { retailer: u64, customer: u64, bottles: Vec<u8> },
+}
+
+// Actors are mostly drawn from the members allowed to act, with a few wrong ones
+// mixed in so that failing calls are exercised as well.
+
+fn stock_holder() -> impl Strategy<Value = u64> {
+	select(vec![MANUFACTURER, DISTRIBUTOR, WAREHOUSE, RETAILER, RETAILER_2, CUSTOMER])
+}
+
+fn carrier() -> impl Strategy<Value = u64> {
+	select(vec![CARRIER, CARRIER_2, RETAILER])
+}
+
+fn customs() -> impl Strategy<Value = u64> {
+	select(vec![CUSTOMS, CUSTOMS_2, CARRIER])
+}
+
+fn any_account() -> impl Strategy<Value = u64> {
+	select(ACCOUNTS.to_vec())
+}
+
+fn shipment_index() -> impl Strategy<Value = u8> {
+	0..SHIPMENTS
+}
+
+fn bottle_indexes() -> impl Strategy<Value = Vec<u8>> {
+	vec(0..BOTTLES, 0..4)
+}
+
+fn operation() -> impl Strategy<Value = ShipmentOperation> {
+	prop_oneof![
+		Just(ShipmentOperation::Pickup),
+		Just(ShipmentOperation::Scan),
+		Just(ShipmentOperation::Deliver),
+	]
+}
+
+fn action() -> impl Strategy<Value = Action> {
+	prop_oneof![
+		3 => (shipment_index(), stock_holder(), carrier(), stock_holder(), prop::option::weighted(0.3, customs()), bottle_indexes())
+			.prop_map(|(id, sender, carrier, receiver, customs, bottles)| {
+				Action::Register { id, sender, carrier, receiver, customs, bottles }
+			}),
+		4 => (shipment_index(), carrier(), operation())
+			.prop_map(|(id, carrier, operation)| Action::Track { id, carrier, operation }),
+		1 => (shipment_index(), stock_holder(), bottle_indexes())
+			.prop_map(|(id, receiver, bottles)| Action::Receive { id, receiver, bottles }),
+		1 => (shipment_index(), any_account()).prop_map(|(id, who)| Action::Cancel { id, who }),
+		1 => (shipment_index(), any_account()).prop_map(|(id, who)| Action::Reject { id, who }),
+		1 => (shipment_index(), customs()).prop_map(|(id, customs)| Action::Hold { id, customs }),
+		1 => (shipment_index(), customs()).prop_map(|(id, customs)| Action::Clear { id, customs }),
+		2 => (select(vec![RETAILER, RETAILER_2, WAREHOUSE]), select(vec![CUSTOMER, RETAILER]), bottle_indexes())
+			.prop_map(|(retailer, customer, bottles)| Action::Sell { retailer, customer, bottles }),
+	]
+}
+
+fn bottle(n: u8) -> BottleId {
+	let mut id = b"bottle-".to_vec();
+	id.push(b'0' + n);
+	id
+}
+
+fn bottles(indexes: &[u8]) -> Vec<BottleId> {
+	indexes.iter().map(|n| bottle(*n)).collect()
+}
+
+fn shipment(n: u8) -> ShipmentId {
+	let mut id = b"shipment-".to_vec();
+	id.push(b'0' + n);
+	id
+}
+
+/// Ownership of every bottle as implied by the calls that succeeded so far.
+#[derive(Default)]
+struct Model {
+	/// The registrar owner of each bottle, the carrier while it is shipped or after it was lost.
+	owner: BTreeMap<BottleId, u64>,
+	/// Bottles on a shipment that is neither completed nor cancelled.
+	shipped: BTreeSet<BottleId>,
+	sold: BTreeSet<BottleId>,
+	/// Sender, carrier, receiver and bottles of every registered shipment.
+	shipments: BTreeMap<ShipmentId, (u64, u64, u64, Vec<BottleId>)>,
+}
+
+impl Model {
+	fn apply(&mut self, action: &Action) {
+		match action {
+			Action::Register { id, sender, carrier, receiver, bottles: indexes, .. } => {
+				let shipped = bottles(indexes);
+				for bottle in &shipped {
+					assert_eq!(self.owner[bottle], *sender, "bottle shipped by an account not owning it");
+					assert!(!self.shipped.contains(bottle), "bottle shipped twice");
+					assert!(!self.sold.contains(bottle), "sold bottle shipped again");
+					self.owner.insert(bottle.clone(), *carrier);
+					self.shipped.insert(bottle.clone());
+				}
+				self.shipments.insert(shipment(*id), (*sender, *carrier, *receiver, shipped));
+			},
+			Action::Track { id, operation: ShipmentOperation::Deliver, .. } => {
+				let (_, _, receiver, shipped) = self.shipments[&shipment(*id)].clone();
+				for bottle in shipped {
+					self.owner.insert(bottle.clone(), receiver);
+					self.shipped.remove(&bottle);
+				}
+			},
+			Action::Receive { id, bottles: indexes, .. } => {
+				let (_, carrier, receiver, shipped) = self.shipments[&shipment(*id)].clone();
+				let received = bottles(indexes);
+				for bottle in shipped {
+					let owner = if received.contains(&bottle) { receiver } else { carrier };
+					self.owner.insert(bottle.clone(), owner);
+					self.shipped.remove(&bottle);
+				}
+			},
+			Action::Cancel { id, .. } | Action::Reject { id, .. } => {
+				let (sender, _, _, shipped) = self.shipments[&shipment(*id)].clone();
+				for bottle in shipped {
+					self.owner.insert(bottle.clone(), sender);
+					self.shipped.remove(&bottle);
+				}
+			},
+			Action::Sell { retailer, customer, bottles: indexes } => {
+				for bottle in bottles(indexes) {
+					assert_eq!(self.owner[&bottle], *retailer, "bottle sold by an account not owning it");
+					self.owner.insert(bottle.clone(), *customer);
+					self.sold.insert(bottle);
+				}
+			},
+			Action::Track { .. } | Action::Hold { .. } | Action::Clear { .. } => (),
+		}
+	}
+}
+
+fn setup() -> Model {
+	assert_ok!(RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)));
+	assert_ok!(RegistrarModule::register_carrier(Origin::signed(CARRIER)));
+	assert_ok!(RegistrarModule::register_carrier(Origin::signed(CARRIER_2)));
+	assert_ok!(RegistrarModule::register_retailer(Origin::signed(RETAILER)));
+	assert_ok!(RegistrarModule::register_retailer(Origin::signed(RETAILER_2)));
+	assert_ok!(RegistrarModule::register_customer(Origin::signed(CUSTOMER)));
+	assert_ok!(RegistrarModule::register_distributor(Origin::signed(DISTRIBUTOR)));
+	assert_ok!(RegistrarModule::register_warehouse(Origin::signed(WAREHOUSE)));
+	assert_ok!(RegistrarModule::register_customs(Origin::signed(CUSTOMS)));
+	assert_ok!(RegistrarModule::register_customs(Origin::signed(CUSTOMS_2)));
+
+	let mut model = Model::default();
+	for n in 0..BOTTLES {
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), bottle(n), vec![]));
+		model.owner.insert(bottle(n), MANUFACTURER);
+	}
+	model
+}
+
+fn dispatch(action: &Action) -> DispatchResult {
+	match action.clone() {
+		Action::Register { id, sender, carrier, receiver, customs, bottles: indexes } =>
+			BottleTracking::register_shipment(
+				Origin::signed(sender), shipment(id), carrier, receiver, customs, None, bottles(&indexes), None,
+				vec![],
+			),
+		Action::Track { id, carrier, operation } =>
+			BottleTracking::track_shipment(Origin::signed(carrier), shipment(id), operation),
+		Action::Receive { id, receiver, bottles: indexes } =>
+			BottleTracking::receive_shipment(Origin::signed(receiver), shipment(id), bottles(&indexes)),
+		Action::Cancel { id, who } =>
+			BottleTracking::cancel_shipment(Origin::signed(who), shipment(id)),
+		Action::Reject { id, who } =>
+			BottleTracking::reject_shipment(Origin::signed(who), shipment(id)),
+		Action::Hold { id, customs } =>
+			BottleTracking::hold_shipment(Origin::signed(customs), shipment(id)),
+		Action::Clear { id, customs } =>
+			BottleTracking::clear_shipment(
+				Origin::signed(customs), shipment(id), b"ref".to_vec(), vec![H256::repeat_byte(1)],
+			),
+		Action::Sell { retailer, customer, bottles: indexes } =>
+			BottleTracking::sell_to_customer(Origin::signed(retailer), customer, bottles(&indexes)),
+	}
+}
+
+fn check_invariants(model: &Model) {
+	let shipments: Vec<_> = Shipments::<Test>::iter().collect();
+
+	for (_, shipment) in &shipments {
+		let unique: BTreeSet<_> = shipment.bottles.iter().collect();
+		assert_eq!(unique.len(), shipment.bottles.len(), "bottle listed twice in a shipment");
+	}
+
+	for n in 0..BOTTLES {
+		let bottle = bottle(n);
+
+		// A bottle is in at most one active shipment, the one it is indexed under.
+		let active: Vec<&ShipmentId> = shipments.iter()
+			.filter(|(_, s)| !s.is_completed() && !s.is_cancelled() && s.bottles.contains(&bottle))
+			.map(|(id, _)| id)
+			.collect();
+		assert!(active.len() <= 1, "bottle in several active shipments");
+		if let Some(id) = active.first() {
+			assert_eq!(BottleOfShipment::get(&bottle).as_ref(), Some(*id));
+		}
+
+		// The registrar owner matches the last custody event.
+		let record = registrar::Bottles::<Test>::get(&bottle).unwrap();
+		let owner = *record.owner();
+		assert_eq!(owner, model.owner[&bottle], "owner does not match the last custody event");
+
+		// Only the owner may ship the bottle, and nobody while it is shipped.
+		let holders: Vec<u64> = ACCOUNTS.iter()
+			.filter(|account| BottleTracking::check_bottle_custody(&bottle, account).is_ok())
+			.cloned()
+			.collect();
+		let expected = if model.shipped.contains(&bottle) { vec![] } else { vec![owner] };
+		assert_eq!(holders, expected, "custody does not follow the owner");
+
+		let sold_to = BottleSoldTo::<Test>::get(&bottle);
+		if let Some(customer) = sold_to {
+			assert_eq!(customer, owner, "sold bottle not owned by its customer");
+		}
+		assert_eq!(sold_to.is_some(), model.sold.contains(&bottle));
+
+		// The status index lists the bottle under its status only.
+		let status = record.status().clone();
+		for other in &STATUSES {
+			let indexed = RegistrarModule::bottles_with_status(other.clone()).contains(&bottle);
+			assert_eq!(indexed, *other == status, "status index out of sync");
+		}
+		assert_eq!(status == BottleStatus::SoldToCustomer, model.sold.contains(&bottle));
+		if model.shipped.contains(&bottle) {
+			assert!(
+				status == BottleStatus::ShipmentRegistered || status == BottleStatus::ShipmentInTransit,
+				"shipped bottle not on its way",
+			);
+		}
+	}
+
+	for customer in &[CUSTOMER, RETAILER] {
+		let owned = BottlesOfCustomer::<Test>::get(customer);
+		let unique: BTreeSet<_> = owned.iter().collect();
+		assert_eq!(unique.len(), owned.len(), "bottle sold twice to a customer");
+		assert!(owned.iter().all(|bottle| model.sold.contains(bottle)));
+	}
+}
+
+proptest! {
+	#![proptest_config(ProptestConfig::with_cases(128))]
+
+	#[test]
+	fn shipment_lifecycle_keeps_invariants(actions in vec(action(), 1..60)) {
+		new_test_ext().execute_with(|| {
+			let mut model = setup();
+			check_invariants(&model);
+
+			for action in &actions {
+				if dispatch(action).is_ok() {
+					model.apply(action);
+				}
+				check_invariants(&model);
+			}
+		});
+	}
+}
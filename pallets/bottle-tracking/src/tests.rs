@@ -0,0 +1,1900 @@
+use crate::{
+	Error, RawEvent, Shipments, ShipmentsOfSender, ShipmentsOfCarrier, ShipmentsOfReceiver,
+	BottleOfShipment, ShipmentsDueAt, ShipmentClearances, ShipmentEscrows, Disputes, DisputableUntil,
+	BottleIncidents, LastCustodianOf, BottlesOfCustomer, BottleSoldTo, LastTelemetry, ShipmentDocuments, Devices,
+	DeviceNonces, StorageVersion,
+	migrations, offchain, SHIPMENT_ID_MAX_LENGTH, SHIPMENT_MAX_BOTTLES, CLEARANCE_REFERENCE_MAX_LENGTH,
+	CLEARANCE_MAX_DOCUMENTS, DISPUTE_MAX_EVIDENCE, TELEMETRY_MAX_REPORTS, mock::*, types::*,
+};
+use codec::{Decode, Encode};
+use frame_support::{assert_ok, assert_noop, dispatch::DispatchResult,
+	traits::{Currency, ReservableCurrency, OffchainWorker, OnInitialize, OnRuntimeUpgrade},
+	unsigned::ValidateUnsigned,
+	storage::migration::put_storage_value, Blake2_128Concat, StorageHasher, StorageMap, StorageValue};
+use registrar::{BottleId, BottleStatus};
+use sp_core::{H256, offchain::{testing, OffchainExt, StorageKind, TransactionPoolExt}};
+use sp_runtime::{
+	DispatchError, RuntimeAppPublic, testing::{TestXt, UintAuthorityId}, traits::{BlakeTwo256, Hash},
+	transaction_validity::{InvalidTransaction, TransactionSource, TransactionValidity},
+};
+
+const PRICE: u64 = 600;
+const CARRIER_FEE: u64 = 60;
+
+fn register_members() {
+	assert_ok!(RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)));
+	assert_ok!(RegistrarModule::register_carrier(Origin::signed(CARRIER)));
+	assert_ok!(RegistrarModule::register_carrier(Origin::signed(CARRIER_2)));
+	assert_ok!(RegistrarModule::register_retailer(Origin::signed(RETAILER)));
+	assert_ok!(RegistrarModule::register_retailer(Origin::signed(RETAILER_2)));
+	assert_ok!(RegistrarModule::register_customer(Origin::signed(CUSTOMER)));
+	assert_ok!(RegistrarModule::register_distributor(Origin::signed(DISTRIBUTOR)));
+	assert_ok!(RegistrarModule::register_warehouse(Origin::signed(WAREHOUSE)));
+	assert_ok!(RegistrarModule::register_customs(Origin::signed(CUSTOMS)));
+	assert_ok!(RegistrarModule::register_customs(Origin::signed(CUSTOMS_2)));
+}
+
+fn bottle(n: u8) -> BottleId {
+	let mut id = b"bottle-".to_vec();
+	id.push(b'0' + n);
+	id
+}
+
+fn shipment(n: u8) -> ShipmentId {
+	let mut id = b"shipment-".to_vec();
+	id.push(b'0' + n);
+	id
+}
+
+fn hashes(n: usize) -> Vec<H256> {
+	(0..n).map(|i| H256::repeat_byte(i as u8 + 1)).collect()
+}
+
+fn payment() -> Option<ShipmentPayment<u64>> {
+	Some(ShipmentPayment { price: PRICE, carrier_fee: CARRIER_FEE })
+}
+
+/// Registers the members and bottles `1..=n` of the manufacturer.
+fn setup(n: u8) -> Vec<BottleId> {
+	register_members();
+	(1..=n).map(|i| {
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), bottle(i), vec![]));
+		bottle(i)
+	}).collect()
+}
+
+fn ship(id: &ShipmentId, sender: u64, receiver: u64, bottles: Vec<BottleId>) -> DispatchResult {
+	BottleTracking::register_shipment(
+		Origin::signed(sender), id.clone(), CARRIER, receiver, None, None, bottles, None, vec![],
+	)
+}
+
+fn ship_paid(id: &ShipmentId, sender: u64, receiver: u64, bottles: Vec<BottleId>) -> DispatchResult {
+	BottleTracking::register_shipment(
+		Origin::signed(sender), id.clone(), CARRIER, receiver, None, None, bottles, payment(), vec![],
+	)
+}
+
+fn ship_through_customs(id: &ShipmentId, bottles: Vec<BottleId>) -> DispatchResult {
+	BottleTracking::register_shipment(
+		Origin::signed(MANUFACTURER), id.clone(), CARRIER, RETAILER, Some(CUSTOMS), None, bottles, None,
+		vec![],
+	)
+}
+
+fn track(id: &ShipmentId, operation: ShipmentOperation) -> DispatchResult {
+	BottleTracking::track_shipment(Origin::signed(CARRIER), id.clone(), operation)
+}
+
+fn pickup_and_deliver(id: &ShipmentId) {
+	assert_ok!(track(id, ShipmentOperation::Pickup));
+	assert_ok!(track(id, ShipmentOperation::Deliver));
+}
+
+fn hold_and_clear(id: &ShipmentId) {
+	assert_ok!(BottleTracking::hold_shipment(Origin::signed(CUSTOMS), id.clone()));
+	assert_ok!(BottleTracking::clear_shipment(Origin::signed(CUSTOMS), id.clone(), b"ref-1".to_vec(), hashes(1)));
+}
+
+/// Registers, funds and delivers a paid shipment of the given bottles from the manufacturer to the retailer.
+fn deliver_paid(id: &ShipmentId, bottles: Vec<BottleId>) {
+	assert_ok!(ship_paid(id, MANUFACTURER, RETAILER, bottles));
+	assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), id.clone()));
+	pickup_and_deliver(id);
+}
+
+fn status_of(id: &ShipmentId) -> ShipmentStatus {
+	Shipments::<Test>::get(id).expect("shipment should exist").status
+}
+
+fn run_to_block(n: u64) {
+	while System::block_number() < n {
+		System::set_block_number(System::block_number() + 1);
+		BottleTracking::on_initialize(System::block_number());
+	}
+}
+
+fn free(account: u64) -> u64 {
+	Balances::free_balance(account)
+}
+
+fn reserved(account: u64) -> u64 {
+	Balances::reserved_balance(account)
+}
+
+// register_shipment
+
+#[test]
+fn register_shipment_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		Timestamp::set_timestamp(42);
+		let id = shipment(1);
+
+		assert_ok!(ship(&id, MANUFACTURER, RETAILER, bottles.clone()));
+
+		let registered = Shipments::<Test>::get(&id).expect("shipment should exist");
+		assert_eq!(registered.sender, MANUFACTURER);
+		assert_eq!(registered.carrier, CARRIER);
+		assert_eq!(registered.receiver, RETAILER);
+		assert_eq!(registered.bottles, bottles);
+		assert_eq!(registered.status, ShipmentStatus::Pending);
+		assert_eq!(registered.registered, 42);
+		assert_eq!(registered.delivered, None);
+		assert!(registered.missing.is_empty());
+
+		assert_eq!(ShipmentsOfSender::<Test>::get(MANUFACTURER), vec![id.clone()]);
+		assert_eq!(ShipmentsOfCarrier::<Test>::get(CARRIER), vec![id.clone()]);
+		assert_eq!(ShipmentsOfReceiver::<Test>::get(RETAILER), vec![id.clone()]);
+		assert_eq!(BottleOfShipment::get(bottle(1)), Some(id.clone()));
+		assert_eq!(BottleOfShipment::get(bottle(2)), Some(id.clone()));
+		assert_eq!(ShipmentEscrows::<Test>::get(&id), None);
+		assert_eq!(last_event(), RawEvent::ShipmentRegistered(id, MANUFACTURER));
+	});
+}
+
+#[test]
+fn register_shipment_with_invalid_id_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+
+		assert_noop!(ship(&vec![], MANUFACTURER, RETAILER, bottles.clone()), Error::<Test>::ShipmentIdMissing);
+		assert_noop!(
+			ship(&vec![b's'; SHIPMENT_ID_MAX_LENGTH + 1], MANUFACTURER, RETAILER, bottles),
+			Error::<Test>::ShipmentIdTooLong
+		);
+	});
+}
+
+#[test]
+fn register_shipment_twice_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1)]));
+
+		assert_noop!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottles[1].clone()]), Error::<Test>::ShipmentIdExists);
+	});
+}
+
+#[test]
+fn register_shipment_with_invalid_parties_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+
+		assert_noop!(ship(&shipment(1), MANUFACTURER, MANUFACTURER, bottles.clone()), Error::<Test>::ShipmentToSelf);
+		assert_noop!(ship(&shipment(1), CUSTOMER, RETAILER, bottles.clone()), Error::<Test>::NotStockHolder);
+		assert_noop!(ship(&shipment(1), CARRIER, RETAILER, bottles.clone()), Error::<Test>::NotStockHolder);
+		assert_noop!(ship(&shipment(1), STRANGER, RETAILER, bottles.clone()), Error::<Test>::NotStockHolder);
+		assert_noop!(ship(&shipment(1), MANUFACTURER, CUSTOMER, bottles.clone()), Error::<Test>::InvalidShipmentReceiver);
+		assert_noop!(ship(&shipment(1), MANUFACTURER, CUSTOMS, bottles.clone()), Error::<Test>::InvalidShipmentReceiver);
+		assert_noop!(
+			BottleTracking::register_shipment(
+				Origin::signed(MANUFACTURER), shipment(1), RETAILER_2, RETAILER, None, None, bottles.clone(), None,
+				vec![],
+			),
+			registrar::Error::<Test>::NotCarrier
+		);
+		assert_noop!(
+			BottleTracking::register_shipment(
+				Origin::signed(MANUFACTURER), shipment(1), CARRIER, RETAILER, Some(CARRIER_2), None, bottles, None,
+				vec![],
+			),
+			registrar::Error::<Test>::NotCustoms
+		);
+	});
+}
+
+#[test]
+fn register_shipment_between_stock_holders_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+
+		assert_ok!(ship(&shipment(1), MANUFACTURER, DISTRIBUTOR, bottles.clone()));
+		pickup_and_deliver(&shipment(1));
+		assert_ok!(ship(&shipment(2), DISTRIBUTOR, WAREHOUSE, bottles.clone()));
+		pickup_and_deliver(&shipment(2));
+		assert_ok!(ship(&shipment(3), WAREHOUSE, RETAILER, bottles.clone()));
+		pickup_and_deliver(&shipment(3));
+		assert_ok!(ship(&shipment(4), RETAILER, RETAILER_2, bottles));
+	});
+}
+
+#[test]
+fn register_shipment_with_past_deadline_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+
+		assert_noop!(
+			BottleTracking::register_shipment(
+				Origin::signed(MANUFACTURER), shipment(1), CARRIER, RETAILER, None, Some(1), bottles, None,
+				vec![],
+			),
+			Error::<Test>::DeadlineInPast
+		);
+	});
+}
+
+#[test]
+fn register_shipment_with_invalid_bottles_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(SHIPMENT_MAX_BOTTLES as u8 + 1);
+
+		assert_noop!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![]), Error::<Test>::ShipmentHasNoBottles);
+		assert_noop!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles), Error::<Test>::ShipmentHasTooManyBottles);
+		assert_noop!(
+			ship(&shipment(1), MANUFACTURER, RETAILER, vec![b"unknown".to_vec()]),
+			registrar::Error::<Test>::BottleNotExist
+		);
+		assert_noop!(
+			ship(&shipment(1), DISTRIBUTOR, RETAILER, vec![bottle(1)]),
+			registrar::Error::<Test>::NotBottleOwner
+		);
+		assert_noop!(
+			ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1), bottle(2), bottle(1)]),
+			Error::<Test>::BottleListedTwice
+		);
+	});
+}
+
+#[test]
+fn register_shipment_with_shipped_bottle_fails() {
+	new_test_ext().execute_with(|| {
+		setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1)]));
+
+		assert_noop!(
+			ship(&shipment(2), MANUFACTURER, RETAILER_2, vec![bottle(2), bottle(1)]),
+			Error::<Test>::BottleAlreadyShipped
+		);
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		assert_noop!(
+			ship(&shipment(2), MANUFACTURER, RETAILER_2, vec![bottle(1)]),
+			Error::<Test>::BottleAlreadyShipped
+		);
+	});
+}
+
+#[test]
+fn register_shipment_with_documents_stores_their_hashes() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		let documents = vec![b"bill of lading".to_vec(), b"invoice".to_vec()];
+
+		assert_ok!(BottleTracking::register_shipment(
+			Origin::signed(MANUFACTURER), shipment(1), CARRIER, RETAILER, None, None, bottles, None,
+			documents.clone(),
+		));
+
+		assert_eq!(
+			ShipmentDocuments::<Test>::get(shipment(1)),
+			documents.iter().map(|document| BlakeTwo256::hash(document)).collect::<Vec<_>>()
+		);
+	});
+}
+
+#[test]
+fn register_shipment_with_invalid_documents_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		let register = |documents| BottleTracking::register_shipment(
+			Origin::signed(MANUFACTURER), shipment(1), CARRIER, RETAILER, None, None, bottles.clone(), None, documents,
+		);
+
+		assert_noop!(register(vec![vec![]]), registrar::Error::<Test>::InvalidDocument);
+		assert_noop!(
+			register(vec![vec![b'd'; registrar::DOCUMENT_MAX_SIZE + 1]]),
+			registrar::Error::<Test>::InvalidDocument
+		);
+		assert_noop!(
+			register(vec![b"invoice".to_vec(); registrar::MAX_DOCUMENTS + 1]),
+			registrar::Error::<Test>::TooManyDocuments
+		);
+	});
+}
+
+#[test]
+fn register_shipment_reserves_carrier_fee() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles));
+
+		assert_eq!(reserved(MANUFACTURER), CARRIER_FEE);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE - CARRIER_FEE);
+		assert_eq!(
+			ShipmentEscrows::<Test>::get(shipment(1)),
+			Some(Escrow { payment: payment().unwrap(), funded: false, held_until: None })
+		);
+	});
+}
+
+#[test]
+fn register_shipment_without_funds_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		let _ = Balances::slash(&MANUFACTURER, INITIAL_BALANCE - CARRIER_FEE + 1);
+
+		assert_noop!(
+			ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles),
+			balances::Error::<Test, balances::DefaultInstance>::InsufficientBalance
+		);
+	});
+}
+
+// track_shipment
+
+#[test]
+fn pickup_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles));
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::InTransit);
+		assert_eq!(last_event(), RawEvent::ShipmentStatusUpdated(shipment(1), CARRIER, ShipmentStatus::InTransit));
+	});
+}
+
+#[test]
+fn scan_keeps_shipment_status() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		let events = tracking_events().len();
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Scan));
+
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::InTransit);
+		assert_eq!(tracking_events().len(), events);
+	});
+}
+
+#[test]
+fn deliver_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		Timestamp::set_timestamp(42);
+
+		pickup_and_deliver(&shipment(1));
+
+		let delivered = Shipments::<Test>::get(shipment(1)).unwrap();
+		assert_eq!(delivered.status, ShipmentStatus::Delivered);
+		assert_eq!(delivered.delivered, Some(42));
+		assert_eq!(last_event(), RawEvent::ShipmentStatusUpdated(shipment(1), CARRIER, ShipmentStatus::Delivered));
+	});
+}
+
+#[test]
+fn deliver_to_warehouse_stores_shipment() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, WAREHOUSE, bottles));
+
+		pickup_and_deliver(&shipment(1));
+
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::Stored);
+	});
+}
+
+#[test]
+fn track_shipment_by_wrong_carrier_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles));
+
+		assert_noop!(
+			BottleTracking::track_shipment(Origin::signed(RETAILER), shipment(1), ShipmentOperation::Pickup),
+			registrar::Error::<Test>::NotCarrier
+		);
+		assert_noop!(
+			BottleTracking::track_shipment(Origin::signed(CARRIER_2), shipment(1), ShipmentOperation::Pickup),
+			Error::<Test>::NotShipmentCarrier
+		);
+	});
+}
+
+#[test]
+fn track_unknown_shipment_fails() {
+	new_test_ext().execute_with(|| {
+		setup(0);
+
+		assert_noop!(track(&vec![], ShipmentOperation::Pickup), Error::<Test>::ShipmentIdMissing);
+		assert_noop!(track(&shipment(1), ShipmentOperation::Pickup), Error::<Test>::ShipmentDoesNotExist);
+	});
+}
+
+#[test]
+fn track_shipment_in_wrong_state_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottles[0].clone()]));
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, vec![bottles[1].clone()]));
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		assert_noop!(track(&shipment(1), ShipmentOperation::Pickup), Error::<Test>::ShipmentInTransit);
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Deliver));
+		assert_noop!(track(&shipment(1), ShipmentOperation::Scan), Error::<Test>::ShipmentHasBeenDelivered);
+		assert_noop!(track(&shipment(1), ShipmentOperation::Deliver), Error::<Test>::ShipmentHasBeenDelivered);
+
+		assert_ok!(BottleTracking::cancel_shipment(Origin::signed(MANUFACTURER), shipment(2)));
+		assert_noop!(track(&shipment(2), ShipmentOperation::Pickup), Error::<Test>::ShipmentHasBeenCancelled);
+	});
+}
+
+#[test]
+fn pickup_unfunded_shipment_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles));
+
+		assert_noop!(track(&shipment(1), ShipmentOperation::Pickup), Error::<Test>::ShipmentNotFunded);
+
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+	});
+}
+
+#[test]
+fn deliver_unfunded_shipment_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles));
+
+		assert_noop!(track(&shipment(1), ShipmentOperation::Deliver), Error::<Test>::ShipmentPending);
+
+		let mut unfunded = Shipments::<Test>::get(shipment(1)).unwrap();
+		unfunded.status = ShipmentStatus::InTransit;
+		assert_noop!(BottleTracking::settle_escrow(&unfunded), Error::<Test>::ShipmentNotFunded);
+		assert_eq!(reserved(RETAILER), 0);
+	});
+}
+
+#[test]
+fn delivery_pays_what_is_left_of_slashed_reserves() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+		let _ = Balances::slash_reserved(&RETAILER, PRICE / 3);
+		let _ = Balances::slash_reserved(&MANUFACTURER, CARRIER_FEE / 2);
+
+		pickup_and_deliver(&shipment(1));
+
+		assert_eq!(
+			ShipmentEscrows::<Test>::get(shipment(1)).unwrap().payment,
+			ShipmentPayment { price: PRICE - PRICE / 3, carrier_fee: CARRIER_FEE - CARRIER_FEE / 2 }
+		);
+		assert_eq!(reserved(MANUFACTURER), PRICE - PRICE / 3);
+		assert_eq!(reserved(CARRIER), CARRIER_FEE - CARRIER_FEE / 2);
+		assert_eq!(reserved(RETAILER), 0);
+
+		run_to_block(1 + DisputePeriod::get());
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE - CARRIER_FEE + PRICE - PRICE / 3);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE + CARRIER_FEE - CARRIER_FEE / 2);
+	});
+}
+
+// receive_shipment
+
+#[test]
+fn receive_shipment_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+
+		assert_ok!(BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(1), bottles));
+
+		let received = Shipments::<Test>::get(shipment(1)).unwrap();
+		assert_eq!(received.status, ShipmentStatus::Delivered);
+		assert!(received.missing.is_empty());
+		assert!(!event_exists(RawEvent::BottlesLostInTransit(shipment(1), CARRIER, vec![])));
+		assert_eq!(last_event(), RawEvent::ShipmentStatusUpdated(shipment(1), RETAILER, ShipmentStatus::Delivered));
+	});
+}
+
+#[test]
+fn receive_partial_shipment_marks_missing_bottles_lost() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(3);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+
+		assert_ok!(BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(1), vec![bottle(2)]));
+
+		let received = Shipments::<Test>::get(shipment(1)).unwrap();
+		assert_eq!(received.missing, vec![bottle(1), bottle(3)]);
+		assert_eq!(BottleIncidents::get(bottle(1)), Some(BottleIncident::Lost));
+		assert_eq!(BottleIncidents::get(bottle(2)), None);
+		assert_eq!(LastCustodianOf::<Test>::get(bottle(3)), Some(CARRIER));
+		assert!(event_exists(RawEvent::BottlesLostInTransit(shipment(1), CARRIER, vec![bottle(1), bottle(3)])));
+
+		// Missing bottles can no longer be shipped or sold.
+		assert_noop!(
+			ship(&shipment(2), RETAILER, RETAILER_2, vec![bottle(1)]),
+			Error::<Test>::BottleLostOrDamaged
+		);
+		assert_noop!(
+			BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, vec![bottle(3)]),
+			Error::<Test>::BottleLostOrDamaged
+		);
+		assert_ok!(BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, vec![bottle(2)]));
+	});
+}
+
+#[test]
+fn receive_shipment_by_wrong_account_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+
+		assert_noop!(
+			BottleTracking::receive_shipment(Origin::signed(RETAILER_2), shipment(1), bottles.clone()),
+			Error::<Test>::NotShipmentReceiver
+		);
+		assert_noop!(
+			BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(2), bottles),
+			Error::<Test>::ShipmentDoesNotExist
+		);
+	});
+}
+
+#[test]
+fn receive_shipment_in_wrong_state_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(3);
+		let receive = |id: ShipmentId, received: Vec<BottleId>| {
+			BottleTracking::receive_shipment(Origin::signed(RETAILER), id, received)
+		};
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottles[0].clone()]));
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, vec![bottles[1].clone()]));
+		assert_ok!(ship_through_customs(&shipment(3), vec![bottles[2].clone()]));
+
+		assert_noop!(receive(shipment(1), vec![bottle(1)]), Error::<Test>::ShipmentPending);
+
+		assert_ok!(BottleTracking::cancel_shipment(Origin::signed(MANUFACTURER), shipment(2)));
+		assert_noop!(receive(shipment(2), vec![bottle(2)]), Error::<Test>::ShipmentHasBeenCancelled);
+
+		assert_ok!(track(&shipment(3), ShipmentOperation::Pickup));
+		assert_noop!(receive(shipment(3), vec![bottle(3)]), Error::<Test>::ShipmentNotCleared);
+		assert_ok!(BottleTracking::hold_shipment(Origin::signed(CUSTOMS), shipment(3)));
+		assert_noop!(receive(shipment(3), vec![bottle(3)]), Error::<Test>::ShipmentInCustoms);
+
+		pickup_and_deliver(&shipment(1));
+		assert_noop!(receive(shipment(1), vec![bottle(1)]), Error::<Test>::ShipmentHasBeenDelivered);
+	});
+}
+
+#[test]
+fn receive_shipment_with_invalid_bottles_fails() {
+	new_test_ext().execute_with(|| {
+		setup(3);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1), bottle(2)]));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+
+		assert_noop!(
+			BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(1), vec![bottle(1), bottle(3)]),
+			Error::<Test>::BottleNotInShipment
+		);
+		assert_noop!(
+			BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(1), vec![bottle(1), bottle(1)]),
+			Error::<Test>::BottleReceivedTwice
+		);
+	});
+}
+
+// fund_shipment, cancel_shipment and reject_shipment
+
+#[test]
+fn fund_shipment_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles));
+
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+
+		assert_eq!(reserved(RETAILER), PRICE);
+		assert_eq!(free(RETAILER), INITIAL_BALANCE - PRICE);
+		assert!(ShipmentEscrows::<Test>::get(shipment(1)).unwrap().funded);
+		assert_eq!(last_event(), RawEvent::ShipmentFunded(shipment(1), RETAILER, PRICE));
+	});
+}
+
+#[test]
+fn fund_shipment_fails() {
+	new_test_ext().execute_with(|| {
+		setup(2);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1)]));
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, vec![bottle(2)]));
+
+		assert_noop!(
+			BottleTracking::fund_shipment(Origin::signed(RETAILER_2), shipment(1)),
+			Error::<Test>::NotShipmentReceiver
+		);
+		assert_noop!(
+			BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(2)),
+			Error::<Test>::ShipmentHasNoEscrow
+		);
+		assert_noop!(
+			BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(3)),
+			Error::<Test>::ShipmentDoesNotExist
+		);
+
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+		assert_noop!(
+			BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)),
+			Error::<Test>::ShipmentAlreadyFunded
+		);
+
+		assert_ok!(track(&shipment(2), ShipmentOperation::Pickup));
+		assert_noop!(
+			BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(2)),
+			Error::<Test>::ShipmentInTransit
+		);
+	});
+}
+
+#[test]
+fn cancel_shipment_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+
+		assert_ok!(BottleTracking::cancel_shipment(Origin::signed(MANUFACTURER), shipment(1)));
+
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::Cancelled);
+		assert_eq!(ShipmentEscrows::<Test>::get(shipment(1)), None);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE);
+		assert_eq!(free(RETAILER), INITIAL_BALANCE);
+		assert_eq!(last_event(), RawEvent::ShipmentCancelled(shipment(1), MANUFACTURER));
+
+		// The bottles are back with the sender.
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER_2, bottles));
+	});
+}
+
+#[test]
+fn cancel_shipment_fails() {
+	new_test_ext().execute_with(|| {
+		setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1)]));
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, vec![bottle(2)]));
+		let cancel = |id: ShipmentId| BottleTracking::cancel_shipment(Origin::signed(MANUFACTURER), id);
+
+		assert_noop!(
+			BottleTracking::cancel_shipment(Origin::signed(RETAILER), shipment(1)),
+			Error::<Test>::NotShipmentSender
+		);
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		assert_noop!(cancel(shipment(1)), Error::<Test>::ShipmentInTransit);
+		assert_ok!(track(&shipment(1), ShipmentOperation::Deliver));
+		assert_noop!(cancel(shipment(1)), Error::<Test>::ShipmentHasBeenDelivered);
+
+		assert_ok!(cancel(shipment(2)));
+		assert_noop!(cancel(shipment(2)), Error::<Test>::ShipmentHasBeenCancelled);
+	});
+}
+
+#[test]
+fn reject_shipment_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+
+		assert_noop!(
+			BottleTracking::reject_shipment(Origin::signed(MANUFACTURER), shipment(1)),
+			Error::<Test>::NotShipmentReceiver
+		);
+		assert_ok!(BottleTracking::reject_shipment(Origin::signed(RETAILER), shipment(1)));
+
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::Rejected);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE);
+		assert_eq!(free(RETAILER), INITIAL_BALANCE);
+		assert_eq!(last_event(), RawEvent::ShipmentRejected(shipment(1), RETAILER));
+		assert_noop!(
+			BottleTracking::reject_shipment(Origin::signed(RETAILER), shipment(1)),
+			Error::<Test>::ShipmentHasBeenCancelled
+		);
+
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER_2, bottles));
+	});
+}
+
+// Escrow settlement
+
+#[test]
+fn delivery_holds_escrow_until_dispute_period_is_over() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+
+		deliver_paid(&shipment(1), bottles);
+
+		let held_until = 1 + DisputePeriod::get();
+		assert_eq!(ShipmentEscrows::<Test>::get(shipment(1)).unwrap().held_until, Some(held_until));
+		assert!(event_exists(RawEvent::EscrowHeld(shipment(1), held_until)));
+		assert_eq!(reserved(RETAILER), 0);
+		assert_eq!(reserved(MANUFACTURER), PRICE);
+		assert_eq!(reserved(CARRIER), CARRIER_FEE);
+
+		run_to_block(held_until - 1);
+		assert_eq!(reserved(MANUFACTURER), PRICE);
+
+		run_to_block(held_until);
+		assert_eq!(ShipmentEscrows::<Test>::get(shipment(1)).unwrap().held_until, None);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE - CARRIER_FEE + PRICE);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE + CARRIER_FEE);
+		assert_eq!(free(RETAILER), INITIAL_BALANCE - PRICE);
+		assert_eq!(last_event(), RawEvent::EscrowReleased(shipment(1), PRICE, CARRIER_FEE));
+	});
+}
+
+#[test]
+fn partial_delivery_refunds_missing_share() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+
+		assert_ok!(BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(1), vec![bottle(1)]));
+
+		assert_eq!(free(RETAILER), INITIAL_BALANCE - PRICE / 2);
+		assert_eq!(reserved(MANUFACTURER), PRICE / 2);
+
+		run_to_block(1 + DisputePeriod::get());
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE - CARRIER_FEE + PRICE / 2);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE + CARRIER_FEE);
+	});
+}
+
+// Deadlines
+
+#[test]
+fn overdue_shipments_are_flagged() {
+	new_test_ext().execute_with(|| {
+		setup(2);
+		for (n, b) in [(1, 1), (2, 2)].iter() {
+			assert_ok!(BottleTracking::register_shipment(
+				Origin::signed(MANUFACTURER), shipment(*n), CARRIER, RETAILER, None, Some(5), vec![bottle(*b)], None,
+				vec![],
+			));
+		}
+		assert_eq!(ShipmentsDueAt::<Test>::get(5), vec![shipment(1), shipment(2)]);
+		pickup_and_deliver(&shipment(2));
+
+		run_to_block(4);
+		assert!(!Shipments::<Test>::get(shipment(1)).unwrap().overdue);
+
+		run_to_block(5);
+		assert!(Shipments::<Test>::get(shipment(1)).unwrap().overdue);
+		assert!(!Shipments::<Test>::get(shipment(2)).unwrap().overdue);
+		assert!(event_exists(RawEvent::ShipmentOverdue(shipment(1), 5)));
+		assert!(!event_exists(RawEvent::ShipmentOverdue(shipment(2), 5)));
+		assert!(ShipmentsDueAt::<Test>::get(5).is_empty());
+
+		// An overdue shipment can still be delivered.
+		pickup_and_deliver(&shipment(1));
+	});
+}
+
+// Disputes
+
+fn open_dispute(claims: Vec<(BottleId, BottleIncident)>, evidence: Vec<H256>) -> DispatchResult {
+	BottleTracking::open_dispute(Origin::signed(RETAILER), shipment(1), claims, evidence)
+}
+
+#[test]
+fn open_dispute_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		deliver_paid(&shipment(1), bottles);
+
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(2)));
+
+		let dispute = Disputes::<Test>::get(shipment(1)).expect("dispute should exist");
+		assert_eq!(dispute.claimant, RETAILER);
+		assert_eq!(dispute.claims, vec![(bottle(1), BottleIncident::Damaged)]);
+		assert_eq!(dispute.evidence, hashes(2));
+		assert!(dispute.responses.is_empty());
+		assert_eq!(last_event(), RawEvent::DisputeOpened(shipment(1), RETAILER));
+
+		assert_noop!(
+			open_dispute(vec![(bottle(2), BottleIncident::Damaged)], hashes(1)),
+			Error::<Test>::DisputeExists
+		);
+	});
+}
+
+#[test]
+fn open_dispute_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(3);
+		let damaged = |n| vec![(bottle(n), BottleIncident::Damaged)];
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles[..2].to_vec()));
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+
+		assert_noop!(open_dispute(damaged(1), hashes(1)), Error::<Test>::ShipmentNotDelivered);
+		pickup_and_deliver(&shipment(1));
+
+		assert_noop!(
+			BottleTracking::open_dispute(Origin::signed(MANUFACTURER), shipment(1), damaged(1), hashes(1)),
+			Error::<Test>::NotShipmentReceiver
+		);
+		assert_noop!(
+			BottleTracking::open_dispute(Origin::signed(RETAILER), shipment(2), damaged(1), hashes(1)),
+			Error::<Test>::ShipmentDoesNotExist
+		);
+		assert_noop!(open_dispute(vec![], hashes(1)), Error::<Test>::DisputeHasNoClaims);
+		assert_noop!(
+			open_dispute(vec![
+				(bottle(1), BottleIncident::Damaged),
+				(bottle(2), BottleIncident::Damaged),
+				(bottle(1), BottleIncident::Lost),
+			], hashes(1)),
+			Error::<Test>::DisputeHasTooManyClaims
+		);
+		assert_noop!(open_dispute(damaged(3), hashes(1)), Error::<Test>::BottleNotInShipment);
+		assert_noop!(
+			open_dispute(vec![(bottle(1), BottleIncident::Damaged), (bottle(1), BottleIncident::Lost)], hashes(1)),
+			Error::<Test>::BottleClaimedTwice
+		);
+		assert_noop!(open_dispute(damaged(1), vec![]), Error::<Test>::DisputeHasNoEvidence);
+		assert_noop!(
+			open_dispute(damaged(1), hashes(DISPUTE_MAX_EVIDENCE + 1)),
+			Error::<Test>::DisputeHasTooMuchEvidence
+		);
+
+		run_to_block(1 + DisputePeriod::get());
+		assert_noop!(open_dispute(damaged(1), hashes(1)), Error::<Test>::DisputePeriodOver);
+	});
+}
+
+#[test]
+fn unpaid_shipments_can_only_be_disputed_for_the_dispute_period() {
+	new_test_ext().execute_with(|| {
+		setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1)]));
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, vec![bottle(2)]));
+		pickup_and_deliver(&shipment(1));
+		pickup_and_deliver(&shipment(2));
+		assert_eq!(DisputableUntil::<Test>::get(shipment(1)), Some(1 + DisputePeriod::get()));
+
+		run_to_block(DisputePeriod::get());
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(1)));
+		assert_ok!(BottleTracking::rule_dispute(Origin::root(), shipment(1), Verdict::Dismissed));
+
+		// A ruling closes the dispute period.
+		assert_eq!(DisputableUntil::<Test>::get(shipment(1)), None);
+		assert_noop!(
+			open_dispute(vec![(bottle(1), BottleIncident::Lost)], hashes(1)),
+			Error::<Test>::DisputePeriodOver
+		);
+
+		run_to_block(1 + DisputePeriod::get());
+		assert_noop!(
+			BottleTracking::open_dispute(
+				Origin::signed(RETAILER), shipment(2), vec![(bottle(2), BottleIncident::Damaged)], hashes(1),
+			),
+			Error::<Test>::DisputePeriodOver
+		);
+	});
+}
+
+#[test]
+fn dispute_on_bottles_missing_on_receipt_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		assert_ok!(BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(1), vec![bottle(1)]));
+
+		assert_noop!(
+			open_dispute(vec![(bottle(2), BottleIncident::Lost)], hashes(1)),
+			Error::<Test>::BottleMissingOnReceipt
+		);
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(1)));
+	});
+}
+
+#[test]
+fn open_dispute_keeps_escrow_held() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		deliver_paid(&shipment(1), bottles);
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(1)));
+
+		run_to_block(1 + DisputePeriod::get());
+
+		assert_eq!(ShipmentEscrows::<Test>::get(shipment(1)).unwrap().held_until, Some(1 + DisputePeriod::get()));
+		assert_eq!(reserved(MANUFACTURER), PRICE);
+		assert_eq!(reserved(CARRIER), CARRIER_FEE);
+	});
+}
+
+#[test]
+fn respond_to_dispute_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		deliver_paid(&shipment(1), bottles);
+		let respond = |who: u64, evidence: Vec<H256>| {
+			BottleTracking::respond_to_dispute(Origin::signed(who), shipment(1), evidence)
+		};
+
+		assert_noop!(respond(CARRIER, hashes(1)), Error::<Test>::DisputeDoesNotExist);
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(1)));
+
+		assert_noop!(respond(RETAILER, hashes(1)), Error::<Test>::NotDisputeParty);
+		assert_noop!(respond(CARRIER, vec![]), Error::<Test>::DisputeHasNoEvidence);
+		assert_noop!(respond(CARRIER, hashes(DISPUTE_MAX_EVIDENCE + 1)), Error::<Test>::DisputeHasTooMuchEvidence);
+
+		assert_ok!(respond(CARRIER, hashes(1)));
+		assert_eq!(last_event(), RawEvent::DisputeResponded(shipment(1), CARRIER));
+		assert_ok!(respond(MANUFACTURER, hashes(2)));
+		assert_noop!(respond(CARRIER, hashes(1)), Error::<Test>::DisputeAlreadyAnswered);
+
+		assert_eq!(
+			Disputes::<Test>::get(shipment(1)).unwrap().responses,
+			vec![(CARRIER, hashes(1)), (MANUFACTURER, hashes(2))]
+		);
+	});
+}
+
+#[test]
+fn rule_dispute_requires_arbitrator() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		deliver_paid(&shipment(1), bottles);
+
+		assert_noop!(
+			BottleTracking::rule_dispute(Origin::root(), shipment(1), Verdict::Dismissed),
+			Error::<Test>::DisputeDoesNotExist
+		);
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(1)));
+		assert_noop!(
+			BottleTracking::rule_dispute(Origin::signed(RETAILER), shipment(1), Verdict::Dismissed),
+			DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn dismissed_dispute_releases_escrow() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		deliver_paid(&shipment(1), bottles);
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(1)));
+
+		assert_ok!(BottleTracking::rule_dispute(Origin::root(), shipment(1), Verdict::Dismissed));
+
+		assert_eq!(Disputes::<Test>::get(shipment(1)), None);
+		assert_eq!(ShipmentEscrows::<Test>::get(shipment(1)).unwrap().held_until, None);
+		assert_eq!(BottleIncidents::get(bottle(1)), None);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE - CARRIER_FEE + PRICE);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE + CARRIER_FEE);
+		assert_eq!(free(RETAILER), INITIAL_BALANCE - PRICE);
+		assert_eq!(last_event(), RawEvent::DisputeRuled(shipment(1), Verdict::Dismissed, 0));
+
+		// A ruling closes the dispute period.
+		assert_noop!(
+			open_dispute(vec![(bottle(2), BottleIncident::Damaged)], hashes(1)),
+			Error::<Test>::DisputePeriodOver
+		);
+	});
+}
+
+#[test]
+fn sender_liable_dispute_refunds_receiver() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		deliver_paid(&shipment(1), bottles);
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(1)));
+
+		assert_ok!(BottleTracking::rule_dispute(Origin::root(), shipment(1), Verdict::SenderLiable));
+
+		assert_eq!(BottleIncidents::get(bottle(1)), Some(BottleIncident::Damaged));
+		assert_eq!(free(RETAILER), INITIAL_BALANCE - PRICE / 2);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE - CARRIER_FEE + PRICE / 2);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE + CARRIER_FEE);
+		assert_eq!(last_event(), RawEvent::DisputeRuled(shipment(1), Verdict::SenderLiable, PRICE / 2));
+	});
+}
+
+#[test]
+fn dispute_after_partial_delivery_refunds_delivered_share() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(3);
+		assert_ok!(ship_paid(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(RETAILER), shipment(1)));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		assert_ok!(BottleTracking::receive_shipment(
+			Origin::signed(RETAILER), shipment(1), vec![bottle(1), bottle(2)],
+		));
+		assert_eq!(free(RETAILER), INITIAL_BALANCE - 2 * PRICE / 3);
+
+		assert_ok!(open_dispute(vec![(bottle(1), BottleIncident::Damaged)], hashes(1)));
+		assert_ok!(BottleTracking::rule_dispute(Origin::root(), shipment(1), Verdict::SenderLiable));
+
+		assert_eq!(free(RETAILER), INITIAL_BALANCE - PRICE / 3);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE - CARRIER_FEE + PRICE / 3);
+		assert_eq!(last_event(), RawEvent::DisputeRuled(shipment(1), Verdict::SenderLiable, PRICE / 3));
+	});
+}
+
+#[test]
+fn carrier_liable_dispute_forfeits_carrier_fee() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		deliver_paid(&shipment(1), bottles);
+		assert_ok!(open_dispute(vec![(bottle(2), BottleIncident::Lost)], hashes(1)));
+
+		assert_ok!(BottleTracking::rule_dispute(Origin::root(), shipment(1), Verdict::CarrierLiable));
+
+		assert_eq!(BottleIncidents::get(bottle(2)), Some(BottleIncident::Lost));
+		assert_eq!(free(RETAILER), INITIAL_BALANCE - PRICE / 2);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE + PRICE / 2);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE);
+		assert_eq!(reserved(MANUFACTURER) + reserved(CARRIER) + reserved(RETAILER), 0);
+
+		// The escrow is gone, reaching the end of the dispute period releases nothing twice.
+		run_to_block(1 + DisputePeriod::get());
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE + PRICE / 2);
+	});
+}
+
+// Customs
+
+#[test]
+fn customs_clearance_flow_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		Timestamp::set_timestamp(42);
+		assert_ok!(ship_through_customs(&shipment(1), bottles));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+
+		assert_ok!(BottleTracking::inspect_shipment(Origin::signed(CUSTOMS), shipment(1)));
+		assert_eq!(last_event(), RawEvent::ShipmentInspected(shipment(1), CUSTOMS));
+
+		assert_noop!(track(&shipment(1), ShipmentOperation::Deliver), Error::<Test>::ShipmentNotCleared);
+
+		assert_ok!(BottleTracking::hold_shipment(Origin::signed(CUSTOMS), shipment(1)));
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::Customs);
+		assert_eq!(last_event(), RawEvent::ShipmentHeldByCustoms(shipment(1), CUSTOMS));
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Scan));
+		assert_noop!(track(&shipment(1), ShipmentOperation::Deliver), Error::<Test>::ShipmentInCustoms);
+		assert_ok!(BottleTracking::inspect_shipment(Origin::signed(CUSTOMS), shipment(1)));
+
+		assert_ok!(BottleTracking::clear_shipment(
+			Origin::signed(CUSTOMS), shipment(1), b"ref-1".to_vec(), hashes(2),
+		));
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::InTransit);
+		assert_eq!(
+			ShipmentClearances::<Test>::get(shipment(1)),
+			Some(Clearance { reference: b"ref-1".to_vec(), documents: hashes(2), cleared_by: CUSTOMS, cleared: 42 })
+		);
+		assert_eq!(last_event(), RawEvent::ShipmentClearedByCustoms(shipment(1), CUSTOMS, b"ref-1".to_vec()));
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Deliver));
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::Delivered);
+	});
+}
+
+#[test]
+fn inspect_shipment_fails() {
+	new_test_ext().execute_with(|| {
+		setup(3);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1)]));
+		assert_ok!(ship_through_customs(&shipment(2), vec![bottle(2)]));
+		assert_ok!(ship_through_customs(&shipment(3), vec![bottle(3)]));
+		let inspect = |who: u64, id: ShipmentId| BottleTracking::inspect_shipment(Origin::signed(who), id);
+
+		assert_noop!(inspect(CUSTOMS, shipment(4)), Error::<Test>::ShipmentDoesNotExist);
+		assert_noop!(inspect(CUSTOMS, shipment(1)), Error::<Test>::ShipmentHasNoCustoms);
+		assert_noop!(inspect(CARRIER, shipment(2)), Error::<Test>::NotShipmentCustoms);
+		assert_noop!(inspect(CUSTOMS_2, shipment(2)), Error::<Test>::NotShipmentCustoms);
+		assert_noop!(inspect(CUSTOMS, shipment(2)), Error::<Test>::ShipmentPending);
+
+		assert_ok!(BottleTracking::cancel_shipment(Origin::signed(MANUFACTURER), shipment(2)));
+		assert_noop!(inspect(CUSTOMS, shipment(2)), Error::<Test>::ShipmentHasBeenCancelled);
+
+		assert_ok!(track(&shipment(3), ShipmentOperation::Pickup));
+		hold_and_clear(&shipment(3));
+		assert_ok!(track(&shipment(3), ShipmentOperation::Deliver));
+		assert_noop!(inspect(CUSTOMS, shipment(3)), Error::<Test>::ShipmentHasBeenDelivered);
+	});
+}
+
+#[test]
+fn hold_shipment_fails() {
+	new_test_ext().execute_with(|| {
+		setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1)]));
+		assert_ok!(ship_through_customs(&shipment(2), vec![bottle(2)]));
+		let hold = |who: u64, id: ShipmentId| BottleTracking::hold_shipment(Origin::signed(who), id);
+
+		assert_noop!(hold(CUSTOMS, shipment(3)), Error::<Test>::ShipmentDoesNotExist);
+		assert_noop!(hold(CUSTOMS, shipment(1)), Error::<Test>::ShipmentHasNoCustoms);
+		assert_noop!(hold(CUSTOMS_2, shipment(2)), Error::<Test>::NotShipmentCustoms);
+		assert_noop!(hold(CUSTOMS, shipment(2)), Error::<Test>::ShipmentPending);
+
+		assert_ok!(track(&shipment(2), ShipmentOperation::Pickup));
+		assert_ok!(hold(CUSTOMS, shipment(2)));
+		assert_noop!(hold(CUSTOMS, shipment(2)), Error::<Test>::ShipmentInCustoms);
+
+		assert_ok!(BottleTracking::clear_shipment(
+			Origin::signed(CUSTOMS), shipment(2), b"ref-1".to_vec(), hashes(1),
+		));
+		assert_noop!(hold(CUSTOMS, shipment(2)), Error::<Test>::ShipmentHasBeenCleared);
+	});
+}
+
+#[test]
+fn clear_shipment_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship_through_customs(&shipment(1), bottles));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		let clear = |who: u64, reference: Vec<u8>, documents: Vec<H256>| {
+			BottleTracking::clear_shipment(Origin::signed(who), shipment(1), reference, documents)
+		};
+
+		assert_noop!(clear(CUSTOMS, b"ref-1".to_vec(), hashes(1)), Error::<Test>::ShipmentNotHeldByCustoms);
+
+		assert_ok!(BottleTracking::hold_shipment(Origin::signed(CUSTOMS), shipment(1)));
+		assert_noop!(clear(CUSTOMS, vec![], hashes(1)), Error::<Test>::ClearanceReferenceMissing);
+		assert_noop!(
+			clear(CUSTOMS, vec![b'r'; CLEARANCE_REFERENCE_MAX_LENGTH + 1], hashes(1)),
+			Error::<Test>::ClearanceReferenceTooLong
+		);
+		assert_noop!(clear(CUSTOMS, b"ref-1".to_vec(), vec![]), Error::<Test>::ClearanceHasNoDocuments);
+		assert_noop!(
+			clear(CUSTOMS, b"ref-1".to_vec(), hashes(CLEARANCE_MAX_DOCUMENTS + 1)),
+			Error::<Test>::ClearanceHasTooManyDocuments
+		);
+		assert_noop!(clear(CUSTOMS_2, b"ref-1".to_vec(), hashes(1)), Error::<Test>::NotShipmentCustoms);
+	});
+}
+
+// sell_to_customer
+
+#[test]
+fn sell_to_customer_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		pickup_and_deliver(&shipment(1));
+
+		assert_ok!(BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, bottles.clone()));
+
+		assert_eq!(BottlesOfCustomer::<Test>::get(CUSTOMER), bottles);
+		assert_eq!(BottleSoldTo::<Test>::get(bottle(1)), Some(CUSTOMER));
+		assert_eq!(BottleSoldTo::<Test>::get(bottle(2)), Some(CUSTOMER));
+		assert_eq!(last_event(), RawEvent::BottlesSoldToCustomer(CUSTOMER));
+	});
+}
+
+#[test]
+fn sell_to_customer_with_invalid_parties_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		pickup_and_deliver(&shipment(1));
+
+		assert_noop!(
+			BottleTracking::sell_to_customer(Origin::signed(MANUFACTURER), CUSTOMER, bottles.clone()),
+			registrar::Error::<Test>::NotRetailer
+		);
+		assert_noop!(
+			BottleTracking::sell_to_customer(Origin::signed(RETAILER), RETAILER_2, bottles.clone()),
+			registrar::Error::<Test>::NotCustomer
+		);
+		assert_noop!(
+			BottleTracking::sell_to_customer(Origin::signed(RETAILER_2), CUSTOMER, bottles.clone()),
+			registrar::Error::<Test>::NotBottleOwner
+		);
+		assert_noop!(
+			BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, vec![bottle(1), bottle(1)]),
+			Error::<Test>::BottleListedTwice
+		);
+	});
+}
+
+#[test]
+fn sell_bottle_without_custody_fails() {
+	new_test_ext().execute_with(|| {
+		setup(3);
+		let sell = |b: u8| BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, vec![bottle(b)]);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottle(1)]));
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, vec![bottle(2)]));
+
+		assert_noop!(sell(3), Error::<Test>::BottleNotShipped);
+		assert_noop!(
+			BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, vec![b"unknown".to_vec()]),
+			registrar::Error::<Test>::BottleNotExist
+		);
+		assert_noop!(sell(1), Error::<Test>::ShipmentPending);
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		assert_noop!(sell(1), Error::<Test>::ShipmentInTransit);
+
+		assert_ok!(BottleTracking::reject_shipment(Origin::signed(RETAILER), shipment(2)));
+		assert_noop!(sell(2), registrar::Error::<Test>::NotBottleOwner);
+	});
+}
+
+#[test]
+fn sold_bottles_cannot_be_sold_or_shipped_again() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		pickup_and_deliver(&shipment(1));
+		assert_ok!(BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, bottles.clone()));
+
+		assert_noop!(
+			BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, bottles.clone()),
+			Error::<Test>::BottleAlreadySold
+		);
+		assert_noop!(ship(&shipment(2), RETAILER, RETAILER_2, bottles), Error::<Test>::BottleAlreadySold);
+	});
+}
+
+// report_telemetry
+
+fn telemetry(recorded: u64) -> Telemetry {
+	Telemetry { latitude: 49_258_300, longitude: 4_031_700, temperature: 1_150, recorded }
+}
+
+fn report(carrier: u64, reports: Vec<(ShipmentId, Telemetry)>) -> DispatchResult {
+	BottleTracking::report_telemetry(Origin::signed(carrier), reports)
+}
+
+#[test]
+fn report_telemetry_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+
+		assert_ok!(report(CARRIER, vec![(shipment(1), telemetry(20)), (shipment(1), telemetry(10))]));
+
+		assert_eq!(LastTelemetry::get(shipment(1)), Some(telemetry(20)));
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::InTransit);
+		assert_eq!(last_event(), RawEvent::ShipmentTelemetryReported(shipment(1), CARRIER, telemetry(10)));
+	});
+}
+
+#[test]
+fn report_telemetry_of_shipment_held_by_customs_works() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship_through_customs(&shipment(1), bottles));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		assert_ok!(BottleTracking::hold_shipment(Origin::signed(CUSTOMS), shipment(1)));
+
+		assert_ok!(report(CARRIER, vec![(shipment(1), telemetry(10))]));
+
+		assert_eq!(LastTelemetry::get(shipment(1)), Some(telemetry(10)));
+	});
+}
+
+#[test]
+fn report_telemetry_fails() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottles[0].clone()]));
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, vec![bottles[1].clone()]));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		let invalid = Telemetry { latitude: 90_000_001, ..telemetry(10) };
+
+		assert_noop!(report(RETAILER, vec![(shipment(1), telemetry(10))]), registrar::Error::<Test>::NotCarrier);
+		assert_noop!(report(CARRIER_2, vec![(shipment(1), telemetry(10))]), Error::<Test>::NotShipmentCarrier);
+		assert_noop!(report(CARRIER, vec![]), Error::<Test>::TelemetryHasNoReports);
+		assert_noop!(
+			report(CARRIER, vec![(shipment(1), telemetry(10)); TELEMETRY_MAX_REPORTS + 1]),
+			Error::<Test>::TelemetryHasTooManyReports
+		);
+		assert_noop!(report(CARRIER, vec![(shipment(1), invalid)]), Error::<Test>::InvalidTelemetry);
+		assert_noop!(report(CARRIER, vec![(shipment(3), telemetry(10))]), Error::<Test>::ShipmentDoesNotExist);
+		assert_noop!(
+			report(CARRIER, vec![(shipment(1), telemetry(10)), (shipment(2), telemetry(10))]),
+			Error::<Test>::ShipmentPending
+		);
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Deliver));
+		assert_noop!(report(CARRIER, vec![(shipment(1), telemetry(10))]), Error::<Test>::ShipmentHasBeenDelivered);
+	});
+}
+
+// Offchain worker
+
+const FEED: &str = "http://localhost:8080/feed";
+
+const READINGS: &str = r#"[
+	{"shipment": "shipment-1", "latitude": 49.2583, "longitude": 4.0317, "temperature": 11.5, "timestamp": 2000},
+	{"shipment": "shipment-1", "latitude": -33.8688, "longitude": 151.2093, "temperature": -2.25, "timestamp": 1000},
+	{"shipment": "shipment-2", "latitude": 49.2583, "longitude": 4.0317, "temperature": 11.5, "timestamp": 3000}
+]"#;
+
+/// Externalities with offchain and transaction pool extensions, and the carrier key in the keystore.
+fn offchain_test_ext() -> (
+	sp_io::TestExternalities,
+	std::sync::Arc<parking_lot::RwLock<testing::OffchainState>>,
+	std::sync::Arc<parking_lot::RwLock<testing::PoolState>>,
+) {
+	let (offchain, offchain_state) = testing::TestOffchainExt::new();
+	let (pool, pool_state) = testing::TestTransactionPoolExt::new();
+	let mut ext = new_test_ext();
+	ext.register_extension(OffchainExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+	UintAuthorityId::set_all_keys(vec![CARRIER]);
+	(ext, offchain_state, pool_state)
+}
+
+fn expect_feed(state: &parking_lot::RwLock<testing::OffchainState>, body: &str) {
+	state.write().expect_request(testing::PendingRequest {
+		method: "GET".into(),
+		uri: FEED.into(),
+		response: Some(body.as_bytes().to_vec()),
+		sent: true,
+		..Default::default()
+	});
+}
+
+/// Ships the bottles `1` and `2` by two shipments, and picks up the first one.
+fn setup_feed() {
+	let bottles = setup(2);
+	assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, vec![bottles[0].clone()]));
+	assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, vec![bottles[1].clone()]));
+	assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+	sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, &offchain::feed_key(&CARRIER), FEED.as_bytes());
+}
+
+#[test]
+fn offchain_worker_reports_device_feed() {
+	let (mut ext, offchain_state, pool_state) = offchain_test_ext();
+	ext.execute_with(|| {
+		setup_feed();
+		expect_feed(&offchain_state, READINGS);
+
+		BottleTracking::offchain_worker(FeedPollInterval::get());
+
+		let transaction = pool_state.write().transactions.pop().expect("report expected");
+		assert!(pool_state.read().transactions.is_empty());
+		let transaction = TestXt::<Call, ()>::decode(&mut &transaction[..]).unwrap();
+		assert_eq!(transaction.signature, Some((0, ())));
+		assert_eq!(transaction.call, Call::BottleTracking(crate::Call::report_telemetry(vec![
+			(shipment(1), Telemetry { latitude: -33_868_800, longitude: 151_209_300, temperature: -225, recorded: 1000 }),
+			(shipment(1), telemetry(2000)),
+		])));
+	});
+}
+
+#[test]
+fn offchain_worker_skips_reported_readings() {
+	let (mut ext, offchain_state, pool_state) = offchain_test_ext();
+	ext.execute_with(|| {
+		setup_feed();
+		expect_feed(&offchain_state, READINGS);
+		BottleTracking::offchain_worker(FeedPollInterval::get());
+		pool_state.write().transactions.clear();
+
+		// Off the poll interval, the feed is not fetched.
+		BottleTracking::offchain_worker(FeedPollInterval::get() + 1);
+		expect_feed(&offchain_state, READINGS);
+		BottleTracking::offchain_worker(2 * FeedPollInterval::get());
+
+		assert!(pool_state.read().transactions.is_empty());
+	});
+}
+
+#[test]
+fn device_feeds_skip_unreadable_readings() {
+	let readings = offchain::parse_readings(br#"[
+		{"shipment": "shipment-1", "latitude": 4.92583e1, "longitude": 4.0317, "temperature": 11.5, "timestamp": 1000},
+		{"shipment": "shipment-1", "latitude": 49.2583, "longitude": 4.0317},
+		{"shipment": "shipment-1", "latitude": 49.2583, "longitude": 4.0317, "temperature": 400.0, "timestamp": 1000},
+		{"shipment": "shipment-1", "latitude": 49.2583, "longitude": 4.0317, "temperature": 11.5, "timestamp": -1},
+		{"shipment": "shipment-1", "latitude": 49.25830012, "longitude": 4.0317, "temperature": 11.5, "timestamp": 2000}
+	]"#);
+
+	assert_eq!(readings, Some(vec![offchain::Reading { shipment: shipment(1), telemetry: telemetry(2000) }]));
+	assert_eq!(offchain::parse_readings(br#"{"shipment": "shipment-1"}"#), None);
+	assert_eq!(offchain::parse_readings(b"not json"), None);
+}
+
+// Device scans
+
+fn scan(nonce: u64) -> ScanPayload<UintAuthorityId, H256> {
+	let genesis = System::block_hash(0);
+	ScanPayload { device: UintAuthorityId(DEVICE), shipment: shipment(1), nonce, genesis }
+}
+
+fn signed_by(key: u64, payload: ScanPayload<UintAuthorityId, H256>) -> crate::Call<Test> {
+	let signature = UintAuthorityId(key).sign(&payload.encode()).unwrap();
+	crate::Call::submit_scan_unsigned(payload, signature)
+}
+
+fn validate(call: &crate::Call<Test>) -> TransactionValidity {
+	<BottleTracking as ValidateUnsigned>::validate_unsigned(TransactionSource::External, call)
+}
+
+fn submit(payload: ScanPayload<UintAuthorityId, H256>) -> DispatchResult {
+	let signature = UintAuthorityId(DEVICE).sign(&payload.encode()).unwrap();
+	BottleTracking::submit_scan_unsigned(Origin::none(), payload, signature)
+}
+
+/// Registers the device of the carrier, and a shipment of a bottle it picked up.
+fn setup_device() {
+	let bottles = setup(1);
+	assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles));
+	assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+	assert_ok!(BottleTracking::register_device(Origin::signed(CARRIER), DEVICE));
+}
+
+#[test]
+fn register_and_remove_device_works() {
+	new_test_ext().execute_with(|| {
+		setup(0);
+
+		assert_ok!(BottleTracking::register_device(Origin::signed(CARRIER), DEVICE));
+		assert_eq!(Devices::<Test>::get(DEVICE).map(|device| device.carrier), Some(CARRIER));
+		assert_eq!(last_event(), RawEvent::DeviceRegistered(CARRIER, DEVICE));
+
+		assert_ok!(BottleTracking::remove_device(Origin::signed(CARRIER), DEVICE));
+		assert!(!Devices::<Test>::contains_key(DEVICE));
+		assert_eq!(last_event(), RawEvent::DeviceRemoved(CARRIER, DEVICE));
+	});
+}
+
+#[test]
+fn register_and_remove_device_fails() {
+	new_test_ext().execute_with(|| {
+		setup(0);
+
+		assert_noop!(BottleTracking::register_device(Origin::signed(RETAILER), DEVICE), registrar::Error::<Test>::NotCarrier);
+		assert_noop!(BottleTracking::remove_device(Origin::signed(CARRIER), DEVICE), Error::<Test>::DeviceNotRegistered);
+
+		assert_ok!(BottleTracking::register_device(Origin::signed(CARRIER), DEVICE));
+		assert_noop!(
+			BottleTracking::register_device(Origin::signed(CARRIER_2), DEVICE),
+			Error::<Test>::DeviceAlreadyRegistered
+		);
+		assert_noop!(BottleTracking::remove_device(Origin::signed(CARRIER_2), DEVICE), Error::<Test>::NotDeviceCarrier);
+	});
+}
+
+#[test]
+fn submit_scan_unsigned_works() {
+	new_test_ext().execute_with(|| {
+		setup_device();
+
+		let valid = validate(&signed_by(DEVICE, scan(0))).expect("scan should be valid");
+		assert_eq!(valid.priority, UnsignedScanPriority::get());
+		assert_eq!(valid.provides, vec![("BottleTrackingScan", (DEVICE, 0u64)).encode()]);
+		assert!(valid.requires.is_empty());
+		assert_eq!(valid.longevity, DeviceScanWindow::get());
+
+		assert_ok!(submit(scan(0)));
+
+		assert_eq!(DeviceNonces::<Test>::get(DEVICE), 1);
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::InTransit);
+		assert_eq!(last_event(), RawEvent::ShipmentScanned(shipment(1), CARRIER, DEVICE));
+	});
+}
+
+#[test]
+fn scan_signed_by_another_key_is_rejected() {
+	new_test_ext().execute_with(|| {
+		setup_device();
+
+		assert_eq!(validate(&signed_by(STRANGER, scan(0))), InvalidTransaction::BadProof.into());
+	});
+}
+
+#[test]
+fn scans_cannot_be_replayed() {
+	new_test_ext().execute_with(|| {
+		setup_device();
+		assert_ok!(submit(scan(0)));
+
+		assert_eq!(validate(&signed_by(DEVICE, scan(0))), InvalidTransaction::Stale.into());
+		assert_noop!(submit(scan(0)), Error::<Test>::InvalidScanNonce);
+
+		// The nonce survives the device, so registering it again does not open a replay.
+		assert_ok!(BottleTracking::remove_device(Origin::signed(CARRIER), DEVICE));
+		assert_ok!(BottleTracking::register_device(Origin::signed(CARRIER), DEVICE));
+		assert_eq!(validate(&signed_by(DEVICE, scan(0))), InvalidTransaction::Stale.into());
+	});
+}
+
+#[test]
+fn scans_ahead_of_the_nonce_wait_for_the_previous_ones() {
+	new_test_ext().execute_with(|| {
+		setup_device();
+
+		let valid = validate(&signed_by(DEVICE, scan(2))).expect("scan should be valid");
+		assert_eq!(valid.requires, vec![("BottleTrackingScan", (DEVICE, 1u64)).encode()]);
+		assert_noop!(submit(scan(2)), Error::<Test>::InvalidScanNonce);
+	});
+}
+
+#[test]
+fn scans_far_ahead_of_the_nonce_are_rejected() {
+	new_test_ext().execute_with(|| {
+		setup_device();
+		let last = MaxDeviceScans::get() as u64;
+
+		assert!(validate(&signed_by(DEVICE, scan(last))).is_ok());
+		assert_eq!(validate(&signed_by(DEVICE, scan(last + 1))), InvalidTransaction::Future.into());
+	});
+}
+
+#[test]
+fn scans_for_another_chain_are_rejected() {
+	new_test_ext().execute_with(|| {
+		setup_device();
+		let payload = ScanPayload { genesis: H256::repeat_byte(1), ..scan(0) };
+
+		assert_eq!(validate(&signed_by(DEVICE, payload.clone())), InvalidTransaction::BadProof.into());
+		assert_noop!(submit(payload), Error::<Test>::ScanForAnotherChain);
+	});
+}
+
+#[test]
+fn device_scans_are_rate_limited() {
+	new_test_ext().execute_with(|| {
+		setup_device();
+		for nonce in 0..MaxDeviceScans::get() as u64 {
+			assert_ok!(submit(scan(nonce)));
+		}
+		let next = MaxDeviceScans::get() as u64;
+
+		assert_eq!(validate(&signed_by(DEVICE, scan(next))), InvalidTransaction::ExhaustsResources.into());
+		assert_noop!(submit(scan(next)), Error::<Test>::TooManyScans);
+
+		run_to_block(1 + DeviceScanWindow::get());
+		assert_ok!(submit(scan(next)));
+	});
+}
+
+#[test]
+fn scans_of_unknown_devices_or_shipments_are_rejected() {
+	new_test_ext().execute_with(|| {
+		setup_device();
+		assert_ok!(track(&shipment(1), ShipmentOperation::Deliver));
+
+		assert_eq!(validate(&signed_by(DEVICE, scan(0))), InvalidTransaction::Call.into());
+		assert_noop!(submit(scan(0)), Error::<Test>::ShipmentHasBeenDelivered);
+
+		assert_ok!(BottleTracking::remove_device(Origin::signed(CARRIER), DEVICE));
+		assert_noop!(submit(scan(0)), Error::<Test>::DeviceNotRegistered);
+	});
+}
+
+// Custody
+
+#[test]
+fn custody_follows_closed_shipments() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(1);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, DISTRIBUTOR, bottles.clone()));
+		pickup_and_deliver(&shipment(1));
+
+		assert_noop!(
+			ship(&shipment(2), MANUFACTURER, RETAILER, bottles.clone()),
+			registrar::Error::<Test>::NotBottleOwner
+		);
+		assert_ok!(BottleTracking::check_bottle_custody(&bottle(1), &DISTRIBUTOR));
+
+		assert_ok!(ship(&shipment(2), DISTRIBUTOR, RETAILER, bottles.clone()));
+		assert_ok!(BottleTracking::reject_shipment(Origin::signed(RETAILER), shipment(2)));
+		assert_ok!(BottleTracking::check_bottle_custody(&bottle(1), &DISTRIBUTOR));
+		assert_noop!(BottleTracking::check_bottle_custody(&bottle(1), &RETAILER), registrar::Error::<Test>::NotBottleOwner);
+	});
+}
+
+#[test]
+fn registrar_owner_follows_shipments() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		let owner = |b: u8| RegistrarModule::bottle_by_id(bottle(b)).unwrap().owner().clone();
+		assert_eq!(owner(1), MANUFACTURER);
+
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		assert_eq!(owner(1), CARRIER);
+		assert_ok!(BottleTracking::cancel_shipment(Origin::signed(MANUFACTURER), shipment(1)));
+		assert_eq!(owner(1), MANUFACTURER);
+
+		assert_ok!(ship(&shipment(2), MANUFACTURER, RETAILER, bottles.clone()));
+		assert_ok!(track(&shipment(2), ShipmentOperation::Pickup));
+		assert_ok!(BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(2), vec![bottle(1)]));
+		assert_eq!(owner(1), RETAILER);
+		assert_eq!(owner(2), CARRIER);
+
+		assert_ok!(BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, vec![bottle(1)]));
+		assert_eq!(owner(1), CUSTOMER);
+	});
+}
+
+#[test]
+fn bottle_status_follows_shipments() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		let status = |b: u8| RegistrarModule::bottle_by_id(bottle(b)).unwrap().status().clone();
+		assert_eq!(status(1), BottleStatus::Manufactured);
+
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles.clone()));
+		assert_eq!(status(1), BottleStatus::ShipmentRegistered);
+		assert_ok!(BottleTracking::reject_shipment(Origin::signed(RETAILER), shipment(1)));
+		assert_eq!(status(1), BottleStatus::Manufactured);
+
+		assert_ok!(ship(&shipment(2), MANUFACTURER, DISTRIBUTOR, bottles.clone()));
+		assert_ok!(track(&shipment(2), ShipmentOperation::Scan));
+		assert_eq!(status(1), BottleStatus::ShipmentRegistered);
+		assert_ok!(track(&shipment(2), ShipmentOperation::Pickup));
+		assert_eq!(status(1), BottleStatus::ShipmentInTransit);
+		assert_ok!(BottleTracking::receive_shipment(Origin::signed(DISTRIBUTOR), shipment(2), vec![bottle(1)]));
+		assert_eq!(status(1), BottleStatus::ShipmentDelivered);
+		assert_eq!(status(2), BottleStatus::ShipmentInTransit);
+
+		assert_ok!(ship(&shipment(3), DISTRIBUTOR, RETAILER, vec![bottle(1)]));
+		assert_ok!(BottleTracking::cancel_shipment(Origin::signed(DISTRIBUTOR), shipment(3)));
+		assert_eq!(status(1), BottleStatus::ShipmentDelivered);
+
+		// Not delivered without a pickup scan.
+		assert_ok!(ship(&shipment(4), DISTRIBUTOR, RETAILER, vec![bottle(1)]));
+		assert_noop!(track(&shipment(4), ShipmentOperation::Deliver), Error::<Test>::ShipmentPending);
+		pickup_and_deliver(&shipment(4));
+		assert_eq!(status(1), BottleStatus::ShipmentDelivered);
+
+		assert_ok!(BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, vec![bottle(1)]));
+		assert_eq!(status(1), BottleStatus::SoldToCustomer);
+		assert_eq!(RegistrarModule::bottles_with_status(BottleStatus::SoldToCustomer), vec![bottle(1)]);
+		assert_eq!(RegistrarModule::bottles_with_status(BottleStatus::ShipmentInTransit), vec![bottle(2)]);
+	});
+}
+
+#[test]
+fn shipment_operation_maps_to_bottle_status() {
+	assert_eq!(BottleStatus::from(ShipmentOperation::Pickup), BottleStatus::ShipmentInTransit);
+	assert_eq!(BottleStatus::from(ShipmentOperation::Scan), BottleStatus::ShipmentInTransit);
+	assert_eq!(BottleStatus::from(ShipmentOperation::Deliver), BottleStatus::ShipmentDelivered);
+}
+
+// End to end
+
+#[test]
+fn bottle_travels_from_manufacturer_to_customer() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+
+		// Manufacturer to warehouse, paid through escrow.
+		assert_ok!(BottleTracking::register_shipment(
+			Origin::signed(MANUFACTURER), shipment(1), CARRIER, WAREHOUSE, None, Some(20), bottles.clone(), payment(),
+			vec![],
+		));
+		assert_ok!(BottleTracking::fund_shipment(Origin::signed(WAREHOUSE), shipment(1)));
+		pickup_and_deliver(&shipment(1));
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::Stored);
+
+		// Warehouse to retailer, across a border.
+		assert_ok!(BottleTracking::register_shipment(
+			Origin::signed(WAREHOUSE), shipment(2), CARRIER_2, RETAILER, Some(CUSTOMS), None, bottles.clone(), None,
+			vec![],
+		));
+		assert_ok!(BottleTracking::track_shipment(Origin::signed(CARRIER_2), shipment(2), ShipmentOperation::Pickup));
+		hold_and_clear(&shipment(2));
+		assert_ok!(BottleTracking::receive_shipment(Origin::signed(RETAILER), shipment(2), bottles.clone()));
+		assert_eq!(status_of(&shipment(2)), ShipmentStatus::Delivered);
+
+		// Retailer to customer.
+		assert_ok!(BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, bottles.clone()));
+		assert_eq!(BottlesOfCustomer::<Test>::get(CUSTOMER), bottles);
+
+		run_to_block(20);
+		assert!(!Shipments::<Test>::get(shipment(1)).unwrap().overdue);
+		assert_eq!(free(MANUFACTURER), INITIAL_BALANCE - CARRIER_FEE + PRICE);
+		assert_eq!(free(WAREHOUSE), INITIAL_BALANCE - PRICE);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE + CARRIER_FEE);
+		assert_eq!(tracking_events().iter().filter(|e| matches!(e, RawEvent::ShipmentOverdue(..))).count(), 0);
+	});
+}
+
+// Migrations
+
+/// Stores a bottle and a shipment of the bottles the way the first release did.
+fn put_v1_bottle(n: u8) {
+	let old = registrar::migrations::v1::Bottle { id: bottle(n), manufacturer: MANUFACTURER, registered: 1u64 };
+	put_storage_value(b"RegistrarModule", b"Bottles", &Blake2_128Concat::hash(&bottle(n).encode()), old);
+	registrar::ManufacturerOf::<Test>::insert(bottle(n), MANUFACTURER);
+}
+
+fn put_v1_shipment(n: u8, status: ShipmentStatus, registered: u64, bottles: Vec<BottleId>) {
+	for b in &bottles {
+		BottleOfShipment::insert(b, shipment(n));
+	}
+	let old = migrations::v1::Shipment {
+		id: shipment(n),
+		manufacturer: MANUFACTURER,
+		carrier: CARRIER,
+		retailer: RETAILER,
+		bottles,
+		delivered: if status == ShipmentStatus::Delivered { Some(registered + 1) } else { None },
+		status,
+		registered,
+	};
+	put_storage_value(b"BottleTracking", b"Shipments", &Blake2_128Concat::hash(&shipment(n).encode()), old);
+	put_storage_value(b"BottleTracking", b"ShipmentsOfManufacturer", &Blake2_128Concat::hash(&MANUFACTURER.encode()), vec![shipment(n)]);
+	put_storage_value(b"BottleTracking", b"ShipmentsOfRetailer", &Blake2_128Concat::hash(&RETAILER.encode()), vec![shipment(n)]);
+}
+
+fn populate_v1() {
+	register_members();
+	StorageVersion::put(Releases::V1_0_0);
+	registrar::StorageVersion::put(registrar::Releases::V1_0_0);
+
+	for n in 0..5 {
+		put_v1_bottle(n);
+	}
+	put_v1_shipment(1, ShipmentStatus::Delivered, 10, vec![bottle(1), bottle(2)]);
+	put_v1_shipment(2, ShipmentStatus::Pending, 30, vec![bottle(3)]);
+	put_v1_shipment(3, ShipmentStatus::InTransit, 20, vec![bottle(4)]);
+	BottleSoldTo::<Test>::insert(bottle(2), CUSTOMER);
+	BottlesOfCustomer::<Test>::insert(CUSTOMER, vec![bottle(2)]);
+}
+
+fn custody(n: u8) -> (u64, BottleStatus) {
+	let bottle = RegistrarModule::bottle_by_id(bottle(n)).unwrap();
+	(*bottle.owner(), bottle.status().clone())
+}
+
+#[test]
+fn genesis_starts_on_the_latest_storage_version() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(BottleTracking::storage_version(), Releases::V2_0_0);
+		assert_eq!(RegistrarModule::storage_version(), registrar::Releases::V2_0_0);
+	});
+}
+
+#[test]
+fn migration_from_v1_upgrades_shipments_and_indexes() {
+	new_test_ext().execute_with(|| {
+		populate_v1();
+		assert_ok!(migrations::pre_migrate::<Test>());
+
+		BottleTracking::on_runtime_upgrade();
+
+		assert_ok!(migrations::post_migrate::<Test>());
+		assert_eq!(BottleTracking::storage_version(), Releases::V2_0_0);
+		assert_eq!(RegistrarModule::storage_version(), registrar::Releases::V2_0_0);
+
+		let delivered = Shipments::<Test>::get(shipment(1)).expect("shipment should be migrated");
+		assert_eq!(delivered.sender, MANUFACTURER);
+		assert_eq!(delivered.receiver, RETAILER);
+		assert_eq!(delivered.status, ShipmentStatus::Delivered);
+		assert_eq!(delivered.delivered, Some(11));
+		assert!(delivered.missing.is_empty() && delivered.customs.is_none() && delivered.deadline.is_none());
+
+		let in_order = vec![shipment(1), shipment(3), shipment(2)];
+		assert_eq!(ShipmentsOfSender::<Test>::get(MANUFACTURER), in_order);
+		assert_eq!(ShipmentsOfCarrier::<Test>::get(CARRIER), in_order);
+		assert_eq!(ShipmentsOfReceiver::<Test>::get(RETAILER), in_order);
+	});
+}
+
+#[test]
+fn migration_from_v1_puts_bottles_where_shipments_left_them() {
+	new_test_ext().execute_with(|| {
+		populate_v1();
+
+		BottleTracking::on_runtime_upgrade();
+
+		assert_eq!(custody(0), (MANUFACTURER, BottleStatus::Manufactured));
+		assert_eq!(custody(1), (RETAILER, BottleStatus::ShipmentDelivered));
+		assert_eq!(custody(2), (CUSTOMER, BottleStatus::SoldToCustomer));
+		assert_eq!(custody(3), (CARRIER, BottleStatus::ShipmentRegistered));
+		assert_eq!(custody(4), (CARRIER, BottleStatus::ShipmentInTransit));
+
+		// The migrated state carries on through the current lifecycle.
+		assert_ok!(BottleTracking::sell_to_customer(Origin::signed(RETAILER), CUSTOMER, vec![bottle(1)]));
+		assert_ok!(ship(&shipment(4), MANUFACTURER, DISTRIBUTOR, vec![bottle(0)]));
+		assert_ok!(track(&shipment(2), ShipmentOperation::Pickup));
+		assert_ok!(track(&shipment(2), ShipmentOperation::Deliver));
+		assert_eq!(custody(3), (RETAILER, BottleStatus::ShipmentDelivered));
+		assert_eq!(BottlesOfCustomer::<Test>::get(CUSTOMER), vec![bottle(2), bottle(1)]);
+		assert_ok!(migrations::post_migrate::<Test>());
+	});
+}
+
+#[test]
+fn migration_leaves_current_storage_alone() {
+	new_test_ext().execute_with(|| {
+		let bottles = setup(2);
+		assert_ok!(ship(&shipment(1), MANUFACTURER, RETAILER, bottles));
+		let before = Shipments::<Test>::get(shipment(1));
+
+		BottleTracking::on_runtime_upgrade();
+
+		assert_eq!(Shipments::<Test>::get(shipment(1)), before);
+		assert_eq!(ShipmentsOfSender::<Test>::get(MANUFACTURER), vec![shipment(1)]);
+		assert_eq!(custody(1), (CARRIER, BottleStatus::ShipmentRegistered));
+		assert_ok!(migrations::post_migrate::<Test>());
+	});
+}
+
+// Genesis
+
+#[test]
+fn genesis_seeds_shipments() {
+	use registrar::MemberType;
+
+	test_ext_with(
+		registrar::GenesisConfig::<Test> {
+			members: vec![
+				(MemberType::Manufacturer, MANUFACTURER),
+				(MemberType::Carrier, CARRIER),
+				(MemberType::Retailer, RETAILER),
+			],
+			bottles: vec![(MANUFACTURER, bottle(1)), (MANUFACTURER, bottle(2))],
+		},
+		crate::GenesisConfig::<Test> {
+			shipments: vec![(shipment(1), MANUFACTURER, CARRIER, RETAILER, vec![bottle(1)])],
+		},
+	).execute_with(|| {
+		assert_eq!(status_of(&shipment(1)), ShipmentStatus::Pending);
+		assert_eq!(ShipmentsOfSender::<Test>::get(MANUFACTURER), vec![shipment(1)]);
+		assert_eq!(BottleOfShipment::get(bottle(1)), Some(shipment(1)));
+		assert_eq!(RegistrarModule::bottle_by_id(bottle(1)).unwrap().owner(), &CARRIER);
+
+		assert_ok!(track(&shipment(1), ShipmentOperation::Pickup));
+		assert_ok!(track(&shipment(1), ShipmentOperation::Deliver));
+		assert_ok!(BottleTracking::check_bottle_custody(&bottle(1), &RETAILER));
+	});
+}
+
+#[test]
+#[should_panic(expected = "genesis shipment is valid")]
+fn genesis_shipment_of_unknown_bottle_panics() {
+	test_ext_with(
+		registrar::GenesisConfig::<Test> {
+			members: vec![
+				(registrar::MemberType::Manufacturer, MANUFACTURER),
+				(registrar::MemberType::Carrier, CARRIER),
+				(registrar::MemberType::Retailer, RETAILER),
+			],
+			bottles: vec![],
+		},
+		crate::GenesisConfig::<Test> {
+			shipments: vec![(shipment(1), MANUFACTURER, CARRIER, RETAILER, vec![bottle(1)])],
+		},
+	);
+}
@@ -0,0 +1,220 @@
+use frame_support::{sp_runtime::RuntimeDebug, sp_std::prelude::*, sp_std::vec::Vec};
+use codec::{Encode, Decode};
+use registrar::{BottleId, BottleStatus};
+
+pub type ShipmentId = Vec<u8>;
+pub type ClearanceReference = Vec<u8>;
+
+/// Storage layout of the pallet, bumped by every release that needs a migration.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Releases {
+    /// Shipments from a manufacturer to a retailer.
+    V1_0_0,
+    /// Shipments between any stock holders, with customs, deadlines and missing bottles.
+    V2_0_0,
+}
+
+impl Default for Releases {
+    fn default() -> Self {
+        Releases::V1_0_0
+    }
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum ShipmentStatus {
+    Pending,
+    InTransit,
+    Delivered,
+    Stored,
+    Customs,
+    Cancelled,
+    Rejected,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum ShipmentOperation {
+    Pickup,
+    Scan,
+    Deliver,
+}
+
+impl From<ShipmentOperation> for BottleStatus {
+    fn from(op: ShipmentOperation) -> Self {
+        match op {
+            ShipmentOperation::Pickup => BottleStatus::ShipmentInTransit,
+            ShipmentOperation::Scan => BottleStatus::ShipmentInTransit,
+            ShipmentOperation::Deliver => BottleStatus::ShipmentDelivered,
+        }
+    }
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Shipment<AccountId, Moment, BlockNumber> {
+    pub id: ShipmentId,
+    pub sender: AccountId,
+    pub carrier: AccountId,
+    pub receiver: AccountId,
+    pub bottles: Vec<BottleId>,
+    pub missing: Vec<BottleId>,
+    pub customs: Option<AccountId>,
+    pub status: ShipmentStatus,
+    pub registered: Moment,
+    pub delivered: Option<Moment>,
+    pub deadline: Option<BlockNumber>,
+    pub overdue: bool,
+}
+
+impl<AccountId, Moment, BlockNumber> Shipment<AccountId, Moment, BlockNumber> {
+    pub fn pickup(mut self) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.status = ShipmentStatus::InTransit;
+        self
+    }
+
+    pub fn delivered(mut self, when: Moment) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.status = ShipmentStatus::Delivered;
+        self.delivered = Some(when);
+        self
+    }
+
+    pub fn with_missing(mut self, missing: Vec<BottleId>) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.missing = missing;
+        self
+    }
+
+    pub fn held_by_customs(mut self) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.status = ShipmentStatus::Customs;
+        self
+    }
+
+    pub fn released_by_customs(mut self) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.status = ShipmentStatus::InTransit;
+        self
+    }
+
+    pub fn stored(mut self, when: Moment) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.status = ShipmentStatus::Stored;
+        self.delivered = Some(when);
+        self
+    }
+
+    pub fn mark_overdue(mut self) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.overdue = true;
+        self
+    }
+
+    pub fn cancelled(mut self) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.status = ShipmentStatus::Cancelled;
+        self
+    }
+
+    pub fn rejected(mut self) -> Shipment<AccountId, Moment, BlockNumber> {
+        self.status = ShipmentStatus::Rejected;
+        self
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.status == ShipmentStatus::Delivered || self.status == ShipmentStatus::Stored
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.status == ShipmentStatus::Cancelled || self.status == ShipmentStatus::Rejected
+    }
+
+    /// The account holding the bottles once the shipment is closed, `None` while
+    /// the shipment is still pending or on its way.
+    pub fn holder(&self) -> Option<&AccountId> {
+        match self.status {
+            ShipmentStatus::Delivered | ShipmentStatus::Stored => Some(&self.receiver),
+            ShipmentStatus::Cancelled | ShipmentStatus::Rejected => Some(&self.sender),
+            _ => None,
+        }
+    }
+}
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Clearance<AccountId, Hash, Moment> {
+    pub reference: ClearanceReference,
+    pub documents: Vec<Hash>,
+    pub cleared_by: AccountId,
+    pub cleared: Moment,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ShipmentPayment<Balance> {
+    pub price: Balance,
+    pub carrier_fee: Balance,
+}
+
+/// Payment escrowed for a shipment. Once the shipment is delivered the payment is
+/// moved to the reserved balances of the payees and held there until `held_until`,
+/// so that it can still be redistributed by a dispute ruling. Once released, `held_until`
+/// is cleared and the shipment can no longer be disputed.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Escrow<Balance, BlockNumber> {
+    pub payment: ShipmentPayment<Balance>,
+    pub funded: bool,
+    pub held_until: Option<BlockNumber>,
+}
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum BottleIncident {
+    Lost,
+    Damaged,
+}
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Verdict {
+    Dismissed,
+    SenderLiable,
+    CarrierLiable,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Dispute<AccountId, Hash, BlockNumber> {
+    pub shipment: ShipmentId,
+    pub claimant: AccountId,
+    pub claims: Vec<(BottleId, BottleIncident)>,
+    pub evidence: Vec<Hash>,
+    pub responses: Vec<(AccountId, Vec<Hash>)>,
+    pub opened: BlockNumber,
+}
+
+/// A reading of the device of a carrier travelling with a shipment.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Telemetry {
+    /// Millionths of a degree, north positive.
+    pub latitude: i32,
+    /// Millionths of a degree, east positive.
+    pub longitude: i32,
+    /// Hundredths of a degree Celsius.
+    pub temperature: i16,
+    /// Milliseconds since the Unix epoch the device took the reading at.
+    pub recorded: u64,
+}
+
+impl Telemetry {
+    pub fn is_valid(&self) -> bool {
+        self.latitude.abs() <= 90_000_000 && self.longitude.abs() <= 180_000_000
+    }
+}
+
+/// A carrier device that scans shipments with unsigned transactions.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Device<AccountId, BlockNumber> {
+    /// Carrier the device scans for.
+    pub carrier: AccountId,
+    /// Block the current rate-limiting window started at.
+    pub window: BlockNumber,
+    /// Scans of the device in the current window.
+    pub scans: u32,
+}
+
+/// Scan of a shipment by a carrier device, signed by the device key.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ScanPayload<Public, Hash> {
+    pub device: Public,
+    pub shipment: ShipmentId,
+    /// Number of the scans of the device before this one.
+    pub nonce: u64,
+    /// Genesis hash of the chain the scan is for, so that it does not replay on other chains.
+    pub genesis: Hash,
+}
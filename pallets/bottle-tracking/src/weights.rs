@@ -0,0 +1,242 @@
+//! Weights for the bottle-tracking pallet.
+//!
+//! The values below are initial estimates. Regenerate them on reference hardware with:
+//!
+//! ./target/release/provenance-substrate benchmark --chain dev --execution wasm --wasm-execution compiled
+//!     --pallet bottle_tracking --extrinsic '*' --steps 50 --repeat 20 --output ./pallets/bottle-tracking/src/weights.rs
+
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight as DbWeight}};
+use frame_support::sp_std::marker::PhantomData;
+
+/// Weight functions needed for the bottle-tracking pallet.
+pub trait WeightInfo {
+	fn register_shipment(b: u32, s: u32, ) -> Weight;
+	fn track_shipment() -> Weight;
+	fn receive_shipment(b: u32, ) -> Weight;
+	fn fund_shipment() -> Weight;
+	fn cancel_shipment() -> Weight;
+	fn reject_shipment() -> Weight;
+	fn open_dispute(c: u32, ) -> Weight;
+	fn respond_to_dispute() -> Weight;
+	fn rule_dispute(c: u32, ) -> Weight;
+	fn inspect_shipment() -> Weight;
+	fn hold_shipment() -> Weight;
+	fn clear_shipment(d: u32, ) -> Weight;
+	fn sell_to_customer(b: u32, ) -> Weight;
+	fn report_telemetry(r: u32, ) -> Weight;
+	fn register_device() -> Weight;
+	fn remove_device() -> Weight;
+	fn submit_scan_unsigned() -> Weight;
+}
+
+/// Weights for the bottle-tracking pallet using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: registrar::SystemConfig> WeightInfo for SubstrateWeight<T> {
+	fn register_shipment(b: u32, s: u32, ) -> Weight {
+		(95_000_000 as Weight)
+			.saturating_add((9_500_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add((2_000 as Weight).saturating_mul(s as Weight))
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().reads((4 as Weight).saturating_mul(b as Weight)))
+			.saturating_add(T::DbWeight::get().writes(7 as Weight))
+			.saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(b as Weight)))
+	}
+	fn track_shipment() -> Weight {
+		(60_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(5 as Weight))
+			.saturating_add(T::DbWeight::get().writes(4 as Weight))
+	}
+	fn receive_shipment(b: u32, ) -> Weight {
+		(85_000_000 as Weight)
+			.saturating_add((6_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add(T::DbWeight::get().reads(6 as Weight))
+			.saturating_add(T::DbWeight::get().writes(4 as Weight))
+			.saturating_add(T::DbWeight::get().writes((2 as Weight).saturating_mul(b as Weight)))
+	}
+	fn fund_shipment() -> Weight {
+		(55_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+	fn cancel_shipment() -> Weight {
+		(58_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(3 as Weight))
+	}
+	fn reject_shipment() -> Weight {
+		(58_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(3 as Weight))
+	}
+	fn open_dispute(c: u32, ) -> Weight {
+		(52_000_000 as Weight)
+			.saturating_add((1_200_000 as Weight).saturating_mul(c as Weight))
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn respond_to_dispute() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn rule_dispute(c: u32, ) -> Weight {
+		(90_000_000 as Weight)
+			.saturating_add((2_500_000 as Weight).saturating_mul(c as Weight))
+			.saturating_add(T::DbWeight::get().reads(5 as Weight))
+			.saturating_add(T::DbWeight::get().writes(4 as Weight))
+			.saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(c as Weight)))
+	}
+	fn inspect_shipment() -> Weight {
+		(30_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(0 as Weight))
+	}
+	fn hold_shipment() -> Weight {
+		(36_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn clear_shipment(d: u32, ) -> Weight {
+		(42_000_000 as Weight)
+			.saturating_add((250_000 as Weight).saturating_mul(d as Weight))
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+	fn sell_to_customer(b: u32, ) -> Weight {
+		(48_000_000 as Weight)
+			.saturating_add((14_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().reads((5 as Weight).saturating_mul(b as Weight)))
+			.saturating_add(T::DbWeight::get().writes(0 as Weight))
+			.saturating_add(T::DbWeight::get().writes((2 as Weight).saturating_mul(b as Weight)))
+	}
+	fn report_telemetry(r: u32, ) -> Weight {
+		(30_000_000 as Weight)
+			.saturating_add((12_000_000 as Weight).saturating_mul(r as Weight))
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().reads((2 as Weight).saturating_mul(r as Weight)))
+			.saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(r as Weight)))
+	}
+	fn register_device() -> Weight {
+		(30_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn remove_device() -> Weight {
+		(25_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn submit_scan_unsigned() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	fn register_shipment(b: u32, s: u32, ) -> Weight {
+		(95_000_000 as Weight)
+			.saturating_add((9_500_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add((2_000 as Weight).saturating_mul(s as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().reads((4 as Weight).saturating_mul(b as Weight)))
+			.saturating_add(DbWeight::get().writes(7 as Weight))
+			.saturating_add(DbWeight::get().writes((1 as Weight).saturating_mul(b as Weight)))
+	}
+	fn track_shipment() -> Weight {
+		(60_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(5 as Weight))
+			.saturating_add(DbWeight::get().writes(4 as Weight))
+	}
+	fn receive_shipment(b: u32, ) -> Weight {
+		(85_000_000 as Weight)
+			.saturating_add((6_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add(DbWeight::get().reads(6 as Weight))
+			.saturating_add(DbWeight::get().writes(4 as Weight))
+			.saturating_add(DbWeight::get().writes((2 as Weight).saturating_mul(b as Weight)))
+	}
+	fn fund_shipment() -> Weight {
+		(55_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+	fn cancel_shipment() -> Weight {
+		(58_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(3 as Weight))
+	}
+	fn reject_shipment() -> Weight {
+		(58_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(3 as Weight))
+	}
+	fn open_dispute(c: u32, ) -> Weight {
+		(52_000_000 as Weight)
+			.saturating_add((1_200_000 as Weight).saturating_mul(c as Weight))
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn respond_to_dispute() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn rule_dispute(c: u32, ) -> Weight {
+		(90_000_000 as Weight)
+			.saturating_add((2_500_000 as Weight).saturating_mul(c as Weight))
+			.saturating_add(DbWeight::get().reads(5 as Weight))
+			.saturating_add(DbWeight::get().writes(4 as Weight))
+			.saturating_add(DbWeight::get().writes((1 as Weight).saturating_mul(c as Weight)))
+	}
+	fn inspect_shipment() -> Weight {
+		(30_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(0 as Weight))
+	}
+	fn hold_shipment() -> Weight {
+		(36_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn clear_shipment(d: u32, ) -> Weight {
+		(42_000_000 as Weight)
+			.saturating_add((250_000 as Weight).saturating_mul(d as Weight))
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+	fn sell_to_customer(b: u32, ) -> Weight {
+		(48_000_000 as Weight)
+			.saturating_add((14_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().reads((5 as Weight).saturating_mul(b as Weight)))
+			.saturating_add(DbWeight::get().writes(0 as Weight))
+			.saturating_add(DbWeight::get().writes((2 as Weight).saturating_mul(b as Weight)))
+	}
+	fn report_telemetry(r: u32, ) -> Weight {
+		(30_000_000 as Weight)
+			.saturating_add((12_000_000 as Weight).saturating_mul(r as Weight))
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().reads((2 as Weight).saturating_mul(r as Weight)))
+			.saturating_add(DbWeight::get().writes((1 as Weight).saturating_mul(r as Weight)))
+	}
+	fn register_device() -> Weight {
+		(30_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn remove_device() -> Weight {
+		(25_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn submit_scan_unsigned() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+}
@@ -0,0 +1,257 @@
+//! Fee waiver for the members of the supply chain.
+//!
+//! Registered members make their `registrar` and `bottle_tracking` calls without fees, up to
+//! `FeelessQuota` transactions every `FeelessPeriod` blocks. Every account may also register
+//! as a member once without fees, so that customers need no funds to join. Other calls, calls
+//! of accounts that are not members, calls beyond the quota and calls with a tip pay fees as
+//! usual.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Decode, Encode};
+use frame_support::{decl_module, decl_storage, traits::{Currency, Get}, weights::DispatchInfo};
+use pallet_transaction_payment::ChargeTransactionPayment;
+use registrar::SystemConfig;
+use sp_runtime::{
+	traits::{DispatchInfoOf, Dispatchable, PostDispatchInfoOf, SignedExtension, Zero},
+	transaction_validity::{TransactionValidity, TransactionValidityError, ValidTransaction},
+	DispatchResult,
+};
+
+// The `rococo-v1` branch of FRAME renamed the configuration traits, and moved `IsSubType`.
+#[cfg(not(feature = "parachain"))]
+use frame_support::dispatch::IsSubType;
+#[cfg(feature = "parachain")]
+use frame_support::traits::IsSubType;
+#[cfg(not(feature = "parachain"))]
+pub use pallet_transaction_payment::Trait as TransactionPaymentConfig;
+#[cfg(feature = "parachain")]
+pub use pallet_transaction_payment::Config as TransactionPaymentConfig;
+
+// The mock runtime is written against the standalone release of FRAME.
+#[cfg(all(test, not(feature = "parachain")))]
+mod mock;
+
+#[cfg(all(test, not(feature = "parachain")))]
+mod tests;
+
+type BalanceOf<T> =
+	<<T as bottle_tracking::Config>::Currency as Currency<<T as SystemConfig>::AccountId>>::Balance;
+
+/// Configure the pallet by specifying the parameters and types on which it depends.
+pub trait Config: bottle_tracking::Config + TransactionPaymentConfig {
+	/// Transactions a member can make without fees in each fee period.
+	type FeelessQuota: Get<u32>;
+
+	/// Length of the fee periods, in blocks. Quotas renew at the start of every period, or
+	/// never if it is zero.
+	type FeelessPeriod: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as MemberFees {
+		/// Fee-less transactions made by a member in a fee period, by the start of the period.
+		pub FeelessTransactions: map hasher(blake2_128_concat) T::AccountId => (T::BlockNumber, u32);
+		/// Accounts that registered as a member without fees.
+		pub FeelessRegistrations: map hasher(blake2_128_concat) T::AccountId => ();
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {}
+}
+
+impl<T: Config> Module<T> {
+	/// Start of the fee period the block belongs to.
+	fn fee_period_of(block: T::BlockNumber) -> T::BlockNumber {
+		let period = T::FeelessPeriod::get();
+		if period.is_zero() {
+			Zero::zero()
+		} else {
+			block - block % period
+		}
+	}
+
+	/// Number of transactions the account can still make without fees in the current fee
+	/// period. Only members make any.
+	pub fn feeless_transactions_left(account_id: &T::AccountId) -> u32 {
+		if registrar::Module::<T>::member_type_of(account_id).is_none() {
+			return 0;
+		}
+
+		let current = Self::fee_period_of(<frame_system::Module<T>>::block_number());
+		let (period, made) = FeelessTransactions::<T>::get(account_id);
+		let made = if period == current { made } else { 0 };
+
+		T::FeelessQuota::get().saturating_sub(made)
+	}
+
+	/// Counts a fee-less transaction of the account against its quota.
+	fn take_feeless_transaction(account_id: &T::AccountId) {
+		let current = Self::fee_period_of(<frame_system::Module<T>>::block_number());
+		FeelessTransactions::<T>::mutate(account_id, |(period, made)| {
+			if *period != current {
+				*period = current;
+				*made = 0;
+			}
+			*made += 1;
+		});
+	}
+
+	/// Whether the account can still register as a member without fees. Registrations that
+	/// would fail are not waived, so that they cannot fill blocks for free.
+	pub fn may_register_without_fees(account_id: &T::AccountId) -> bool {
+		!FeelessRegistrations::<T>::contains_key(account_id)
+			&& registrar::Module::<T>::validate_new_member(account_id).is_ok()
+	}
+}
+
+/// Why the fees of a transaction are waived.
+enum Waiver {
+	/// A supply-chain call of a member, within its quota.
+	Quota,
+	/// The first registration of the account as a member.
+	Registration,
+}
+
+fn is_registration<T: registrar::Config>(call: &registrar::Call<T>) -> bool {
+	matches!(
+		call,
+		registrar::Call::register_manufacturer()
+			| registrar::Call::register_carrier()
+			| registrar::Call::register_retailer()
+			| registrar::Call::register_customer()
+			| registrar::Call::register_distributor()
+			| registrar::Call::register_warehouse()
+			| registrar::Call::register_customs()
+	)
+}
+
+/// Charges transaction fees like `ChargeTransactionPayment`, except for the supply-chain
+/// calls of members within their quota and the first registration of an account. It is
+/// encoded as the tip, like the extension it replaces, so clients sign transactions as before.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct WaiveMemberFees<T: Config + Send + Sync>(#[codec(compact)] BalanceOf<T>);
+
+impl<T: Config + Send + Sync> From<BalanceOf<T>> for WaiveMemberFees<T> {
+	fn from(tip: BalanceOf<T>) -> Self {
+		WaiveMemberFees(tip)
+	}
+}
+
+impl<T: Config + Send + Sync> sp_std::fmt::Debug for WaiveMemberFees<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "WaiveMemberFees<{:?}>", self.0)
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T> WaiveMemberFees<T> where
+	T: Config + Send + Sync,
+	<T as SystemConfig>::Call: IsSubType<registrar::Call<T>> + IsSubType<bottle_tracking::Call<T>>,
+	ChargeTransactionPayment<T>: From<BalanceOf<T>>,
+{
+	/// Why the fees of the call may be waived, if they may. Tipping opts out, as fee-less
+	/// transactions have no priority to buy.
+	fn waiver(&self, who: &T::AccountId, call: &<T as SystemConfig>::Call) -> Option<Waiver> {
+		if !self.0.is_zero() {
+			return None;
+		}
+
+		let registrar_call = IsSubType::<registrar::Call<T>>::is_sub_type(call);
+		if registrar_call.map_or(false, is_registration) && Module::<T>::may_register_without_fees(who) {
+			return Some(Waiver::Registration);
+		}
+
+		let supply_chain = registrar_call.is_some()
+			|| IsSubType::<bottle_tracking::Call<T>>::is_sub_type(call).is_some();
+		if supply_chain && Module::<T>::feeless_transactions_left(who) > 0 {
+			return Some(Waiver::Quota);
+		}
+
+		None
+	}
+
+	fn charge(&self) -> ChargeTransactionPayment<T> {
+		ChargeTransactionPayment::from(self.0)
+	}
+}
+
+impl<T> SignedExtension for WaiveMemberFees<T> where
+	T: Config + Send + Sync,
+	<T as SystemConfig>::Call: Dispatchable<Info = DispatchInfo>
+		+ IsSubType<registrar::Call<T>> + IsSubType<bottle_tracking::Call<T>>,
+	BalanceOf<T>: Send + Sync,
+	ChargeTransactionPayment<T>: SignedExtension<AccountId = T::AccountId, Call = <T as SystemConfig>::Call>
+		+ From<BalanceOf<T>>,
+{
+	// Clients pick the encoding of extensions by name.
+	const IDENTIFIER: &'static str = "ChargeTransactionPayment";
+	type AccountId = T::AccountId;
+	type Call = <T as SystemConfig>::Call;
+	type AdditionalSigned = ();
+	/// `None` when the fees are waived.
+	type Pre = Option<<ChargeTransactionPayment<T> as SignedExtension>::Pre>;
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	// Waived transactions all provide the same tag of the account, so the pool keeps one of
+	// them at a time: an account cannot flood it with transactions beyond its quota. Members
+	// queue more transactions by tipping.
+	fn validate(
+		&self,
+		who: &T::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> TransactionValidity {
+		if self.waiver(who, call).is_some() {
+			return ValidTransaction::with_tag_prefix("MemberFees").and_provides(who).build();
+		}
+
+		self.charge().validate(who, call, info, len)
+	}
+
+	fn pre_dispatch(
+		self,
+		who: &T::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> Result<Self::Pre, TransactionValidityError> {
+		match self.waiver(who, call) {
+			Some(Waiver::Quota) => Module::<T>::take_feeless_transaction(who),
+			Some(Waiver::Registration) => FeelessRegistrations::<T>::insert(who, ()),
+			None => return self.charge().pre_dispatch(who, call, info, len).map(Some),
+		}
+
+		Ok(None)
+	}
+
+	fn post_dispatch(
+		pre: Self::Pre,
+		info: &DispatchInfoOf<Self::Call>,
+		post_info: &PostDispatchInfoOf<Self::Call>,
+		len: usize,
+		result: &DispatchResult,
+	) -> Result<(), TransactionValidityError> {
+		match pre {
+			Some(pre) => ChargeTransactionPayment::<T>::post_dispatch(pre, info, post_info, len, result),
+			None => {
+				// The quota or the registration recorded in `pre_dispatch`.
+				<frame_system::Module<T>>::register_extra_weight_unchecked(
+					T::DbWeight::get().reads_writes(1, 1),
+					info.class,
+				);
+				Ok(())
+			},
+		}
+	}
+}
@@ -0,0 +1,192 @@
+use crate as member_fees;
+use crate::Config;
+use sp_core::H256;
+use frame_support::{parameter_types, weights::{IdentityFee, Weight}};
+use sp_runtime::{
+	traits::{BlakeTwo256, IdentityLookup}, testing::{Header, TestSignature, TestXt, UintAuthorityId},
+	transaction_validity::TransactionPriority, Perbill,
+};
+use frame_system::{self as system, EnsureRoot};
+use registrar::MemberType;
+
+pub type Block = sp_runtime::generic::Block<Header, UncheckedExtrinsic>;
+pub type UncheckedExtrinsic = sp_runtime::generic::UncheckedExtrinsic<u64, Call, (), ()>;
+
+// Configure a mock runtime to test the pallet.
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: system::{Module, Call, Config, Storage, Event<T>},
+		Balances: balances::{Module, Call, Storage, Config<T>, Event<T>},
+		Timestamp: timestamp::{Module, Call, Storage, Inherent},
+		TransactionPayment: pallet_transaction_payment::{Module, Storage},
+		RegistrarModule: registrar::{Module, Call, Config<T>, Storage, Event<T>},
+		BottleTracking: bottle_tracking::{Module, Call, Config<T>, Storage, Event<T>, ValidateUnsigned},
+		MemberFees: member_fees::{Module, Storage},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MaximumBlockWeight: Weight = 1024;
+	pub const MaximumBlockLength: u32 = 2 * 1024;
+	pub const AvailableBlockRatio: Perbill = Perbill::one();
+}
+
+impl system::Trait for Test {
+	type BaseCallFilter = ();
+	type Origin = Origin;
+	type Call = Call;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = BlockHashCount;
+	type MaximumBlockWeight = MaximumBlockWeight;
+	type DbWeight = ();
+	type BlockExecutionWeight = ();
+	type ExtrinsicBaseWeight = ();
+	type MaximumExtrinsicWeight = ();
+	type MaximumBlockLength = MaximumBlockLength;
+	type AvailableBlockRatio = AvailableBlockRatio;
+	type Version = ();
+	type PalletInfo = ();
+	type AccountData = balances::AccountData<u64>;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+}
+
+parameter_types! {
+	pub const ExistentialDeposit: u64 = 1;
+	pub const MaxLocks: u32 = 50;
+}
+
+impl balances::Trait for Test {
+	type Balance = u64;
+	type DustRemoval = ();
+	type Event = Event;
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+	type MaxLocks = MaxLocks;
+}
+
+parameter_types! {
+	pub const MinimumPeriod: u64 = 1;
+}
+
+impl timestamp::Trait for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = MinimumPeriod;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const TransactionByteFee: u64 = 1;
+}
+
+impl pallet_transaction_payment::Trait for Test {
+	type Currency = Balances;
+	type OnTransactionPayment = ();
+	type TransactionByteFee = TransactionByteFee;
+	type WeightToFee = IdentityFee<u64>;
+	type FeeMultiplierUpdate = ();
+}
+
+impl registrar::Config for Test {
+	type Event = Event;
+	type WeightInfo = ();
+}
+
+impl system::offchain::SigningTypes for Test {
+	type Public = UintAuthorityId;
+	type Signature = TestSignature;
+}
+
+impl<C> system::offchain::SendTransactionTypes<C> for Test where Call: From<C> {
+	type OverarchingCall = Call;
+	type Extrinsic = TestXt<Call, ()>;
+}
+
+impl<C> system::offchain::CreateSignedTransaction<C> for Test where Call: From<C> {
+	fn create_transaction<S: system::offchain::AppCrypto<Self::Public, Self::Signature>>(
+		call: Call,
+		_public: UintAuthorityId,
+		_account: u64,
+		nonce: u64,
+	) -> Option<(Call, <TestXt<Call, ()> as sp_runtime::traits::Extrinsic>::SignaturePayload)> {
+		Some((call, (nonce, ())))
+	}
+}
+
+pub struct TestDeviceId;
+
+impl system::offchain::AppCrypto<UintAuthorityId, TestSignature> for TestDeviceId {
+	type RuntimeAppPublic = UintAuthorityId;
+	type GenericSignature = TestSignature;
+	type GenericPublic = UintAuthorityId;
+}
+
+parameter_types! {
+	pub const DisputePeriod: u64 = 10;
+	pub const FeedPollInterval: u64 = 5;
+	pub const MaxDeviceScans: u32 = 3;
+	pub const DeviceScanWindow: u64 = 10;
+	pub const UnsignedScanPriority: TransactionPriority = 1 << 20;
+}
+
+impl bottle_tracking::Config for Test {
+	type Event = Event;
+	type Currency = Balances;
+	type ArbitratorOrigin = EnsureRoot<u64>;
+	type DisputePeriod = DisputePeriod;
+	type DeviceId = TestDeviceId;
+	type FeedPollInterval = FeedPollInterval;
+	type MaxDeviceScans = MaxDeviceScans;
+	type DeviceScanWindow = DeviceScanWindow;
+	type UnsignedScanPriority = UnsignedScanPriority;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const FeelessQuota: u32 = 2;
+	pub const FeelessPeriod: u64 = 10;
+}
+
+impl Config for Test {
+	type FeelessQuota = FeelessQuota;
+	type FeelessPeriod = FeelessPeriod;
+}
+
+pub const CARRIER: u64 = 2;
+pub const CUSTOMER: u64 = 4;
+pub const STRANGER: u64 = 99;
+/// An account without funds, which is not a member yet.
+pub const NEWCOMER: u64 = 100;
+
+pub const INITIAL_BALANCE: u64 = 1_000;
+
+// Build genesis storage according to the mock runtime.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	balances::GenesisConfig::<Test> {
+		balances: [CARRIER, CUSTOMER, STRANGER].iter().map(|account| (*account, INITIAL_BALANCE)).collect(),
+	}.assimilate_storage(&mut t).unwrap();
+	registrar::GenesisConfig::<Test> {
+		members: vec![(MemberType::Carrier, CARRIER), (MemberType::Customer, CUSTOMER)],
+		bottles: vec![],
+	}.assimilate_storage(&mut t).unwrap();
+
+	let mut ext = sp_io::TestExternalities::new(t);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
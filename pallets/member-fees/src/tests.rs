@@ -0,0 +1,156 @@
+use crate::{mock::*, FeelessRegistrations, WaiveMemberFees};
+use frame_support::{assert_ok, weights::{DispatchInfo, PostDispatchInfo}};
+use sp_runtime::{
+	traits::SignedExtension,
+	transaction_validity::{InvalidTransaction, TransactionValidityError},
+};
+
+/// Weight of the calls of the tests, which is also their fee, along with the length.
+const WEIGHT: u64 = 5;
+const LEN: usize = 10;
+const FEE: u64 = WEIGHT + LEN as u64;
+
+fn info() -> DispatchInfo {
+	DispatchInfo { weight: WEIGHT, ..Default::default() }
+}
+
+fn supply_chain_call() -> Call {
+	Call::BottleTracking(bottle_tracking::Call::register_device(42))
+}
+
+fn registration() -> Call {
+	Call::RegistrarModule(registrar::Call::register_customer())
+}
+
+fn other_call() -> Call {
+	Call::System(frame_system::Call::remark(vec![]))
+}
+
+/// Makes the transaction as far as fees go, with the given tip.
+fn transact(who: u64, call: &Call, tip: u64) -> Result<(), TransactionValidityError> {
+	let pre = WaiveMemberFees::<Test>::from(tip).pre_dispatch(&who, call, &info(), LEN)?;
+	WaiveMemberFees::<Test>::post_dispatch(pre, &info(), &PostDispatchInfo::default(), LEN, &Ok(()))
+}
+
+fn free(who: u64) -> u64 {
+	Balances::free_balance(who)
+}
+
+#[test]
+fn members_make_supply_chain_calls_without_fees() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(transact(CARRIER, &supply_chain_call(), 0));
+		assert_ok!(transact(CUSTOMER, &Call::RegistrarModule(registrar::Call::register_bottle(vec![], vec![])), 0));
+
+		assert_eq!(free(CARRIER), INITIAL_BALANCE);
+		assert_eq!(free(CUSTOMER), INITIAL_BALANCE);
+		assert_eq!(MemberFees::feeless_transactions_left(&CARRIER), 1);
+	});
+}
+
+#[test]
+fn members_pay_beyond_their_quota() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(transact(CARRIER, &supply_chain_call(), 0));
+		System::set_block_number(9);
+		assert_ok!(transact(CARRIER, &supply_chain_call(), 0));
+		assert_eq!(free(CARRIER), INITIAL_BALANCE);
+
+		assert_ok!(transact(CARRIER, &supply_chain_call(), 0));
+
+		assert_eq!(free(CARRIER), INITIAL_BALANCE - FEE);
+		assert_eq!(MemberFees::feeless_transactions_left(&CUSTOMER), 2);
+	});
+}
+
+#[test]
+fn quota_renews_every_period() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(transact(CARRIER, &supply_chain_call(), 0));
+		assert_ok!(transact(CARRIER, &supply_chain_call(), 0));
+
+		System::set_block_number(10);
+
+		assert_eq!(MemberFees::feeless_transactions_left(&CARRIER), 2);
+		assert_ok!(transact(CARRIER, &supply_chain_call(), 0));
+		assert_eq!(MemberFees::feeless_transactions_left(&CARRIER), 1);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE);
+	});
+}
+
+#[test]
+fn tips_opt_out_of_the_waiver() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(transact(CARRIER, &supply_chain_call(), 3));
+
+		assert_eq!(free(CARRIER), INITIAL_BALANCE - FEE - 3);
+		assert_eq!(MemberFees::feeless_transactions_left(&CARRIER), 2);
+	});
+}
+
+#[test]
+fn non_members_and_other_calls_pay() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(transact(STRANGER, &supply_chain_call(), 0));
+		assert_ok!(transact(CARRIER, &other_call(), 0));
+
+		assert_eq!(free(STRANGER), INITIAL_BALANCE - FEE);
+		assert_eq!(free(CARRIER), INITIAL_BALANCE - FEE);
+		assert_eq!(MemberFees::feeless_transactions_left(&STRANGER), 0);
+		assert_eq!(MemberFees::feeless_transactions_left(&CARRIER), 2);
+	});
+}
+
+#[test]
+fn accounts_register_once_without_fees() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(transact(NEWCOMER, &registration(), 0));
+		assert!(FeelessRegistrations::<Test>::contains_key(&NEWCOMER));
+
+		// Another registration, say after the first failed, is paid for.
+		assert_eq!(
+			transact(NEWCOMER, &Call::RegistrarModule(registrar::Call::register_carrier()), 0),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Payment)),
+		);
+
+		// Once a member, the account makes its supply-chain calls within its quota.
+		assert_ok!(RegistrarModule::register_customer(Origin::signed(NEWCOMER)));
+		assert_ok!(transact(NEWCOMER, &supply_chain_call(), 0));
+		assert_eq!(MemberFees::feeless_transactions_left(&NEWCOMER), 1);
+	});
+}
+
+#[test]
+fn members_register_again_within_their_quota() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(transact(CARRIER, &registration(), 0));
+
+		assert!(!FeelessRegistrations::<Test>::contains_key(&CARRIER));
+		assert_eq!(MemberFees::feeless_transactions_left(&CARRIER), 1);
+	});
+}
+
+#[test]
+fn waived_transactions_are_pooled_without_payment_one_per_account() {
+	new_test_ext().execute_with(|| {
+		let extension = WaiveMemberFees::<Test>::from(0);
+
+		let registering = extension.validate(&NEWCOMER, &registration(), &info(), LEN).unwrap();
+		assert_eq!(registering.provides.len(), 1);
+		assert_eq!(
+			extension.validate(&NEWCOMER, &Call::RegistrarModule(registrar::Call::register_carrier()), &info(), LEN),
+			Ok(registering.clone()),
+		);
+		assert_eq!(
+			extension.validate(&NEWCOMER, &other_call(), &info(), LEN),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Payment)),
+		);
+
+		let shipping = extension.validate(&CARRIER, &supply_chain_call(), &info(), LEN).unwrap();
+		assert_ne!(shipping.provides, registering.provides);
+
+		// Transactions that pay are pooled as usual.
+		let tipped = WaiveMemberFees::<Test>::from(3).validate(&CARRIER, &supply_chain_call(), &info(), LEN).unwrap();
+		assert!(tipped.provides.is_empty());
+	});
+}
@@ -0,0 +1,211 @@
+//! Verification of bottle ownership from storage proofs, for clients that do not trust the
+//! node serving them.
+//!
+//! A client first establishes a header it trusts: a header of the standalone chain finalized
+//! by a GRANDPA justification with `verify_finalized_header`, or the head of the parachain
+//! included in a trusted relay-chain header with `verify_para_head`. It then checks the proof
+//! of the bottle against the state root of that header with `verify_bottle`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Decode, Encode};
+use sp_core::{
+	crypto::Pair as _,
+	ed25519,
+	hashing::{blake2_128, twox_128, twox_64},
+	H256,
+};
+use sp_runtime::traits::BlakeTwo256;
+use sp_std::{collections::{btree_map::BTreeMap, btree_set::BTreeSet}, prelude::*};
+use sp_trie::{read_trie_value, Layout, StorageProof};
+use registrar::Bottle;
+
+#[cfg(test)]
+mod tests;
+
+/// Header of the standalone chain and of the parachain.
+pub type Header = sp_runtime::generic::Header<u32, BlakeTwo256>;
+
+/// GRANDPA authorities with their voting weight.
+pub type AuthorityList = Vec<(ed25519::Public, u64)>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The proof lacks the nodes to read a key, or does not match the state root.
+	InvalidProof,
+	/// The proof shows the bottle is not registered.
+	BottleNotFound,
+	/// The proof shows the relay chain has no head for the parachain.
+	ParaHeadNotFound,
+	/// A proven value does not decode as expected.
+	UndecodableValue,
+	/// The justification does not decode, or a vote is not on the chain of the header.
+	InvalidJustification,
+	/// The justification finalizes another block.
+	JustificationTargetMismatch,
+	/// A vote is signed by an account outside the authority set.
+	UnknownAuthority,
+	/// A vote signature does not check.
+	InvalidSignature,
+	/// The votes do not reach the supermajority of the authority set.
+	NotEnoughVotes,
+}
+
+/// Bottle as proven by the state of a block.
+#[derive(Debug, PartialEq)]
+pub struct VerifiedBottle<AccountId, Moment> {
+	pub bottle: Bottle<AccountId, Moment>,
+	/// Customer the bottle was sold to, if it was.
+	pub sold_to: Option<AccountId>,
+}
+
+fn storage_prefix(pallet: &[u8], item: &[u8]) -> Vec<u8> {
+	[twox_128(pallet), twox_128(item)].concat()
+}
+
+/// Storage key of the bottle in `registrar::Bottles`.
+pub fn bottle_key(bottle: &[u8]) -> Vec<u8> {
+	let key = bottle.encode();
+	[storage_prefix(b"RegistrarModule", b"Bottles"), blake2_128(&key).to_vec(), key].concat()
+}
+
+/// Storage key of the customer of the bottle in `bottle_tracking::BottleSoldTo`.
+pub fn sold_to_key(bottle: &[u8]) -> Vec<u8> {
+	let key = bottle.encode();
+	[storage_prefix(b"BottleTracking", b"BottleSoldTo"), blake2_128(&key).to_vec(), key].concat()
+}
+
+/// Keys a proof of the bottle must cover.
+pub fn bottle_keys(bottle: &[u8]) -> Vec<Vec<u8>> {
+	vec![bottle_key(bottle), sold_to_key(bottle)]
+}
+
+/// Storage key of the head of the parachain in the `Paras` pallet of the relay chain.
+pub fn para_head_key(para_id: u32) -> Vec<u8> {
+	let key = para_id.encode();
+	[storage_prefix(b"Paras", b"Heads"), twox_64(&key).to_vec(), key].concat()
+}
+
+/// Reads a key from the proof, telling a proven absence from a proof missing the key.
+fn read<T: Decode>(db: &sp_trie::MemoryDB<BlakeTwo256>, root: &H256, key: &[u8]) -> Result<Option<T>, Error> {
+	match read_trie_value::<Layout<BlakeTwo256>, _>(db, root, key).map_err(|_| Error::InvalidProof)? {
+		Some(data) => T::decode(&mut &data[..]).map(Some).map_err(|_| Error::UndecodableValue),
+		None => Ok(None),
+	}
+}
+
+/// Checks the proof of the bottle against the state root of a trusted header.
+pub fn verify_bottle<AccountId: Decode, Moment: Decode>(
+	state_root: &H256,
+	bottle: &[u8],
+	proof: Vec<Vec<u8>>,
+) -> Result<VerifiedBottle<AccountId, Moment>, Error> {
+	let db = StorageProof::new(proof).into_memory_db::<BlakeTwo256>();
+
+	let found: Bottle<AccountId, Moment> = read(&db, state_root, &bottle_key(bottle))?
+		.ok_or(Error::BottleNotFound)?;
+	let sold_to = read(&db, state_root, &sold_to_key(bottle))?;
+
+	Ok(VerifiedBottle { bottle: found, sold_to })
+}
+
+/// Checks the proof of the head of the parachain against the state root of a trusted
+/// relay-chain header and returns the parachain header.
+pub fn verify_para_head(relay_state_root: &H256, para_id: u32, proof: Vec<Vec<u8>>) -> Result<Header, Error> {
+	let db = StorageProof::new(proof).into_memory_db::<BlakeTwo256>();
+
+	let head: Vec<u8> = read(&db, relay_state_root, &para_head_key(para_id))?.ok_or(Error::ParaHeadNotFound)?;
+	Header::decode(&mut &head[..]).map_err(|_| Error::UndecodableValue)
+}
+
+/// A GRANDPA precommit, encoded as the `finality-grandpa` crate does.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct Precommit {
+	pub target_hash: H256,
+	pub target_number: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct SignedPrecommit {
+	pub precommit: Precommit,
+	pub signature: ed25519::Signature,
+	pub id: ed25519::Public,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct Commit {
+	pub target_hash: H256,
+	pub target_number: u32,
+	pub precommits: Vec<SignedPrecommit>,
+}
+
+/// A GRANDPA justification, encoded as the nodes store and serve it.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct Justification {
+	pub round: u64,
+	pub commit: Commit,
+	pub votes_ancestries: Vec<Header>,
+}
+
+/// What an authority signs when precommitting in a round of a set.
+pub fn precommit_payload(precommit: &Precommit, round: u64, set_id: u64) -> Vec<u8> {
+	// `finality_grandpa::Message::Precommit` is the second variant of the message enum.
+	(1u8, precommit, round, set_id).encode()
+}
+
+/// Checks that the justification finalizes the header with the votes of a supermajority of
+/// the authority set, and returns the hash of the header.
+pub fn verify_finalized_header(
+	header: &Header,
+	justification: &[u8],
+	set_id: u64,
+	authorities: &[(ed25519::Public, u64)],
+) -> Result<H256, Error> {
+	let hash = header.hash();
+	let justification = Justification::decode(&mut &justification[..]).map_err(|_| Error::InvalidJustification)?;
+	let commit = &justification.commit;
+	if commit.target_hash != hash || commit.target_number != header.number {
+		return Err(Error::JustificationTargetMismatch);
+	}
+
+	let parents: BTreeMap<H256, H256> = justification.votes_ancestries.iter()
+		.map(|ancestor| (ancestor.hash(), ancestor.parent_hash))
+		.collect();
+	let descends_from_header = |mut block: H256| {
+		while block != hash {
+			match parents.get(&block) {
+				Some(parent) => block = *parent,
+				None => return false,
+			}
+		}
+		true
+	};
+
+	let mut voters = BTreeSet::new();
+	let mut weight: u64 = 0;
+	for signed in &commit.precommits {
+		let voter_weight = authorities.iter()
+			.find(|(id, _)| id == &signed.id)
+			.map(|(_, weight)| *weight)
+			.ok_or(Error::UnknownAuthority)?;
+		let payload = precommit_payload(&signed.precommit, justification.round, set_id);
+		if !ed25519::Pair::verify(&signed.signature, &payload, &signed.id) {
+			return Err(Error::InvalidSignature);
+		}
+		if !descends_from_header(signed.precommit.target_hash) {
+			return Err(Error::InvalidJustification);
+		}
+		if voters.insert(signed.id.clone()) {
+			weight = weight.saturating_add(voter_weight);
+		}
+	}
+
+	// GRANDPA tolerates up to a third of faulty weight, rounded down.
+	let total = authorities.iter().fold(0u64, |total, (_, weight)| total.saturating_add(*weight));
+	let threshold = total - total.saturating_sub(1) / 3;
+	if weight < threshold {
+		return Err(Error::NotEnoughVotes);
+	}
+
+	Ok(hash)
+}
@@ -0,0 +1,150 @@
+use crate::*;
+use sp_core::{crypto::Pair, ed25519};
+use sp_runtime::{traits::Header as _, Digest};
+use sp_trie::{MemoryDB, TrieDBMut, TrieMut};
+use registrar::BottleBuilder;
+
+/// State holding the entries, and a proof made of every node of it.
+fn state(entries: &[(Vec<u8>, Vec<u8>)]) -> (H256, Vec<Vec<u8>>) {
+	let mut db = MemoryDB::<BlakeTwo256>::default();
+	let mut root = H256::default();
+	{
+		let mut trie = TrieDBMut::<Layout<BlakeTwo256>>::new(&mut db, &mut root);
+		for (key, value) in entries {
+			trie.insert(key, value).unwrap();
+		}
+	}
+	let nodes = db.drain().into_iter().map(|(_, (node, _))| node).collect();
+	(root, nodes)
+}
+
+fn bottle(id: &[u8]) -> Bottle<u64, u64> {
+	BottleBuilder::default()
+		.identified_by(id.to_vec())
+		.manufactured_by(1)
+		.registered_on(10)
+		.build()
+}
+
+#[test]
+fn verifies_bottle_and_its_customer() {
+	let (root, proof) = state(&[
+		(bottle_key(b"bottle-1"), bottle(b"bottle-1").encode()),
+		(sold_to_key(b"bottle-1"), 4u64.encode()),
+		(bottle_key(b"bottle-2"), bottle(b"bottle-2").encode()),
+	]);
+
+	assert_eq!(
+		verify_bottle::<u64, u64>(&root, b"bottle-1", proof.clone()),
+		Ok(VerifiedBottle { bottle: bottle(b"bottle-1"), sold_to: Some(4) }),
+	);
+	assert_eq!(
+		verify_bottle::<u64, u64>(&root, b"bottle-2", proof.clone()),
+		Ok(VerifiedBottle { bottle: bottle(b"bottle-2"), sold_to: None }),
+	);
+	assert_eq!(verify_bottle::<u64, u64>(&root, b"bottle-3", proof), Err(Error::BottleNotFound));
+}
+
+#[test]
+fn rejects_proof_of_another_state() {
+	let (_, proof) = state(&[(bottle_key(b"bottle-1"), bottle(b"bottle-1").encode())]);
+	let (other_root, _) = state(&[(bottle_key(b"bottle-2"), bottle(b"bottle-2").encode())]);
+
+	assert_eq!(verify_bottle::<u64, u64>(&other_root, b"bottle-1", proof), Err(Error::InvalidProof));
+}
+
+#[test]
+fn rejects_proof_missing_nodes() {
+	let (root, _) = state(&[(bottle_key(b"bottle-1"), bottle(b"bottle-1").encode())]);
+
+	assert_eq!(verify_bottle::<u64, u64>(&root, b"bottle-1", vec![]), Err(Error::InvalidProof));
+}
+
+#[test]
+fn verifies_para_head() {
+	let head = Header::new(7, H256::repeat_byte(1), H256::repeat_byte(2), H256::repeat_byte(3), Digest::default());
+	let (root, proof) = state(&[(para_head_key(100), head.encode().encode())]);
+
+	assert_eq!(verify_para_head(&root, 100, proof.clone()), Ok(head));
+	assert_eq!(verify_para_head(&root, 200, proof), Err(Error::ParaHeadNotFound));
+}
+
+fn authorities() -> Vec<ed25519::Pair> {
+	(1..=4u8).map(|seed| ed25519::Pair::from_seed(&[seed; 32])).collect()
+}
+
+fn authority_list() -> AuthorityList {
+	authorities().iter().map(|pair| (pair.public(), 1)).collect()
+}
+
+fn header() -> Header {
+	Header::new(1, H256::default(), H256::repeat_byte(9), H256::default(), Digest::default())
+}
+
+fn justification(signers: &[ed25519::Pair], round: u64, set_id: u64) -> Vec<u8> {
+	let header = header();
+	let precommit = Precommit { target_hash: header.hash(), target_number: header.number };
+	let precommits = signers.iter()
+		.map(|pair| SignedPrecommit {
+			precommit: precommit.clone(),
+			signature: pair.sign(&precommit_payload(&precommit, round, set_id)),
+			id: pair.public(),
+		})
+		.collect();
+
+	Justification {
+		round,
+		commit: Commit { target_hash: header.hash(), target_number: header.number, precommits },
+		votes_ancestries: vec![],
+	}.encode()
+}
+
+#[test]
+fn verifies_header_finalized_by_supermajority() {
+	let authorities = authorities();
+
+	assert_eq!(
+		verify_finalized_header(&header(), &justification(&authorities[..3], 1, 0), 0, &authority_list()),
+		Ok(header().hash()),
+	);
+	assert_eq!(
+		verify_finalized_header(&header(), &justification(&authorities[..2], 1, 0), 0, &authority_list()),
+		Err(Error::NotEnoughVotes),
+	);
+}
+
+#[test]
+fn duplicate_votes_count_once() {
+	let authorities = authorities();
+	let signers = [authorities[0].clone(), authorities[1].clone(), authorities[1].clone()];
+
+	assert_eq!(
+		verify_finalized_header(&header(), &justification(&signers, 1, 0), 0, &authority_list()),
+		Err(Error::NotEnoughVotes),
+	);
+}
+
+#[test]
+fn rejects_votes_of_another_set_or_authority() {
+	let authorities = authorities();
+
+	assert_eq!(
+		verify_finalized_header(&header(), &justification(&authorities[..3], 1, 1), 0, &authority_list()),
+		Err(Error::InvalidSignature),
+	);
+	assert_eq!(
+		verify_finalized_header(&header(), &justification(&authorities[..3], 1, 0), 0, &authority_list()[1..]),
+		Err(Error::UnknownAuthority),
+	);
+}
+
+#[test]
+fn rejects_justification_of_another_header() {
+	let authorities = authorities();
+	let other = Header::new(2, H256::default(), H256::repeat_byte(9), header().hash(), Digest::default());
+
+	assert_eq!(
+		verify_finalized_header(&other, &justification(&authorities[..3], 1, 0), 0, &authority_list()),
+		Err(Error::JustificationTargetMismatch),
+	);
+}
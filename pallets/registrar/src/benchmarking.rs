@@ -0,0 +1,106 @@
+//! Benchmarking setup for registrar
+
+use super::*;
+
+// The `benchmarks!` macro of the standalone release of FRAME expects the pallet
+// configuration trait to be named `Trait`.
+#[cfg(not(feature = "parachain"))]
+use crate::Config as Trait;
+
+use frame_system::RawOrigin;
+use frame_benchmarking::{benchmarks, account, whitelisted_caller};
+use sp_std::prelude::*;
+
+const SEED: u32 = 0;
+
+/// Fills the member list of the given type with `m` accounts.
+fn add_members<T: Config>(member_type: MemberType, m: u32) {
+	let members: Vec<T::AccountId> = (0..m).map(|i| account("member", i, SEED)).collect();
+	Members::<T>::insert(member_type, members);
+}
+
+/// Documents of `s` bytes in total, as few as they can be.
+fn documents(s: u32) -> Vec<Vec<u8>> {
+	vec![b'd'; s as usize].chunks(DOCUMENT_MAX_SIZE).map(|chunk| chunk.to_vec()).collect()
+}
+
+benchmarks! {
+	_ { }
+
+	register_manufacturer {
+		let m in 0 .. MAX_MEMBERS - 1;
+		add_members::<T>(MemberType::Customer, m);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller.clone()))
+	verify {
+		assert_eq!(Module::<T>::member_type_of(&caller), Some(MemberType::Manufacturer));
+	}
+
+	register_carrier {
+		let m in 0 .. MAX_MEMBERS - 1;
+		add_members::<T>(MemberType::Customer, m);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller.clone()))
+	verify {
+		assert_eq!(Module::<T>::member_type_of(&caller), Some(MemberType::Carrier));
+	}
+
+	register_retailer {
+		let m in 0 .. MAX_MEMBERS - 1;
+		add_members::<T>(MemberType::Customer, m);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller.clone()))
+	verify {
+		assert_eq!(Module::<T>::member_type_of(&caller), Some(MemberType::Retailer));
+	}
+
+	register_customer {
+		let m in 0 .. MAX_MEMBERS - 1;
+		add_members::<T>(MemberType::Customer, m);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller.clone()))
+	verify {
+		assert_eq!(Module::<T>::member_type_of(&caller), Some(MemberType::Customer));
+	}
+
+	register_distributor {
+		let m in 0 .. MAX_MEMBERS - 1;
+		add_members::<T>(MemberType::Customer, m);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller.clone()))
+	verify {
+		assert_eq!(Module::<T>::member_type_of(&caller), Some(MemberType::Distributor));
+	}
+
+	register_warehouse {
+		let m in 0 .. MAX_MEMBERS - 1;
+		add_members::<T>(MemberType::Customer, m);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller.clone()))
+	verify {
+		assert_eq!(Module::<T>::member_type_of(&caller), Some(MemberType::Warehouse));
+	}
+
+	register_customs {
+		let m in 0 .. MAX_MEMBERS - 1;
+		add_members::<T>(MemberType::Customer, m);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller.clone()))
+	verify {
+		assert_eq!(Module::<T>::member_type_of(&caller), Some(MemberType::Customs));
+	}
+
+	register_bottle {
+		let m in 1 .. MAX_MEMBERS;
+		let s in 0 .. (MAX_DOCUMENTS * DOCUMENT_MAX_SIZE) as u32;
+		add_members::<T>(MemberType::Manufacturer, m - 1);
+		let caller: T::AccountId = whitelisted_caller();
+		Members::<T>::append(MemberType::Manufacturer, &caller);
+		let id: BottleId = vec![b'b'; BOTTLE_ID_MAX_LENGTH];
+		let documents = documents(s);
+	}: _(RawOrigin::Signed(caller.clone()), id.clone(), documents.clone())
+	verify {
+		assert_eq!(ManufacturerOf::<T>::get(&id), Some(caller));
+		assert_eq!(BottleDocuments::<T>::get(&id).len(), documents.len());
+	}
+}
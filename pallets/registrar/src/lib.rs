@@ -0,0 +1,699 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Edit this file to define custom logic or remove it if it is not needed.
+/// Learn more about FRAME and the core library of Substrate FRAME pallets:
+/// https://substrate.dev/docs/en/knowledgebase/runtime/frame
+
+use sp_std::{prelude::*, vec::Vec};
+use frame_support::{decl_module, decl_storage, decl_event, decl_error, dispatch, ensure, storage::IterableStorageDoubleMap,
+	weights::Weight, sp_runtime::traits::Hash};
+use frame_system::ensure_signed;
+use codec::{Encode, Decode};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+
+// The standalone node builds against the crates.io release of FRAME, where configuration
+// traits are still named `Trait`, and the parachain against the `rococo-v1` branch, where
+// they are named `Config`.
+#[cfg(not(feature = "parachain"))]
+pub use frame_system::Trait as SystemConfig;
+#[cfg(not(feature = "parachain"))]
+pub use timestamp::Trait as TimestampConfig;
+#[cfg(feature = "parachain")]
+pub use frame_system::Config as SystemConfig;
+#[cfg(feature = "parachain")]
+pub use timestamp::Config as TimestampConfig;
+
+// The mock runtime is written against the standalone release of FRAME.
+#[cfg(all(test, not(feature = "parachain")))]
+mod mock;
+
+#[cfg(all(test, not(feature = "parachain")))]
+mod tests;
+
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+pub mod migrations;
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum MemberType {
+	Manufacturer,
+	Carrier,
+	Retailer,
+	Customer,
+	Distributor,
+	Warehouse,
+	Customs,
+}
+
+pub const BOTTLE_ID_MAX_LENGTH: usize = 36;
+/// Maximum number of registered members across all member types.
+pub const MAX_MEMBERS: u32 = 5_000;
+pub type BottleId = Vec<u8>;
+
+/// Largest document a bottle or a shipment can be registered with, in bytes.
+pub const DOCUMENT_MAX_SIZE: usize = 512 * 1024;
+/// Maximum number of documents a bottle or a shipment can be registered with.
+pub const MAX_DOCUMENTS: usize = 5;
+
+const DOCUMENT_PREFIX: &[u8] = b"provenance::document::";
+
+/// Key of a document in the offchain database of the nodes indexing documents, by the hash
+/// stored on chain.
+pub fn document_key<H: Encode>(hash: &H) -> Vec<u8> {
+	[DOCUMENT_PREFIX, &hash.encode()[..]].concat()
+}
+
+/// Size of the documents in bytes, which the weight of registering them depends on.
+pub fn documents_size(documents: &[Vec<u8>]) -> u32 {
+	documents.iter().fold(0u32, |size, document| size.saturating_add(document.len() as u32))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum BottleStatus {
+	Manufactured,
+	ShipmentRegistered,
+	ShipmentInTransit,
+	ShipmentDelivered,
+	SoldToCustomer,
+}
+
+impl Default for BottleStatus {
+	fn default() -> Self {
+		BottleStatus::Manufactured
+	}
+}
+
+impl BottleStatus {
+	/// Whether a bottle in this status may move on to `next`. Bottles of a shipment that is
+	/// cancelled or rejected before pickup go back in stock through `Module::return_bottle`.
+	pub fn can_become(&self, next: &BottleStatus) -> bool {
+		use BottleStatus::*;
+
+		matches!(
+			(self, next),
+			(Manufactured, ShipmentRegistered)
+				| (ShipmentRegistered, ShipmentInTransit)
+				| (ShipmentInTransit, ShipmentDelivered)
+				| (ShipmentDelivered, ShipmentRegistered)
+				| (ShipmentDelivered, SoldToCustomer)
+		)
+	}
+}
+
+/// Storage layout of the pallet, bumped by every release that needs a migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum Releases {
+	/// Bottles without owner and status, or the parachain ones without manufacturer.
+	V1_0_0,
+	/// Bottles with manufacturer, owner and status, indexed by status.
+	V2_0_0,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V1_0_0
+	}
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct Bottle<AccountId, Moment> {
+	id: BottleId,
+	manufacturer: AccountId,
+	owner: AccountId,
+	status: BottleStatus,
+	registered: Moment,
+}
+
+impl<AccountId, Moment> Bottle<AccountId, Moment> {
+	pub fn id(&self) -> &BottleId {
+		&self.id
+	}
+
+	pub fn manufacturer(&self) -> &AccountId {
+		&self.manufacturer
+	}
+
+	pub fn registered(&self) -> &Moment {
+		&self.registered
+	}
+
+	pub fn owner(&self) -> &AccountId {
+		&self.owner
+	}
+
+	pub fn status(&self) -> &BottleStatus {
+		&self.status
+	}
+
+	pub fn change_owner(mut self, new_owner: AccountId) -> Self {
+		self.owner = new_owner;
+		self
+	}
+
+	pub fn change_status(mut self, new_status: BottleStatus) -> Self {
+		self.status = new_status;
+		self
+	}
+}
+
+/// Configure the pallet by specifying the parameters and types on which it depends.
+pub trait Config: SystemConfig + TimestampConfig {
+	/// Because this pallet emits events, it depends on the runtime's definition of an event.
+	type Event: From<Event<Self>> + Into<<Self as SystemConfig>::Event>;
+
+	/// Weight information for extrinsics in this pallet.
+	type WeightInfo: WeightInfo;
+}
+
+// The pallet's runtime storage items.
+// https://substrate.dev/docs/en/knowledgebase/runtime/storage
+decl_storage! {
+	// A unique name is used to ensure that the pallet's storage items are isolated.
+	// This name may be updated, but each pallet in the runtime must use a unique name.
+	// ---------------------------------vvvvvvvvvvvvvv
+	trait Store for Module<T: Config> as RegistrarModule {
+		// Learn more about declaring storage items:
+		// https://substrate.dev/docs/en/knowledgebase/runtime/storage#declaring-storage-items
+		pub Members get(fn members): map hasher(identity) MemberType => Vec<T::AccountId>;
+		
+		pub Bottles get(fn bottle_by_id): map hasher(blake2_128_concat) BottleId => Option<Bottle<T::AccountId, T::Moment>>;
+		pub BottlesOfManufacturer get(fn bottles_of_manufacturer): map hasher(blake2_128_concat) T::AccountId => Vec<BottleId>;
+		pub ManufacturerOf get(fn owner_of): map hasher(blake2_128_concat) BottleId => Option<T::AccountId>;
+		pub BottlesByStatus: double_map hasher(blake2_128_concat) BottleStatus, hasher(blake2_128_concat) BottleId => ();
+		/// Hashes of the documents of a bottle, which are kept in the offchain index.
+		pub BottleDocuments get(fn bottle_documents): map hasher(blake2_128_concat) BottleId => Vec<T::Hash>;
+
+		/// Storage layout in use. Chains started before it was introduced are on `V1_0_0`.
+		pub StorageVersion get(fn storage_version) build(|_: &GenesisConfig<T>| Releases::V2_0_0): Releases;
+	}
+	add_extra_genesis {
+		/// Members registered at genesis. [member_type, account]
+		config(members): Vec<(MemberType, T::AccountId)>;
+		/// Bottles registered at genesis, by a manufacturer among the members. [manufacturer, bottleid]
+		config(bottles): Vec<(T::AccountId, BottleId)>;
+		build(|config: &GenesisConfig<T>| {
+			for (member_type, account) in &config.members {
+				Module::<T>::add_member(member_type.clone(), account).expect("genesis member is valid; qed");
+			}
+			for (manufacturer, id) in &config.bottles {
+				Module::<T>::add_bottle(manufacturer, id.clone()).expect("genesis bottle is valid; qed");
+			}
+		})
+	}
+}
+
+// Pallets use events to inform users when important changes are made.
+// https://substrate.dev/docs/en/knowledgebase/runtime/events
+decl_event!(
+	pub enum Event<T> where AccountId = <T as SystemConfig>::AccountId {
+		/// Manufacturer has been added. [account]
+		ManufacturerAdded(AccountId),
+		/// Carrier has been added. [account]
+		CarrierAdded(AccountId),
+		/// Retailer has been added. [account]
+		RetailerAdded(AccountId),
+		///Customer has been added. [account]
+		CustomerAdded(AccountId),
+		/// Distributor has been added. [account]
+		DistributorAdded(AccountId),
+		/// Warehouse has been added. [account]
+		WarehouseAdded(AccountId),
+		/// Customs authority has been added. [account]
+		CustomsAdded(AccountId),
+		///Bottle has been registered. [account, bottleid]
+		BottleRegistered(AccountId, BottleId),
+		/// Bottle has moved on in its lifecycle. [bottleid, status]
+		BottleStatusChanged(BottleId, BottleStatus),
+	}
+);
+
+// Errors inform users that something went wrong.
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// Member Account is already registered.
+		MemberAlreadyExist,
+		/// Please provide bottle id.
+		BottleIdMissing,
+		/// Bottle already exists.
+		BottleIdExists,
+		/// Bottle id too long.
+		BottleIdTooLong,
+		/// Not a manufacturer.
+		NotManufacturer,
+		/// Not a carrier.
+		NotCarrier,
+		/// Not a retailer.
+		NotRetailer,
+		/// Not a customer.
+		NotCustomer,
+		/// Not a distributor.
+		NotDistributor,
+		/// Not a warehouse.
+		NotWarehouse,
+		/// Not a customs authority.
+		NotCustoms,
+		/// Account is not a registered member.
+		NotMember,
+		/// Maximum number of members reached.
+		TooManyMembers,
+		/// Bottle does not exist.
+		BottleNotExist,
+		/// Not the bottle manufacturer
+		NotBottleManufacturer,
+		// Not the bottle owner
+		NotBottleOwner,
+		/// Bottle cannot move to this status from its current one.
+		InvalidBottleStatus,
+		/// Document is empty or too large.
+		InvalidDocument,
+		/// Too many documents.
+		TooManyDocuments,
+	}
+}
+
+// Dispatchable functions allows users to interact with the pallet and invoke state changes.
+// These functions materialize as "extrinsics", which are often compared to transactions.
+// Dispatchable functions must be annotated with a weight and must return a DispatchResult.
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		// Errors must be initialized if they are used by the pallet.
+		type Error = Error<T>;
+
+		// Events must be initialized if they are used by the pallet.
+		fn deposit_event() = default;
+
+		fn on_runtime_upgrade() -> Weight {
+			#[cfg(feature = "try-runtime")]
+			migrations::pre_migrate::<T>().expect("registrar pre-migration checks failed");
+
+			let weight = migrations::migrate::<T>();
+
+			#[cfg(feature = "try-runtime")]
+			migrations::post_migrate::<T>().expect("registrar post-migration checks failed");
+
+			weight
+		}
+
+		#[weight = <T as Config>::WeightInfo::register_manufacturer(MAX_MEMBERS)]
+		pub fn register_manufacturer(origin) -> dispatch::DispatchResultWithPostInfo {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			let members = Self::add_member(MemberType::Manufacturer, &who)?;
+
+			// Emit an event.
+			Self::deposit_event(Event::<T>::ManufacturerAdded(who));
+			// Return the actual weight, which depends on the number of members scanned
+			Ok(Some(<T as Config>::WeightInfo::register_manufacturer(members)).into())
+		}
+
+		#[weight = <T as Config>::WeightInfo::register_carrier(MAX_MEMBERS)]
+		pub fn register_carrier(origin) -> dispatch::DispatchResultWithPostInfo {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			let members = Self::add_member(MemberType::Carrier, &who)?;
+
+			// Emit an event.
+			Self::deposit_event(Event::<T>::CarrierAdded(who));
+			// Return the actual weight, which depends on the number of members scanned
+			Ok(Some(<T as Config>::WeightInfo::register_carrier(members)).into())
+		}
+
+		#[weight = <T as Config>::WeightInfo::register_retailer(MAX_MEMBERS)]
+		pub fn register_retailer(origin) -> dispatch::DispatchResultWithPostInfo {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			let members = Self::add_member(MemberType::Retailer, &who)?;
+
+			// Emit an event.
+			Self::deposit_event(Event::<T>::RetailerAdded(who));
+			// Return the actual weight, which depends on the number of members scanned
+			Ok(Some(<T as Config>::WeightInfo::register_retailer(members)).into())
+		}
+
+		#[weight = <T as Config>::WeightInfo::register_customer(MAX_MEMBERS)]
+		pub fn register_customer(origin) -> dispatch::DispatchResultWithPostInfo {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			let members = Self::add_member(MemberType::Customer, &who)?;
+
+			// Emit an event.
+			Self::deposit_event(Event::<T>::CustomerAdded(who));
+			// Return the actual weight, which depends on the number of members scanned
+			Ok(Some(<T as Config>::WeightInfo::register_customer(members)).into())
+		}
+
+		#[weight = <T as Config>::WeightInfo::register_distributor(MAX_MEMBERS)]
+		pub fn register_distributor(origin) -> dispatch::DispatchResultWithPostInfo {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			let members = Self::add_member(MemberType::Distributor, &who)?;
+
+			// Emit an event.
+			Self::deposit_event(Event::<T>::DistributorAdded(who));
+			// Return the actual weight, which depends on the number of members scanned
+			Ok(Some(<T as Config>::WeightInfo::register_distributor(members)).into())
+		}
+
+		#[weight = <T as Config>::WeightInfo::register_warehouse(MAX_MEMBERS)]
+		pub fn register_warehouse(origin) -> dispatch::DispatchResultWithPostInfo {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			let members = Self::add_member(MemberType::Warehouse, &who)?;
+
+			// Emit an event.
+			Self::deposit_event(Event::<T>::WarehouseAdded(who));
+			// Return the actual weight, which depends on the number of members scanned
+			Ok(Some(<T as Config>::WeightInfo::register_warehouse(members)).into())
+		}
+
+		#[weight = <T as Config>::WeightInfo::register_customs(MAX_MEMBERS)]
+		pub fn register_customs(origin) -> dispatch::DispatchResultWithPostInfo {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			let members = Self::add_member(MemberType::Customs, &who)?;
+
+			// Emit an event.
+			Self::deposit_event(Event::<T>::CustomsAdded(who));
+			// Return the actual weight, which depends on the number of members scanned
+			Ok(Some(<T as Config>::WeightInfo::register_customs(members)).into())
+		}
+
+		/// Registers a bottle with its documents, such as lab certificates. Only the hashes
+		/// of the documents are stored on chain, the documents go to the offchain index.
+		#[weight = <T as Config>::WeightInfo::register_bottle(MAX_MEMBERS, documents_size(documents))]
+		pub fn register_bottle(origin, id: BottleId, documents: Vec<Vec<u8>>) -> dispatch::DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			Self::validate_documents(&documents)?;
+
+			Self::add_bottle(&who, id.clone())?;
+
+			if !documents.is_empty() {
+				BottleDocuments::<T>::insert(&id, Self::index_documents(&documents));
+			}
+
+			Self::deposit_event(Event::<T>::BottleRegistered(who, id));
+
+			Ok(())
+		}
+	}
+
+}
+
+impl<T: Config> Module<T> {
+	/// Adds the account to the members of the given type and returns the number of
+	/// members that had to be checked.
+	fn add_member(member_type: MemberType, account_id: &T::AccountId) -> Result<u32, dispatch::DispatchError> {
+		let count = Self::validate_new_member(account_id)?;
+
+		let mut members = <Module<T>>::members(&member_type);
+		
+		members.push(account_id.clone());
+		Members::<T>::insert(member_type, members);
+
+		Ok(count)
+	}
+
+	/// Registers a bottle made by `manufacturer`, registered now.
+	fn add_bottle(manufacturer: &T::AccountId, id: BottleId) -> dispatch::DispatchResult {
+		Self::validate_manufacturer(manufacturer)?;
+
+		Self::validate_bottle_id(&id)?;
+
+		Self::validate_new_bottle(&id)?;
+
+		let new_bottle = Self::new_bottle()
+			.identified_by(id.clone())
+			.manufactured_by(manufacturer.clone())
+			.registered_on(<timestamp::Module<T>>::now())
+			.build();
+
+		Bottles::<T>::insert(&id, new_bottle);
+		BottlesOfManufacturer::<T>::append(manufacturer, &id);
+		ManufacturerOf::<T>::insert(&id, manufacturer);
+		BottlesByStatus::insert(BottleStatus::Manufactured, &id, ());
+
+		Ok(())
+	}
+
+	pub fn validate_documents(documents: &[Vec<u8>]) -> dispatch::DispatchResult {
+		ensure!(documents.len() <= MAX_DOCUMENTS, Error::<T>::TooManyDocuments);
+		ensure!(
+			documents.iter().all(|document| !document.is_empty() && document.len() <= DOCUMENT_MAX_SIZE),
+			Error::<T>::InvalidDocument
+		);
+
+		Ok(())
+	}
+
+	/// Writes validated documents into the offchain index of the nodes indexing documents
+	/// and returns their hashes. The offchain index is not reverted with storage, so this
+	/// must come after everything that may fail.
+	pub fn index_documents(documents: &[Vec<u8>]) -> Vec<T::Hash> {
+		documents.iter().map(|document| {
+			let hash = T::Hashing::hash(document);
+			sp_io::offchain_index::set(&document_key(&hash), document);
+			hash
+		}).collect()
+	}
+
+	pub fn member_type_of(account_id: &T::AccountId) -> Option<MemberType> {
+		Members::<T>::iter()
+			.find(|(_, accounts)| accounts.contains(account_id))
+			.map(|(member_type, _)| member_type)
+	}
+
+	/// Checks that the account can register as a member, and returns the number of members.
+	pub fn validate_new_member(account_id: &T::AccountId) -> Result<u32, dispatch::DispatchError> {
+		let member_itr = Members::<T>::iter();
+		let mut count: u32 = 0;
+
+		for mem in member_itr {
+			ensure!(!(mem.1).contains(account_id), Error::<T>::MemberAlreadyExist);
+			count = count.saturating_add(mem.1.len() as u32);
+		}
+
+		ensure!(count < MAX_MEMBERS, Error::<T>::TooManyMembers);
+
+		Ok(count)
+	}
+
+	pub fn validate_member(account_id: &T::AccountId) -> dispatch::DispatchResult {
+		ensure!(Self::member_type_of(account_id).is_some(), Error::<T>::NotMember);
+
+		Ok(())
+	}
+
+	pub fn validate_manufacturer(account_id: &T::AccountId) -> dispatch::DispatchResult {
+		let all_manufacturers = <Module<T>>::members(MemberType::Manufacturer);
+
+		ensure!(all_manufacturers.contains(account_id), Error::<T>::NotManufacturer);
+
+		Ok(())
+	}
+
+	pub fn validate_carrier(account_id: &T::AccountId) -> dispatch::DispatchResult {
+		let all_carriers = <Module<T>>::members(MemberType::Carrier);
+
+		ensure!(all_carriers.contains(account_id), Error::<T>::NotCarrier);
+
+		Ok(())
+	}
+
+	pub fn validate_retailer(account_id: &T::AccountId) -> dispatch::DispatchResult {
+		let all_retailers = <Module<T>>::members(MemberType::Retailer);
+
+		ensure!(all_retailers.contains(account_id), Error::<T>::NotRetailer);
+
+		Ok(())
+	}
+
+	pub fn validate_customer(account_id: &T::AccountId) -> dispatch::DispatchResult {
+		let all_customers = Module::<T>::members(MemberType::Customer);
+
+		ensure!(all_customers.contains(account_id), Error::<T>::NotCustomer);
+
+		Ok(())
+	}
+
+	pub fn validate_distributor(account_id: &T::AccountId) -> dispatch::DispatchResult {
+		let all_distributors = Module::<T>::members(MemberType::Distributor);
+
+		ensure!(all_distributors.contains(account_id), Error::<T>::NotDistributor);
+
+		Ok(())
+	}
+
+	pub fn validate_warehouse(account_id: &T::AccountId) -> dispatch::DispatchResult {
+		let all_warehouses = Module::<T>::members(MemberType::Warehouse);
+
+		ensure!(all_warehouses.contains(account_id), Error::<T>::NotWarehouse);
+
+		Ok(())
+	}
+
+	pub fn validate_customs(account_id: &T::AccountId) -> dispatch::DispatchResult {
+		let all_customs = Module::<T>::members(MemberType::Customs);
+
+		ensure!(all_customs.contains(account_id), Error::<T>::NotCustoms);
+
+		Ok(())
+	}
+
+	pub fn check_bottle_id_present(id: &[u8]) -> dispatch::DispatchResult {
+		ensure!(
+			<Bottles::<T>>::contains_key(id),
+			Error::<T>::BottleNotExist
+		);
+		Ok(())
+	}
+
+	pub fn check_bottle_manufacturer(id: &[u8], account: &T::AccountId) -> dispatch::DispatchResult {
+		ensure!(<ManufacturerOf::<T>>::get(id) == Some(account.clone()), Error::<T>::NotBottleManufacturer);
+
+		Ok(())
+	}
+
+	pub fn validate_bottle_id(id: &[u8]) -> dispatch::DispatchResult {
+		ensure!(!id.is_empty(), Error::<T>::BottleIdMissing);
+		ensure!(id.len() <= BOTTLE_ID_MAX_LENGTH, Error::<T>::BottleIdTooLong);
+		Ok(())
+	}
+
+	pub fn validate_new_bottle(id: &[u8]) -> dispatch::DispatchResult {
+		// Bottle existence check
+		ensure!(
+			!<Bottles::<T>>::contains_key(id),
+			Error::<T>::BottleIdExists
+		);
+		Ok(())
+	}
+
+	pub fn new_bottle() -> BottleBuilder<T::AccountId, T::Moment> {
+        BottleBuilder::<T::AccountId, T::Moment>::default()
+    }
+
+	/// Hands the bottle over to `new_owner`, whoever has custody of it from now on.
+	pub fn update_bottle_owner(bottle_id: &BottleId, new_owner: T::AccountId) -> dispatch::DispatchResult {
+
+		let mut bottle: Bottle<T::AccountId, T::Moment> = match Bottles::<T>::get(bottle_id) {
+			None => Err(Error::<T>::BottleNotExist),
+			Some(bottle) => Ok(bottle),
+		}?;
+
+		bottle = bottle.change_owner(new_owner);
+
+		Bottles::<T>::insert(bottle_id, bottle);
+
+		Ok(())
+	}
+
+	/// Moves the bottle on to `new_status`, which has to follow from its current status.
+	/// Setting the status the bottle already has does nothing.
+	pub fn update_bottle_status(bottle_id: &BottleId, new_status: BottleStatus) -> dispatch::DispatchResult {
+		let bottle = Bottles::<T>::get(bottle_id).ok_or(Error::<T>::BottleNotExist)?;
+
+		if bottle.status == new_status {
+			return Ok(());
+		}
+
+		ensure!(bottle.status.can_become(&new_status), Error::<T>::InvalidBottleStatus);
+
+		Self::move_bottle(bottle_id, bottle, new_status);
+
+		Ok(())
+	}
+
+	fn move_bottle(bottle_id: &BottleId, bottle: Bottle<T::AccountId, T::Moment>, new_status: BottleStatus) {
+		BottlesByStatus::remove(&bottle.status, bottle_id);
+		BottlesByStatus::insert(&new_status, bottle_id, ());
+		Bottles::<T>::insert(bottle_id, bottle.change_status(new_status.clone()));
+
+		Self::deposit_event(Event::<T>::BottleStatusChanged(bottle_id.clone(), new_status));
+	}
+
+	/// Hands the bottle of a shipment that fell through before pickup back to `owner`. It is
+	/// back in stock, as manufactured if `owner` is its manufacturer and as delivered otherwise.
+	pub fn return_bottle(bottle_id: &BottleId, owner: T::AccountId) -> dispatch::DispatchResult {
+		let bottle = Bottles::<T>::get(bottle_id).ok_or(Error::<T>::BottleNotExist)?;
+
+		ensure!(bottle.status == BottleStatus::ShipmentRegistered, Error::<T>::InvalidBottleStatus);
+
+		let status = match ManufacturerOf::<T>::get(bottle_id) {
+			Some(manufacturer) if manufacturer == owner => BottleStatus::Manufactured,
+			_ => BottleStatus::ShipmentDelivered,
+		};
+
+		Self::move_bottle(bottle_id, bottle, status);
+		Self::update_bottle_owner(bottle_id, owner)
+	}
+
+	pub fn bottles_with_status(status: BottleStatus) -> Vec<BottleId> {
+		BottlesByStatus::iter_prefix(status).map(|(id, ())| id).collect()
+	}
+
+	pub fn check_bottle_owner(id: &[u8], owner: &T::AccountId) -> dispatch::DispatchResult {
+		let bottle = Bottles::<T>::get(id).ok_or(Error::<T>::BottleNotExist)?;
+
+		ensure!(bottle.owner == *owner, Error::<T>::NotBottleOwner);
+
+		Ok(())
+	}
+}
+
+
+#[derive(Default)]
+pub struct BottleBuilder<AccountId, Moment>
+where
+    AccountId: Default + Clone,
+    Moment: Default,
+{
+    id: BottleId,
+    manufacturer: AccountId,
+	registered: Moment,
+}
+
+impl<AccountId, Moment> BottleBuilder<AccountId, Moment>
+where
+    AccountId: Default + Clone,
+    Moment: Default,
+{
+    pub fn identified_by(mut self, id: BottleId) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn manufactured_by(mut self, manufacturer: AccountId) -> Self {
+        self.manufacturer = manufacturer;
+        self
+    }
+
+    pub fn registered_on(mut self, registered: Moment) -> Self {
+        self.registered = registered;
+        self
+    }
+
+    pub fn build(self) -> Bottle<AccountId, Moment> {
+        Bottle::<AccountId, Moment> {
+            id: self.id,
+            owner: self.manufacturer.clone(),
+            manufacturer: self.manufacturer,
+            status: BottleStatus::Manufactured,
+            registered: self.registered
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,144 @@
+//! Storage migrations of the registrar pallet.
+//!
+//! `migrate` brings the storage from any earlier release to the current one and is a no-op
+//! once it did, so other pallets relying on the current layout may run it first.
+
+use frame_support::{traits::Get, weights::Weight, storage::IterableStorageMap, StorageMap, StorageDoubleMap, StorageValue};
+use crate::{Config, Bottle, BottleId, BottleStatus, Bottles, BottlesByStatus, Releases, StorageVersion};
+#[cfg(any(feature = "parachain", test, feature = "try-runtime"))]
+use crate::ManufacturerOf;
+
+/// Layout of the first release.
+pub mod v1 {
+	use codec::{Encode, Decode};
+	#[cfg(feature = "parachain")]
+	use crate::BottleStatus;
+	use crate::BottleId;
+
+	/// The standalone chain only recorded who manufactured the bottle.
+	#[cfg(not(feature = "parachain"))]
+	#[derive(Debug, PartialEq, Encode, Decode)]
+	pub struct Bottle<AccountId, Moment> {
+		pub id: BottleId,
+		pub manufacturer: AccountId,
+		pub registered: Moment,
+	}
+
+	/// The parachain replaced the manufacturer with the owner and added a status, which
+	/// was never updated. The manufacturer is still in `ManufacturerOf`.
+	#[cfg(feature = "parachain")]
+	#[derive(Debug, PartialEq, Encode, Decode)]
+	pub struct Bottle<AccountId, Moment> {
+		pub id: BottleId,
+		pub owner: AccountId,
+		pub status: BottleStatus,
+		pub registered: Moment,
+	}
+}
+
+pub fn migrate<T: Config>() -> Weight {
+	match StorageVersion::get() {
+		Releases::V1_0_0 => migrate_to_v2::<T>(),
+		Releases::V2_0_0 => T::DbWeight::get().reads(1),
+	}
+}
+
+/// Gives every bottle a manufacturer, owner and status and indexes it by status. Bottles keep
+/// the owner they had, or their manufacturer, and start out as manufactured; pallets tracking
+/// the bottles afterwards put them back where they are with `restore_bottle`.
+fn migrate_to_v2<T: Config>() -> Weight {
+	Bottles::<T>::translate::<v1::Bottle<T::AccountId, T::Moment>, _>(|id, old| Some(upgrade_bottle::<T>(&id, old)));
+
+	let mut count: Weight = 0;
+	for (id, bottle) in Bottles::<T>::iter() {
+		BottlesByStatus::insert(&bottle.status, &id, ());
+		count = count.saturating_add(1);
+	}
+
+	StorageVersion::put(Releases::V2_0_0);
+
+	T::DbWeight::get().reads_writes(count.saturating_mul(3).saturating_add(1), count.saturating_mul(2).saturating_add(1))
+}
+
+#[cfg(not(feature = "parachain"))]
+fn upgrade_bottle<T: Config>(_id: &BottleId, old: v1::Bottle<T::AccountId, T::Moment>) -> Bottle<T::AccountId, T::Moment> {
+	Bottle {
+		id: old.id,
+		owner: old.manufacturer.clone(),
+		manufacturer: old.manufacturer,
+		status: BottleStatus::Manufactured,
+		registered: old.registered,
+	}
+}
+
+#[cfg(feature = "parachain")]
+fn upgrade_bottle<T: Config>(id: &BottleId, old: v1::Bottle<T::AccountId, T::Moment>) -> Bottle<T::AccountId, T::Moment> {
+	Bottle {
+		id: old.id,
+		manufacturer: ManufacturerOf::<T>::get(id).unwrap_or_else(|| old.owner.clone()),
+		owner: old.owner,
+		status: BottleStatus::Manufactured,
+		registered: old.registered,
+	}
+}
+
+/// Puts a migrated bottle back with its owner and in its status, skipping the transition
+/// checks of `Module::update_bottle_status`. Only meant for migrations.
+pub fn restore_bottle<T: Config>(id: &BottleId, owner: T::AccountId, status: BottleStatus) -> Weight {
+	if let Some(bottle) = Bottles::<T>::get(id) {
+		BottlesByStatus::remove(&bottle.status, id);
+		BottlesByStatus::insert(&status, id, ());
+		Bottles::<T>::insert(id, bottle.change_owner(owner).change_status(status));
+	}
+
+	T::DbWeight::get().reads_writes(1, 3)
+}
+
+/// Checks that every bottle is stored in the layout the migration expects.
+#[cfg(any(test, feature = "try-runtime"))]
+pub fn pre_migrate<T: Config>() -> Result<(), &'static str> {
+	use codec::Encode;
+	use frame_support::{storage::migration::get_storage_value, Blake2_128Concat, StorageHasher};
+
+	if StorageVersion::get() != Releases::V1_0_0 {
+		return Ok(());
+	}
+
+	for (id, _) in ManufacturerOf::<T>::iter() {
+		let old = get_storage_value::<v1::Bottle<T::AccountId, T::Moment>>(
+			b"RegistrarModule", b"Bottles", &Blake2_128Concat::hash(&id.encode()),
+		);
+		if old.is_none() {
+			return Err("bottle not stored in the v1 layout");
+		}
+	}
+
+	Ok(())
+}
+
+/// Checks that every bottle decodes in the current layout and is indexed under its status only.
+#[cfg(any(test, feature = "try-runtime"))]
+pub fn post_migrate<T: Config>() -> Result<(), &'static str> {
+	use frame_support::storage::IterableStorageDoubleMap;
+
+	if StorageVersion::get() != Releases::V2_0_0 {
+		return Err("storage version not bumped");
+	}
+
+	for (id, manufacturer) in ManufacturerOf::<T>::iter() {
+		let bottle = Bottles::<T>::get(&id).ok_or("bottle not stored in the v2 layout")?;
+		if bottle.manufacturer != manufacturer {
+			return Err("bottle manufacturer lost");
+		}
+		if !BottlesByStatus::contains_key(&bottle.status, &id) {
+			return Err("bottle not indexed under its status");
+		}
+	}
+
+	let indexed: usize = BottlesByStatus::iter().count();
+	if indexed != Bottles::<T>::iter().count() {
+		return Err("bottle indexed under several statuses");
+	}
+
+	Ok(())
+}
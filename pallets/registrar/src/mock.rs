@@ -0,0 +1,105 @@
+use crate as registrar;
+use crate::Config;
+use sp_core::H256;
+use frame_support::{parameter_types, weights::Weight};
+use sp_runtime::{
+	traits::{BlakeTwo256, Block as _, IdentityLookup}, testing::Header, Perbill,
+};
+use frame_system as system;
+
+pub type Block = sp_runtime::generic::Block<Header, UncheckedExtrinsic>;
+pub type UncheckedExtrinsic = sp_runtime::generic::UncheckedExtrinsic<u64, Call, (), ()>;
+
+// Configure a mock runtime to test the pallet.
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: system::{Module, Call, Config, Storage, Event<T>},
+		Timestamp: timestamp::{Module, Call, Storage, Inherent},
+		RegistrarModule: registrar::{Module, Call, Config<T>, Storage, Event<T>},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MaximumBlockWeight: Weight = 1024;
+	pub const MaximumBlockLength: u32 = 2 * 1024;
+	pub const AvailableBlockRatio: Perbill = Perbill::one();
+}
+
+impl system::Trait for Test {
+	type BaseCallFilter = ();
+	type Origin = Origin;
+	type Call = Call;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = BlockHashCount;
+	type MaximumBlockWeight = MaximumBlockWeight;
+	type DbWeight = ();
+	type BlockExecutionWeight = ();
+	type ExtrinsicBaseWeight = ();
+	type MaximumExtrinsicWeight = ();
+	type MaximumBlockLength = MaximumBlockLength;
+	type AvailableBlockRatio = AvailableBlockRatio;
+	type Version = ();
+	type PalletInfo = ();
+	type AccountData = ();
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+}
+
+parameter_types! {
+	pub const MinimumPeriod: u64 = 1;
+}
+
+impl timestamp::Trait for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = MinimumPeriod;
+	type WeightInfo = ();
+}
+
+impl Config for Test {
+	type Event = Event;
+	type WeightInfo = ();
+}
+
+pub const MANUFACTURER: u64 = 1;
+pub const CARRIER: u64 = 2;
+pub const RETAILER: u64 = 3;
+pub const CUSTOMER: u64 = 4;
+pub const DISTRIBUTOR: u64 = 5;
+pub const WAREHOUSE: u64 = 6;
+pub const CUSTOMS: u64 = 7;
+pub const STRANGER: u64 = 99;
+
+// Build genesis storage according to the mock runtime.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	test_ext_with(Default::default())
+}
+
+/// Externalities starting from the given registrar genesis.
+pub fn test_ext_with(genesis: registrar::GenesisConfig<Test>) -> sp_io::TestExternalities {
+	let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	genesis.assimilate_storage(&mut t).unwrap();
+	let mut ext = sp_io::TestExternalities::new(t);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
+
+pub fn last_event() -> Event {
+	system::Module::<Test>::events()
+		.pop()
+		.map(|e| e.event)
+		.expect("Event expected")
+}
@@ -0,0 +1,546 @@
+use crate::{
+	Error, Event as RegistrarEvent, GenesisConfig, Members, MemberType, Bottles, BottleDocuments, BottleStatus,
+	ManufacturerOf, Releases, StorageVersion, MAX_MEMBERS, BOTTLE_ID_MAX_LENGTH, DOCUMENT_MAX_SIZE, MAX_DOCUMENTS,
+	migrations, mock::*,
+};
+use codec::Encode;
+use frame_support::{
+	assert_ok, assert_noop, storage::migration::put_storage_value, traits::OnRuntimeUpgrade, Blake2_128Concat,
+	StorageHasher, StorageMap, StorageValue,
+};
+use sp_runtime::traits::{BlakeTwo256, Hash};
+
+fn register_all_members() {
+	assert_ok!(RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)));
+	assert_ok!(RegistrarModule::register_carrier(Origin::signed(CARRIER)));
+	assert_ok!(RegistrarModule::register_retailer(Origin::signed(RETAILER)));
+	assert_ok!(RegistrarModule::register_customer(Origin::signed(CUSTOMER)));
+	assert_ok!(RegistrarModule::register_distributor(Origin::signed(DISTRIBUTOR)));
+	assert_ok!(RegistrarModule::register_warehouse(Origin::signed(WAREHOUSE)));
+	assert_ok!(RegistrarModule::register_customs(Origin::signed(CUSTOMS)));
+}
+
+#[test]
+fn register_manufacturer_works() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)));
+
+		assert_eq!(RegistrarModule::members(MemberType::Manufacturer), vec![MANUFACTURER]);
+		assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::ManufacturerAdded(MANUFACTURER)));
+	});
+}
+
+#[test]
+fn register_carrier_works() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_carrier(Origin::signed(CARRIER)));
+
+		assert_eq!(RegistrarModule::members(MemberType::Carrier), vec![CARRIER]);
+		assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::CarrierAdded(CARRIER)));
+	});
+}
+
+#[test]
+fn register_retailer_works() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_retailer(Origin::signed(RETAILER)));
+
+		assert_eq!(RegistrarModule::members(MemberType::Retailer), vec![RETAILER]);
+		assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::RetailerAdded(RETAILER)));
+	});
+}
+
+#[test]
+fn register_customer_works() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_customer(Origin::signed(CUSTOMER)));
+
+		assert_eq!(RegistrarModule::members(MemberType::Customer), vec![CUSTOMER]);
+		assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::CustomerAdded(CUSTOMER)));
+	});
+}
+
+#[test]
+fn register_distributor_works() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_distributor(Origin::signed(DISTRIBUTOR)));
+
+		assert_eq!(RegistrarModule::members(MemberType::Distributor), vec![DISTRIBUTOR]);
+		assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::DistributorAdded(DISTRIBUTOR)));
+	});
+}
+
+#[test]
+fn register_warehouse_works() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_warehouse(Origin::signed(WAREHOUSE)));
+
+		assert_eq!(RegistrarModule::members(MemberType::Warehouse), vec![WAREHOUSE]);
+		assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::WarehouseAdded(WAREHOUSE)));
+	});
+}
+
+#[test]
+fn register_customs_works() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_customs(Origin::signed(CUSTOMS)));
+
+		assert_eq!(RegistrarModule::members(MemberType::Customs), vec![CUSTOMS]);
+		assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::CustomsAdded(CUSTOMS)));
+	});
+}
+
+#[test]
+fn register_member_twice_fails() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)));
+
+		assert_noop!(
+			RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)),
+			Error::<Test>::MemberAlreadyExist
+		);
+	});
+}
+
+#[test]
+fn register_member_with_another_type_fails() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)));
+
+		assert_noop!(
+			RegistrarModule::register_carrier(Origin::signed(MANUFACTURER)),
+			Error::<Test>::MemberAlreadyExist
+		);
+		assert_noop!(
+			RegistrarModule::register_customs(Origin::signed(MANUFACTURER)),
+			Error::<Test>::MemberAlreadyExist
+		);
+	});
+}
+
+#[test]
+fn register_member_fails_when_members_are_full() {
+	new_test_ext().execute_with(|| {
+		let half = (MAX_MEMBERS / 2) as u64;
+		Members::<Test>::insert(MemberType::Retailer, (1_000..1_000 + half).collect::<Vec<u64>>());
+		Members::<Test>::insert(
+			MemberType::Customer,
+			(10_000..10_000 + MAX_MEMBERS as u64 - half).collect::<Vec<u64>>(),
+		);
+
+		assert_noop!(
+			RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)),
+			Error::<Test>::TooManyMembers
+		);
+	});
+}
+
+#[test]
+fn register_member_below_limit_works() {
+	new_test_ext().execute_with(|| {
+		Members::<Test>::insert(
+			MemberType::Customer,
+			(10_000..10_000 + MAX_MEMBERS as u64 - 1).collect::<Vec<u64>>(),
+		);
+
+		assert_ok!(RegistrarModule::register_manufacturer(Origin::signed(MANUFACTURER)));
+	});
+}
+
+#[test]
+fn member_type_of_works() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+
+		assert_eq!(RegistrarModule::member_type_of(&MANUFACTURER), Some(MemberType::Manufacturer));
+		assert_eq!(RegistrarModule::member_type_of(&CARRIER), Some(MemberType::Carrier));
+		assert_eq!(RegistrarModule::member_type_of(&RETAILER), Some(MemberType::Retailer));
+		assert_eq!(RegistrarModule::member_type_of(&CUSTOMER), Some(MemberType::Customer));
+		assert_eq!(RegistrarModule::member_type_of(&DISTRIBUTOR), Some(MemberType::Distributor));
+		assert_eq!(RegistrarModule::member_type_of(&WAREHOUSE), Some(MemberType::Warehouse));
+		assert_eq!(RegistrarModule::member_type_of(&CUSTOMS), Some(MemberType::Customs));
+		assert_eq!(RegistrarModule::member_type_of(&STRANGER), None);
+	});
+}
+
+#[test]
+fn validate_member_works() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+
+		assert_ok!(RegistrarModule::validate_member(&CUSTOMS));
+		assert_noop!(RegistrarModule::validate_member(&STRANGER), Error::<Test>::NotMember);
+	});
+}
+
+#[test]
+fn validate_member_type_works() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+
+		assert_ok!(RegistrarModule::validate_manufacturer(&MANUFACTURER));
+		assert_ok!(RegistrarModule::validate_carrier(&CARRIER));
+		assert_ok!(RegistrarModule::validate_retailer(&RETAILER));
+		assert_ok!(RegistrarModule::validate_customer(&CUSTOMER));
+		assert_ok!(RegistrarModule::validate_distributor(&DISTRIBUTOR));
+		assert_ok!(RegistrarModule::validate_warehouse(&WAREHOUSE));
+		assert_ok!(RegistrarModule::validate_customs(&CUSTOMS));
+	});
+}
+
+#[test]
+fn validate_member_type_fails_for_other_types() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+
+		assert_noop!(RegistrarModule::validate_manufacturer(&CARRIER), Error::<Test>::NotManufacturer);
+		assert_noop!(RegistrarModule::validate_carrier(&RETAILER), Error::<Test>::NotCarrier);
+		assert_noop!(RegistrarModule::validate_retailer(&CUSTOMER), Error::<Test>::NotRetailer);
+		assert_noop!(RegistrarModule::validate_customer(&DISTRIBUTOR), Error::<Test>::NotCustomer);
+		assert_noop!(RegistrarModule::validate_distributor(&WAREHOUSE), Error::<Test>::NotDistributor);
+		assert_noop!(RegistrarModule::validate_warehouse(&CUSTOMS), Error::<Test>::NotWarehouse);
+		assert_noop!(RegistrarModule::validate_customs(&MANUFACTURER), Error::<Test>::NotCustoms);
+	});
+}
+
+#[test]
+fn register_bottle_works() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		Timestamp::set_timestamp(42);
+		let id = b"bottle-1".to_vec();
+
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), id.clone(), vec![]));
+
+		let bottle = RegistrarModule::bottle_by_id(&id).expect("bottle should exist");
+		assert_eq!(bottle.id, id);
+		assert_eq!(bottle.manufacturer, MANUFACTURER);
+		assert_eq!(bottle.registered, 42);
+		assert_eq!(bottle.owner(), &MANUFACTURER);
+		assert_eq!(bottle.status(), &BottleStatus::Manufactured);
+		assert_eq!(RegistrarModule::bottles_of_manufacturer(MANUFACTURER), vec![id.clone()]);
+		assert_eq!(RegistrarModule::owner_of(&id), Some(MANUFACTURER));
+		assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::BottleRegistered(MANUFACTURER, id)));
+	});
+}
+
+#[test]
+fn register_bottle_by_non_manufacturer_fails() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(RETAILER), b"bottle-1".to_vec(), vec![]),
+			Error::<Test>::NotManufacturer
+		);
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(STRANGER), b"bottle-1".to_vec(), vec![]),
+			Error::<Test>::NotManufacturer
+		);
+	});
+}
+
+#[test]
+fn register_bottle_without_id_fails() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), vec![], vec![]),
+			Error::<Test>::BottleIdMissing
+		);
+	});
+}
+
+#[test]
+fn register_bottle_with_long_id_fails() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+
+		assert_ok!(RegistrarModule::register_bottle(
+			Origin::signed(MANUFACTURER),
+			vec![b'b'; BOTTLE_ID_MAX_LENGTH],
+			vec![]
+		));
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), vec![b'b'; BOTTLE_ID_MAX_LENGTH + 1], vec![]),
+			Error::<Test>::BottleIdTooLong
+		);
+	});
+}
+
+#[test]
+fn register_bottle_twice_fails() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		assert_ok!(RegistrarModule::register_manufacturer(Origin::signed(STRANGER)));
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), b"bottle-1".to_vec(), vec![]));
+
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), b"bottle-1".to_vec(), vec![]),
+			Error::<Test>::BottleIdExists
+		);
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(STRANGER), b"bottle-1".to_vec(), vec![]),
+			Error::<Test>::BottleIdExists
+		);
+	});
+}
+
+#[test]
+fn register_bottle_with_documents_stores_their_hashes() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		let documents = vec![b"lab certificate".to_vec(), vec![b'd'; DOCUMENT_MAX_SIZE]];
+
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), b"bottle-1".to_vec(), documents.clone()));
+
+		assert_eq!(
+			RegistrarModule::bottle_documents(b"bottle-1".to_vec()),
+			documents.iter().map(|document| BlakeTwo256::hash(document)).collect::<Vec<_>>()
+		);
+		assert!(!BottleDocuments::<Test>::contains_key(b"bottle-2".to_vec()));
+	});
+}
+
+#[test]
+fn register_bottle_with_invalid_documents_fails() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		let bottle = || b"bottle-1".to_vec();
+
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), bottle(), vec![vec![]]),
+			Error::<Test>::InvalidDocument
+		);
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), bottle(), vec![vec![b'd'; DOCUMENT_MAX_SIZE + 1]]),
+			Error::<Test>::InvalidDocument
+		);
+		assert_noop!(
+			RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), bottle(), vec![b"doc".to_vec(); MAX_DOCUMENTS + 1]),
+			Error::<Test>::TooManyDocuments
+		);
+	});
+}
+
+#[test]
+fn check_bottle_works() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), b"bottle-1".to_vec(), vec![]));
+
+		assert_ok!(RegistrarModule::check_bottle_id_present(b"bottle-1"));
+		assert_noop!(RegistrarModule::check_bottle_id_present(b"bottle-2"), Error::<Test>::BottleNotExist);
+
+		assert_ok!(RegistrarModule::check_bottle_manufacturer(b"bottle-1", &MANUFACTURER));
+		assert_noop!(
+			RegistrarModule::check_bottle_manufacturer(b"bottle-1", &RETAILER),
+			Error::<Test>::NotBottleManufacturer
+		);
+		assert!(!Bottles::<Test>::contains_key(b"bottle-2".to_vec()));
+	});
+}
+
+#[test]
+fn update_bottle_owner_works() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), b"bottle-1".to_vec(), vec![]));
+		assert_ok!(RegistrarModule::check_bottle_owner(b"bottle-1", &MANUFACTURER));
+
+		assert_ok!(RegistrarModule::update_bottle_owner(&b"bottle-1".to_vec(), CARRIER));
+
+		assert_ok!(RegistrarModule::check_bottle_owner(b"bottle-1", &CARRIER));
+		assert_noop!(RegistrarModule::check_bottle_owner(b"bottle-1", &MANUFACTURER), Error::<Test>::NotBottleOwner);
+		assert_ok!(RegistrarModule::check_bottle_manufacturer(b"bottle-1", &MANUFACTURER));
+		assert_noop!(
+			RegistrarModule::update_bottle_owner(&b"bottle-2".to_vec(), CARRIER),
+			Error::<Test>::BottleNotExist
+		);
+		assert_noop!(RegistrarModule::check_bottle_owner(b"bottle-2", &CARRIER), Error::<Test>::BottleNotExist);
+	});
+}
+
+#[test]
+fn bottle_status_moves_through_the_lifecycle() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		let id = b"bottle-1".to_vec();
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), id.clone(), vec![]));
+		assert_eq!(RegistrarModule::bottles_with_status(BottleStatus::Manufactured), vec![id.clone()]);
+
+		for status in &[
+			BottleStatus::ShipmentRegistered,
+			BottleStatus::ShipmentInTransit,
+			BottleStatus::ShipmentDelivered,
+			BottleStatus::SoldToCustomer,
+		] {
+			assert_ok!(RegistrarModule::update_bottle_status(&id, status.clone()));
+			assert_eq!(RegistrarModule::bottle_by_id(&id).unwrap().status(), status);
+			assert_eq!(RegistrarModule::bottles_with_status(status.clone()), vec![id.clone()]);
+			assert_eq!(last_event(), Event::registrar(RegistrarEvent::<Test>::BottleStatusChanged(id.clone(), status.clone())));
+		}
+
+		assert!(RegistrarModule::bottles_with_status(BottleStatus::Manufactured).is_empty());
+		assert!(RegistrarModule::bottles_with_status(BottleStatus::ShipmentDelivered).is_empty());
+	});
+}
+
+#[test]
+fn bottle_status_cannot_skip_states() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		let id = b"bottle-1".to_vec();
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), id.clone(), vec![]));
+
+		assert_noop!(
+			RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentInTransit),
+			Error::<Test>::InvalidBottleStatus
+		);
+		assert_noop!(
+			RegistrarModule::update_bottle_status(&id, BottleStatus::SoldToCustomer),
+			Error::<Test>::InvalidBottleStatus
+		);
+		assert_ok!(RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentRegistered));
+		assert_noop!(
+			RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentDelivered),
+			Error::<Test>::InvalidBottleStatus
+		);
+		assert_ok!(RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentInTransit));
+		assert_noop!(
+			RegistrarModule::update_bottle_status(&id, BottleStatus::Manufactured),
+			Error::<Test>::InvalidBottleStatus
+		);
+		assert_noop!(
+			RegistrarModule::update_bottle_status(&b"bottle-2".to_vec(), BottleStatus::ShipmentRegistered),
+			Error::<Test>::BottleNotExist
+		);
+	});
+}
+
+#[test]
+fn returned_bottle_is_back_in_stock() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		let id = b"bottle-1".to_vec();
+		let status = || RegistrarModule::bottle_by_id(&id).unwrap().status().clone();
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), id.clone(), vec![]));
+
+		assert_ok!(RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentRegistered));
+		assert_ok!(RegistrarModule::return_bottle(&id, MANUFACTURER));
+		assert_eq!(status(), BottleStatus::Manufactured);
+
+		assert_ok!(RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentRegistered));
+		assert_ok!(RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentInTransit));
+		assert_ok!(RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentDelivered));
+		assert_ok!(RegistrarModule::update_bottle_owner(&id, RETAILER));
+		assert_ok!(RegistrarModule::update_bottle_status(&id, BottleStatus::ShipmentRegistered));
+		assert_ok!(RegistrarModule::return_bottle(&id, RETAILER));
+		assert_eq!(status(), BottleStatus::ShipmentDelivered);
+		assert_ok!(RegistrarModule::check_bottle_owner(&id, &RETAILER));
+		assert_noop!(RegistrarModule::return_bottle(&id, RETAILER), Error::<Test>::InvalidBottleStatus);
+	});
+}
+
+// Migrations
+
+/// Stores a bottle the way the first release did.
+fn put_v1_bottle(id: &[u8], manufacturer: u64, registered: u64) {
+	let bottle = migrations::v1::Bottle { id: id.to_vec(), manufacturer, registered };
+	put_storage_value(b"RegistrarModule", b"Bottles", &Blake2_128Concat::hash(&id.to_vec().encode()), bottle);
+	ManufacturerOf::<Test>::insert(id.to_vec(), manufacturer);
+}
+
+#[test]
+fn genesis_starts_on_the_latest_storage_version() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(RegistrarModule::storage_version(), Releases::V2_0_0);
+	});
+}
+
+#[test]
+fn migration_from_v1_upgrades_bottles() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::put(Releases::V1_0_0);
+		put_v1_bottle(b"bottle-1", MANUFACTURER, 7);
+		put_v1_bottle(b"bottle-2", STRANGER, 8);
+		assert_ok!(migrations::pre_migrate::<Test>());
+
+		RegistrarModule::on_runtime_upgrade();
+
+		assert_ok!(migrations::post_migrate::<Test>());
+		assert_eq!(RegistrarModule::storage_version(), Releases::V2_0_0);
+
+		let bottle = RegistrarModule::bottle_by_id(b"bottle-1".to_vec()).expect("bottle should be migrated");
+		assert_eq!(bottle.manufacturer, MANUFACTURER);
+		assert_eq!(bottle.owner(), &MANUFACTURER);
+		assert_eq!(bottle.status(), &BottleStatus::Manufactured);
+		assert_eq!(bottle.registered, 7);
+		assert_ok!(RegistrarModule::check_bottle_owner(b"bottle-2", &STRANGER));
+
+		let mut manufactured = RegistrarModule::bottles_with_status(BottleStatus::Manufactured);
+		manufactured.sort();
+		assert_eq!(manufactured, vec![b"bottle-1".to_vec(), b"bottle-2".to_vec()]);
+	});
+}
+
+#[test]
+fn migration_runs_once() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), b"bottle-1".to_vec(), vec![]));
+		assert_ok!(RegistrarModule::update_bottle_status(&b"bottle-1".to_vec(), BottleStatus::ShipmentRegistered));
+
+		RegistrarModule::on_runtime_upgrade();
+
+		assert_ok!(migrations::post_migrate::<Test>());
+		assert_eq!(
+			RegistrarModule::bottle_by_id(b"bottle-1".to_vec()).unwrap().status(),
+			&BottleStatus::ShipmentRegistered
+		);
+	});
+}
+
+#[test]
+fn restore_bottle_moves_it_in_the_status_index() {
+	new_test_ext().execute_with(|| {
+		register_all_members();
+		assert_ok!(RegistrarModule::register_bottle(Origin::signed(MANUFACTURER), b"bottle-1".to_vec(), vec![]));
+
+		migrations::restore_bottle::<Test>(&b"bottle-1".to_vec(), CUSTOMER, BottleStatus::SoldToCustomer);
+
+		assert_ok!(RegistrarModule::check_bottle_owner(b"bottle-1", &CUSTOMER));
+		assert!(RegistrarModule::bottles_with_status(BottleStatus::Manufactured).is_empty());
+		assert_eq!(RegistrarModule::bottles_with_status(BottleStatus::SoldToCustomer), vec![b"bottle-1".to_vec()]);
+		assert_ok!(migrations::post_migrate::<Test>());
+	});
+}
+
+// Genesis
+
+#[test]
+fn genesis_registers_members_and_bottles() {
+	test_ext_with(GenesisConfig::<Test> {
+		members: vec![(MemberType::Manufacturer, MANUFACTURER), (MemberType::Carrier, CARRIER)],
+		bottles: vec![(MANUFACTURER, b"bottle-1".to_vec()), (MANUFACTURER, b"bottle-2".to_vec())],
+	}).execute_with(|| {
+		assert_eq!(RegistrarModule::member_type_of(&MANUFACTURER), Some(MemberType::Manufacturer));
+		assert_eq!(RegistrarModule::member_type_of(&CARRIER), Some(MemberType::Carrier));
+		assert_eq!(
+			RegistrarModule::bottles_of_manufacturer(MANUFACTURER),
+			vec![b"bottle-1".to_vec(), b"bottle-2".to_vec()]
+		);
+		assert_ok!(RegistrarModule::check_bottle_owner(b"bottle-2", &MANUFACTURER));
+		assert_eq!(RegistrarModule::bottles_with_status(BottleStatus::Manufactured).len(), 2);
+	});
+}
+
+#[test]
+#[should_panic(expected = "genesis bottle is valid")]
+fn genesis_bottle_of_non_manufacturer_panics() {
+	test_ext_with(GenesisConfig::<Test> {
+		members: vec![(MemberType::Carrier, CARRIER)],
+		bottles: vec![(CARRIER, b"bottle-1".to_vec())],
+	});
+}
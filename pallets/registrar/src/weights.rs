@@ -0,0 +1,131 @@
+//! Weights for the registrar pallet.
+//!
+//! The values below are initial estimates. Regenerate them on reference hardware with:
+//!
+//! ./target/release/provenance-substrate benchmark --chain dev --execution wasm --wasm-execution compiled
+//!     --pallet registrar --extrinsic '*' --steps 50 --repeat 20 --output ./pallets/registrar/src/weights.rs
+
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight as DbWeight}};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for the registrar pallet.
+pub trait WeightInfo {
+	fn register_manufacturer(m: u32, ) -> Weight;
+	fn register_carrier(m: u32, ) -> Weight;
+	fn register_retailer(m: u32, ) -> Weight;
+	fn register_customer(m: u32, ) -> Weight;
+	fn register_distributor(m: u32, ) -> Weight;
+	fn register_warehouse(m: u32, ) -> Weight;
+	fn register_customs(m: u32, ) -> Weight;
+	fn register_bottle(m: u32, s: u32, ) -> Weight;
+}
+
+/// Weights for the registrar pallet using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: crate::SystemConfig> WeightInfo for SubstrateWeight<T> {
+	fn register_manufacturer(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn register_carrier(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn register_retailer(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn register_customer(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn register_distributor(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn register_warehouse(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn register_customs(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	fn register_bottle(m: u32, s: u32, ) -> Weight {
+		(45_000_000 as Weight)
+			.saturating_add((120_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add((2_000 as Weight).saturating_mul(s as Weight))
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(5 as Weight))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	fn register_manufacturer(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_carrier(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_retailer(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_customer(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_distributor(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_warehouse(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_customs(m: u32, ) -> Weight {
+		(32_000_000 as Weight)
+			.saturating_add((310_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_bottle(m: u32, s: u32, ) -> Weight {
+		(45_000_000 as Weight)
+			.saturating_add((120_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add((2_000 as Weight).saturating_mul(s as Weight))
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(5 as Weight))
+	}
+}
@@ -4,7 +4,7 @@ use sc_service::ChainType;
 use serde::{Deserialize, Serialize};
 use sp_core::{sr25519, Pair, Public};
 use sp_runtime::traits::{IdentifyAccount, Verify};
-use parachain_runtime::{AccountId, Signature};
+use parachain_runtime::{AccountId, Signature, registrar::MemberType};
 
 /// Specialized `ChainSpec` for the normal parachain runtime.
 pub type ChainSpec = sc_service::GenericChainSpec<parachain_runtime::GenesisConfig, Extensions>;
@@ -43,6 +43,17 @@ where
 	AccountPublic::from(get_from_seed::<TPublic>(seed)).into_account()
 }
 
+/// Demo members of the supply chain: Alice makes the bottles, Bob carries them, Charlie
+/// sells them and Dave buys them.
+pub fn provenance_members() -> Vec<(MemberType, AccountId)> {
+	vec![
+		(MemberType::Manufacturer, get_account_id_from_seed::<sr25519::Public>("Alice")),
+		(MemberType::Carrier, get_account_id_from_seed::<sr25519::Public>("Bob")),
+		(MemberType::Retailer, get_account_id_from_seed::<sr25519::Public>("Charlie")),
+		(MemberType::Customer, get_account_id_from_seed::<sr25519::Public>("Dave")),
+	]
+}
+
 pub fn development_config(id: ParaId) -> ChainSpec {
 	ChainSpec::from_genesis(
 		// Name
@@ -56,9 +67,12 @@ pub fn development_config(id: ParaId) -> ChainSpec {
 				vec![
 					get_account_id_from_seed::<sr25519::Public>("Alice"),
 					get_account_id_from_seed::<sr25519::Public>("Bob"),
+					get_account_id_from_seed::<sr25519::Public>("Charlie"),
+					get_account_id_from_seed::<sr25519::Public>("Dave"),
 					get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
 				],
+				provenance_members(),
 				id,
 			)
 		},
@@ -97,6 +111,7 @@ pub fn local_testnet_config(id: ParaId) -> ChainSpec {
 					get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
 				],
+				provenance_members(),
 				id,
 			)
 		},
@@ -114,8 +129,11 @@ pub fn local_testnet_config(id: ParaId) -> ChainSpec {
 fn testnet_genesis(
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
+	members: Vec<(MemberType, AccountId)>,
 	id: ParaId,
 ) -> parachain_runtime::GenesisConfig {
+	let (bottles, shipments) = provenance_demo(&members);
+
 	parachain_runtime::GenesisConfig {
 		frame_system: Some(parachain_runtime::SystemConfig {
 			code: parachain_runtime::WASM_BINARY
@@ -132,5 +150,37 @@ fn testnet_genesis(
 		}),
 		pallet_sudo: Some(parachain_runtime::SudoConfig { key: root_key }),
 		parachain_info: Some(parachain_runtime::ParachainInfoConfig { parachain_id: id }),
+		registrar: Some(parachain_runtime::RegistrarModuleConfig { members, bottles }),
+		bottle_tracking: Some(parachain_runtime::BottleTrackingConfig { shipments }),
 	}
 }
+
+/// A few bottles of the first manufacturer and a shipment of them to the first retailer
+/// waiting for the first carrier, so that the demo starts midway through the supply chain.
+fn provenance_demo(
+	members: &[(MemberType, AccountId)],
+) -> (Vec<(AccountId, Vec<u8>)>, Vec<(Vec<u8>, AccountId, AccountId, AccountId, Vec<Vec<u8>>)>) {
+	let first = |member_type: MemberType| {
+		members
+			.iter()
+			.find(|(t, _)| *t == member_type)
+			.map(|(_, account)| account.clone())
+	};
+
+	let manufacturer = match first(MemberType::Manufacturer) {
+		Some(manufacturer) => manufacturer,
+		None => return (vec![], vec![]),
+	};
+
+	let ids: Vec<Vec<u8>> = (1..=3).map(|n| format!("bottle-{}", n).into_bytes()).collect();
+	let bottles = ids.iter().map(|id| (manufacturer.clone(), id.clone())).collect();
+
+	let shipments = match (first(MemberType::Carrier), first(MemberType::Retailer)) {
+		(Some(carrier), Some(retailer)) => {
+			vec![(b"shipment-1".to_vec(), manufacturer, carrier, retailer, ids[..2].to_vec())]
+		}
+		_ => vec![],
+	};
+
+	(bottles, shipments)
+}
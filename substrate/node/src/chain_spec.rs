@@ -1,7 +1,8 @@
 use sp_core::{Pair, Public, sr25519};
 use node_template_runtime::{
 	AccountId, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig,
-	SudoConfig, SystemConfig, WASM_BINARY, Signature
+	SudoConfig, SystemConfig, RegistrarModuleConfig, BottleTrackingConfig, WASM_BINARY, Signature,
+	registrar::MemberType,
 };
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_finality_grandpa::AuthorityId as GrandpaId;
@@ -30,6 +31,17 @@ pub fn get_account_id_from_seed<TPublic: Public>(seed: &str) -> AccountId where
 	AccountPublic::from(get_from_seed::<TPublic>(seed)).into_account()
 }
 
+/// Demo members of the supply chain: Alice makes the bottles, Bob carries them, Charlie
+/// sells them and Dave buys them.
+pub fn provenance_members() -> Vec<(MemberType, AccountId)> {
+	vec![
+		(MemberType::Manufacturer, get_account_id_from_seed::<sr25519::Public>("Alice")),
+		(MemberType::Carrier, get_account_id_from_seed::<sr25519::Public>("Bob")),
+		(MemberType::Retailer, get_account_id_from_seed::<sr25519::Public>("Charlie")),
+		(MemberType::Customer, get_account_id_from_seed::<sr25519::Public>("Dave")),
+	]
+}
+
 /// Generate an Aura authority key.
 pub fn authority_keys_from_seed(s: &str) -> (AuraId, GrandpaId) {
 	(
@@ -59,9 +71,13 @@ pub fn development_config() -> Result<ChainSpec, String> {
 			vec![
 				get_account_id_from_seed::<sr25519::Public>("Alice"),
 				get_account_id_from_seed::<sr25519::Public>("Bob"),
+				get_account_id_from_seed::<sr25519::Public>("Charlie"),
+				get_account_id_from_seed::<sr25519::Public>("Dave"),
 				get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
 				get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
 			],
+			// Supply chain members
+			provenance_members(),
 			true,
 		),
 		// Bootnodes
@@ -110,6 +126,8 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 				get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
 				get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
 			],
+			// Supply chain members
+			provenance_members(),
 			true,
 		),
 		// Bootnodes
@@ -131,8 +149,11 @@ fn testnet_genesis(
 	initial_authorities: Vec<(AuraId, GrandpaId)>,
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
+	members: Vec<(MemberType, AccountId)>,
 	_enable_println: bool,
 ) -> GenesisConfig {
+	let (bottles, shipments) = provenance_demo(&members);
+
 	GenesisConfig {
 		frame_system: Some(SystemConfig {
 			// Add Wasm runtime to storage.
@@ -153,5 +174,38 @@ fn testnet_genesis(
 			// Assign network admin rights.
 			key: root_key,
 		}),
+		registrar: Some(RegistrarModuleConfig {
+			members,
+			bottles,
+		}),
+		bottle_tracking: Some(BottleTrackingConfig {
+			shipments,
+		}),
 	}
 }
+
+/// A few bottles of the first manufacturer and a shipment of them to the first retailer
+/// waiting for the first carrier, so that the demo starts midway through the supply chain.
+fn provenance_demo(
+	members: &[(MemberType, AccountId)],
+) -> (Vec<(AccountId, Vec<u8>)>, Vec<(Vec<u8>, AccountId, AccountId, AccountId, Vec<Vec<u8>>)>) {
+	let first = |member_type: MemberType| members.iter()
+		.find(|(t, _)| *t == member_type)
+		.map(|(_, account)| account.clone());
+
+	let manufacturer = match first(MemberType::Manufacturer) {
+		Some(manufacturer) => manufacturer,
+		None => return (vec![], vec![]),
+	};
+
+	let ids: Vec<Vec<u8>> = (1..=3).map(|n| format!("bottle-{}", n).into_bytes()).collect();
+	let bottles = ids.iter().map(|id| (manufacturer.clone(), id.clone())).collect();
+
+	let shipments = match (first(MemberType::Carrier), first(MemberType::Retailer)) {
+		(Some(carrier), Some(retailer)) =>
+			vec![(b"shipment-1".to_vec(), manufacturer, carrier, retailer, ids[..2].to_vec())],
+		_ => vec![],
+	};
+
+	(bottles, shipments)
+}
@@ -8,6 +8,10 @@ pub struct Cli {
 
 	#[structopt(flatten)]
 	pub run: RunCmd,
+
+	#[cfg(feature = "graphql")]
+	#[structopt(flatten)]
+	pub graphql: crate::graphql::GraphQlParams,
 }
 
 #[derive(Debug, StructOpt)]
@@ -33,6 +37,12 @@ pub enum Subcommand {
 	/// Revert the chain to a previous state.
 	Revert(sc_cli::RevertCmd),
 
+	/// Register bottles and shipments from a CSV or JSON file on a running node.
+	ImportProvenance(crate::import::ImportProvenanceCmd),
+
+	/// Export a signed certificate of the provenance of a bottle.
+	ExportProvenance(crate::export::ExportProvenanceCmd),
+
 	/// The custom benchmark subcommmand benchmarking runtime pallets.
 	#[structopt(name = "benchmark", about = "Benchmark runtime pallets.")]
 	Benchmark(frame_benchmarking_cli::BenchmarkCmd),
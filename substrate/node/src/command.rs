@@ -114,6 +114,14 @@ pub fn run() -> sc_cli::Result<()> {
 				Ok((cmd.run(client, backend), task_manager))
 			})
 		},
+		Some(Subcommand::ImportProvenance(cmd)) => cmd.run(),
+		Some(Subcommand::ExportProvenance(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let PartialComponents { client, .. } = service::new_partial(&config)?;
+				cmd.run(client)
+			})
+		},
 		Some(Subcommand::Benchmark(cmd)) => {
 			if cfg!(feature = "runtime-benchmarks") {
 				let runner = cli.create_runner(cmd)?;
@@ -128,7 +136,16 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(&cli.run)?;
 			runner.run_node_until_exit(|config| match config.role {
 				Role::Light => service::new_light(config),
-				_ => service::new_full(config),
+				_ => {
+					#[cfg(feature = "graphql")]
+					let graphql = crate::graphql::server(&cli.graphql, &config)?;
+					let task_manager = service::new_full(config)?;
+					#[cfg(feature = "graphql")]
+					if let Some(server) = graphql {
+						task_manager.spawn_handle().spawn("provenance-graphql", server);
+					}
+					Ok(task_manager)
+				},
 			})
 		}
 	}
@@ -0,0 +1,354 @@
+//! The `export-provenance` subcommand, certifying where a bottle has been.
+//!
+//! The certificate is read from the state of a block of the local database. Every claim in
+//! it comes with the storage entry backing it and a proof of that entry against the state
+//! root of the block, and the whole certificate is signed by the exporting account.
+
+use std::{fs::File, io::Write, path::PathBuf, str::FromStr, sync::Arc};
+
+use codec::Decode;
+use sc_cli::{CliConfiguration, SharedParams};
+use sc_client_api::{Backend, ProofProvider, StorageProvider};
+use serde::Serialize;
+use sp_blockchain::HeaderBackend;
+use sp_core::{crypto::{Pair as _, Ss58Codec}, storage::StorageKey, Bytes};
+use sp_runtime::{generic::BlockId, traits::Header as _};
+use frame_support::StorageMap;
+use structopt::StructOpt;
+
+use crate::signer::SignerParams;
+use node_template_runtime::{
+	bottle_tracking::{self, types::{Shipment, ShipmentId, ShipmentStatus}},
+	opaque::Block, registrar, AccountId, BlockNumber, Hash, Runtime,
+};
+
+type Moment = u64;
+
+/// The `export-provenance` command.
+#[derive(Debug, StructOpt)]
+pub struct ExportProvenanceCmd {
+	/// Id of the bottle to certify.
+	#[structopt(long)]
+	pub bottle: String,
+
+	/// Hash of the block to read the state at, the best block by default.
+	#[structopt(long)]
+	pub at: Option<String>,
+
+	/// File to write the signed JSON certificate to, the standard output by default.
+	#[structopt(long, parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Also render the certificate as HTML into this file.
+	#[structopt(long, parse(from_os_str))]
+	pub html: Option<PathBuf>,
+
+	#[structopt(flatten)]
+	pub signer: SignerParams,
+
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// A storage entry and the proof of it against the state root of the certified block.
+#[derive(Debug, Serialize)]
+pub struct StorageClaim {
+	pub key: Bytes,
+	pub value: Bytes,
+	pub proof: Vec<Bytes>,
+}
+
+/// The bottle as the registrar has it.
+#[derive(Debug, Serialize)]
+pub struct BottleClaim {
+	pub id: String,
+	pub manufacturer: String,
+	pub owner: String,
+	pub status: String,
+	pub registered: Moment,
+	pub storage: StorageClaim,
+}
+
+/// A shipment the bottle was on, in the order the bottle travelled.
+#[derive(Debug, Serialize)]
+pub struct CustodyHop {
+	pub shipment: String,
+	pub sender: String,
+	pub carrier: String,
+	pub receiver: String,
+	pub status: String,
+	pub registered: Moment,
+	pub delivered: Option<Moment>,
+	pub storage: StorageClaim,
+}
+
+/// The customer the bottle was sold to.
+#[derive(Debug, Serialize)]
+pub struct SaleClaim {
+	pub customer: String,
+	pub storage: StorageClaim,
+}
+
+/// Provenance of a bottle at a block.
+#[derive(Debug, Serialize)]
+pub struct Certificate {
+	pub genesis_hash: Hash,
+	pub block_number: BlockNumber,
+	pub block_hash: Hash,
+	pub state_root: Hash,
+	pub bottle: BottleClaim,
+	pub custody: Vec<CustodyHop>,
+	pub sale: Option<SaleClaim>,
+}
+
+/// The certificate and the signature of its compact JSON encoding.
+#[derive(Debug, Serialize)]
+pub struct SignedCertificate {
+	pub certificate: Certificate,
+	pub signer: String,
+	pub signature: Bytes,
+}
+
+impl CliConfiguration for ExportProvenanceCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+}
+
+impl ExportProvenanceCmd {
+	/// Certifies the provenance of the bottle and writes out the certificate.
+	pub fn run<C, B>(&self, client: Arc<C>) -> sc_cli::Result<()>
+	where
+		B: Backend<Block>,
+		C: StorageProvider<Block, B> + ProofProvider<Block> + HeaderBackend<Block>,
+	{
+		let pair = self.signer.pair()?;
+		let at = match &self.at {
+			Some(hash) => Hash::from_str(hash.trim_start_matches("0x"))
+				.map_err(|e| format!("invalid block hash `{}`: {:?}", hash, e))?,
+			None => client.info().best_hash,
+		};
+
+		let certificate = certify::<B, C>(&State { client: &*client, at: BlockId::Hash(at) }, self.bottle.as_bytes())?;
+
+		if let Some(path) = &self.html {
+			write(path, render_html(&certificate).as_bytes())?;
+		}
+
+		let payload = serde_json::to_vec(&certificate).map_err(|e| e.to_string())?;
+		let signed = SignedCertificate {
+			signer: pair.public().to_ss58check(),
+			signature: Bytes(pair.sign(&payload).as_ref().to_vec()),
+			certificate,
+		};
+		let json = serde_json::to_string_pretty(&signed).map_err(|e| e.to_string())?;
+
+		match &self.output {
+			Some(path) => write(path, json.as_bytes())?,
+			None => println!("{}", json),
+		}
+		Ok(())
+	}
+}
+
+fn write(path: &PathBuf, contents: &[u8]) -> Result<(), String> {
+	File::create(path)
+		.and_then(|mut file| file.write_all(contents))
+		.map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// State of the certified block.
+struct State<'a, C> {
+	client: &'a C,
+	at: BlockId<Block>,
+}
+
+impl<'a, C> State<'a, C> {
+	/// Reads and decodes a storage entry along with its proof.
+	fn get<B, T>(&self, key: Vec<u8>) -> Result<Option<(T, StorageClaim)>, String>
+	where
+		B: Backend<Block>,
+		C: StorageProvider<Block, B> + ProofProvider<Block>,
+		T: Decode,
+	{
+		let data = match self.client.storage(&self.at, &StorageKey(key.clone())).map_err(|e| e.to_string())? {
+			Some(data) => data.0,
+			None => return Ok(None),
+		};
+		let value = T::decode(&mut &data[..]).map_err(|e| format!("cannot decode storage: {}", e))?;
+		let proof = self.client
+			.read_proof(&self.at, &mut std::iter::once(&key[..]))
+			.map_err(|e| e.to_string())?;
+
+		Ok(Some((value, StorageClaim {
+			key: Bytes(key),
+			value: Bytes(data),
+			proof: proof.iter_nodes().map(Bytes).collect(),
+		})))
+	}
+}
+
+fn certify<B, C>(state: &State<C>, bottle: &[u8]) -> Result<Certificate, String>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B> + ProofProvider<Block> + HeaderBackend<Block>,
+{
+	let header = state.client.header(state.at).map_err(|e| e.to_string())?
+		.ok_or("unknown block")?;
+	let genesis_hash = state.client.hash(0).map_err(|e| e.to_string())?
+		.ok_or("unknown genesis block")?;
+
+	let (registered, storage) = state
+		.get::<B, registrar::Bottle<AccountId, Moment>>(registrar::Bottles::<Runtime>::hashed_key_for(bottle))?
+		.ok_or_else(|| format!("bottle {} is not registered", String::from_utf8_lossy(bottle)))?;
+
+	let sale = state
+		.get::<B, AccountId>(bottle_tracking::BottleSoldTo::<Runtime>::hashed_key_for(bottle))?
+		.map(|(customer, storage)| SaleClaim { customer: customer.to_ss58check(), storage });
+
+	Ok(Certificate {
+		genesis_hash,
+		block_number: header.number,
+		block_hash: header.hash(),
+		state_root: header.state_root,
+		bottle: BottleClaim {
+			id: String::from_utf8_lossy(registered.id()).into(),
+			manufacturer: registered.manufacturer().to_ss58check(),
+			owner: registered.owner().to_ss58check(),
+			status: format!("{:?}", registered.status()),
+			registered: *registered.registered(),
+			storage,
+		},
+		custody: custody::<B, C>(state, bottle)?,
+		sale,
+	})
+}
+
+/// Shipments of the bottle, walking back from its last one through the shipments that
+/// delivered it to their sender.
+fn custody<B, C>(state: &State<C>, bottle: &[u8]) -> Result<Vec<CustodyHop>, String>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B> + ProofProvider<Block>,
+{
+	let shipment = |id: &ShipmentId| {
+		state.get::<B, Shipment<AccountId, Moment, BlockNumber>>(bottle_tracking::Shipments::<Runtime>::hashed_key_for(id))
+	};
+
+	let mut next = match state.get::<B, ShipmentId>(bottle_tracking::BottleOfShipment::hashed_key_for(bottle))? {
+		Some((id, _)) => shipment(&id)?,
+		None => None,
+	};
+
+	let mut hops = Vec::new();
+	let mut seen = Vec::new();
+	while let Some((current, storage)) = next.take() {
+		let received_by_sender = state
+			.get::<B, Vec<ShipmentId>>(bottle_tracking::ShipmentsOfReceiver::<Runtime>::hashed_key_for(&current.sender))?
+			.map(|(ids, _)| ids)
+			.unwrap_or_default();
+
+		for id in received_by_sender.iter().filter(|id| **id != current.id && !seen.contains(*id)) {
+			if let Some((previous, storage)) = shipment(id)? {
+				let delivered_it = matches!(previous.status, ShipmentStatus::Delivered | ShipmentStatus::Stored)
+					&& previous.bottles.iter().any(|b| b == bottle)
+					&& !previous.missing.iter().any(|b| b == bottle)
+					&& previous.registered <= current.registered;
+				// Shipments registered in the same block share a timestamp, and the receiver's
+				// shipments are kept in the order they were registered, so the last one wins.
+				let later = next.as_ref().map_or(true, |(found, _)| previous.registered >= found.registered);
+				if delivered_it && later {
+					next = Some((previous, storage));
+				}
+			}
+		}
+
+		seen.push(current.id.clone());
+		hops.push(CustodyHop {
+			shipment: String::from_utf8_lossy(&current.id).into(),
+			sender: current.sender.to_ss58check(),
+			carrier: current.carrier.to_ss58check(),
+			receiver: current.receiver.to_ss58check(),
+			status: format!("{:?}", current.status),
+			registered: current.registered,
+			delivered: current.delivered,
+			storage,
+		});
+	}
+
+	hops.reverse();
+	Ok(hops)
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Renders the certificate as a standalone HTML page, ready to print.
+pub fn render_html(certificate: &Certificate) -> String {
+	let bottle = &certificate.bottle;
+	let mut html = format!(
+		"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+		<title>Certificate of authenticity: {id}</title>\n</head>\n<body>\n\
+		<h1>Certificate of authenticity</h1>\n\
+		<p>Bottle <strong>{id}</strong>, manufactured by {manufacturer}, is {status} and owned by {owner}.</p>\n\
+		<p>Certified at block #{number} ({hash}).</p>\n",
+		id = escape(&bottle.id),
+		manufacturer = escape(&bottle.manufacturer),
+		status = escape(&bottle.status),
+		owner = escape(&bottle.owner),
+		number = certificate.block_number,
+		hash = escape(&format!("{:?}", certificate.block_hash)),
+	);
+
+	html.push_str("<h2>Custody</h2>\n<table>\n<tr><th>Shipment</th><th>From</th><th>Carrier</th><th>To</th><th>Status</th></tr>\n");
+	for hop in &certificate.custody {
+		html.push_str(&format!(
+			"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+			escape(&hop.shipment), escape(&hop.sender), escape(&hop.carrier), escape(&hop.receiver), escape(&hop.status),
+		));
+	}
+	html.push_str("</table>\n");
+
+	if let Some(sale) = &certificate.sale {
+		html.push_str(&format!("<p>Sold to {}.</p>\n", escape(&sale.customer)));
+	}
+
+	html.push_str("</body>\n</html>\n");
+	html
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn claim() -> StorageClaim {
+		StorageClaim { key: Bytes(vec![]), value: Bytes(vec![]), proof: vec![] }
+	}
+
+	#[test]
+	fn html_escapes_claims() {
+		let certificate = Certificate {
+			genesis_hash: Hash::zero(),
+			block_number: 1,
+			block_hash: Hash::zero(),
+			state_root: Hash::zero(),
+			bottle: BottleClaim {
+				id: "<script>".into(),
+				manufacturer: "alice".into(),
+				owner: "dave".into(),
+				status: "SoldToCustomer".into(),
+				registered: 0,
+				storage: claim(),
+			},
+			custody: vec![],
+			sale: Some(SaleClaim { customer: "dave".into(), storage: claim() }),
+		};
+
+		let html = render_html(&certificate);
+
+		assert!(html.contains("&lt;script&gt;"));
+		assert!(!html.contains("<script>"));
+		assert!(html.contains("Sold to dave."));
+	}
+}
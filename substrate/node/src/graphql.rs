@@ -0,0 +1,565 @@
+//! GraphQL server over the provenance index, built with the `graphql` feature.
+//!
+//! The index follows the block import stream of the node (see `indexer`), so the server only
+//! reads it, through a connection of its own.
+
+use std::{
+	convert::Infallible,
+	future::Future,
+	net::{Ipv4Addr, SocketAddr},
+	sync::Mutex,
+};
+
+use async_graphql::{
+	http::{playground_source, GraphQLPlaygroundConfig},
+	Context, EmptyMutation, EmptySubscription, Object, Result, Schema, SimpleObject,
+};
+use rusqlite::{Connection, Row, ToSql};
+use sc_service::Configuration;
+use structopt::StructOpt;
+use warp::{http::Response as HttpResponse, Filter};
+
+use crate::indexer::{self, store};
+
+/// Most items a page holds.
+const MAX_PAGE: i64 = 500;
+const DEFAULT_PAGE: i64 = 100;
+
+/// Views of the index the queries read. The current status of a bottle or shipment is the
+/// status of its latest custody event carrying one, as is the owner of a bottle.
+const VIEWS: &str = "
+	CREATE TEMP VIEW IF NOT EXISTS custody AS
+		SELECT c.*, k.number AS block_number, k.timestamp AS timestamp
+		FROM custody_events c JOIN blocks k ON k.hash = c.block_hash;
+	CREATE TEMP VIEW IF NOT EXISTS bottle_state AS
+		SELECT b.id, b.manufacturer, k.number AS block_number, k.timestamp AS timestamp,
+			(SELECT status FROM custody WHERE bottle_id = b.id AND status IS NOT NULL
+				ORDER BY block_number DESC, event_index DESC LIMIT 1) AS status,
+			(SELECT account FROM custody WHERE bottle_id = b.id AND shipment_id IS NULL AND account IS NOT NULL
+				ORDER BY block_number DESC, event_index DESC LIMIT 1) AS owner
+		FROM bottles b JOIN blocks k ON k.hash = b.block_hash;
+	CREATE TEMP VIEW IF NOT EXISTS shipment_state AS
+		SELECT s.id, s.sender, s.carrier, s.receiver, s.customs, k.number AS block_number, k.timestamp AS timestamp,
+			(SELECT status FROM custody WHERE shipment_id = s.id AND bottle_id IS NULL AND status IS NOT NULL
+				ORDER BY block_number DESC, event_index DESC LIMIT 1) AS status
+		FROM shipments s JOIN blocks k ON k.hash = s.block_hash;
+";
+
+#[derive(Debug, StructOpt)]
+pub struct GraphQlParams {
+	/// Specify the GraphQL server TCP port.
+	#[structopt(long = "graphql-port", value_name = "PORT", default_value = "9955")]
+	pub graphql_port: u16,
+
+	/// Listen to all GraphQL interfaces.
+	///
+	/// Default is local. The index holds nothing the chain does not, but queries over it can
+	/// be expensive.
+	#[structopt(long = "graphql-external")]
+	pub graphql_external: bool,
+}
+
+/// The index, as the resolvers share it.
+struct Index(Mutex<Connection>);
+
+impl Index {
+	fn query<T>(&self, select: Select, order: &str, page: Page, row: impl FnMut(&Row) -> rusqlite::Result<T>) -> Result<Vec<T>> {
+		let db = self.0.lock().map_err(|_| "the index connection is poisoned")?;
+		Ok(select.rows(&db, order, page, row)?)
+	}
+}
+
+fn index<'a>(ctx: &Context<'a>) -> Result<&'a Index> {
+	ctx.data::<Index>()
+}
+
+/// A `SELECT` over a table or view, narrowed by the filters given.
+struct Select {
+	columns: &'static str,
+	from: &'static str,
+	conditions: Vec<String>,
+	params: Vec<Box<dyn ToSql>>,
+}
+
+impl Select {
+	fn new(columns: &'static str, from: &'static str) -> Self {
+		Select { columns, from, conditions: Vec::new(), params: Vec::new() }
+	}
+
+	/// Adds the condition, holding a `?` for the value, if the value is given.
+	fn when<T: ToSql + 'static>(mut self, condition: String, value: Option<T>) -> Self {
+		if let Some(value) = value {
+			self.conditions.push(condition);
+			self.params.push(Box::new(value));
+		}
+		self
+	}
+
+	fn eq<T: ToSql + 'static>(self, column: &str, value: Option<T>) -> Self {
+		self.when(format!("{} = ?", column), value)
+	}
+
+	/// Keeps the rows with a timestamp from `from` included to `to` excluded.
+	fn between(self, column: &str, from: Option<i64>, to: Option<i64>) -> Self {
+		self.when(format!("{} >= ?", column), from).when(format!("{} < ?", column), to)
+	}
+
+	fn rows<T>(
+		self,
+		db: &Connection,
+		order: &str,
+		page: Page,
+		row: impl FnMut(&Row) -> rusqlite::Result<T>,
+	) -> rusqlite::Result<Vec<T>> {
+		let mut sql = format!("SELECT {} FROM {}", self.columns, self.from);
+		if !self.conditions.is_empty() {
+			sql += &format!(" WHERE {}", self.conditions.join(" AND "));
+		}
+		sql += &format!(" ORDER BY {} LIMIT {} OFFSET {}", order, page.limit, page.offset);
+
+		let mut statement = db.prepare(&sql)?;
+		let rows = statement.query_map(&self.params, row)?;
+		rows.collect()
+	}
+}
+
+/// Slice of the results a query returns.
+#[derive(Clone, Copy)]
+struct Page {
+	offset: i64,
+	limit: i64,
+}
+
+impl Page {
+	fn new(offset: Option<i32>, limit: Option<i32>) -> Self {
+		Page {
+			offset: offset.map_or(0, |offset| offset.max(0) as i64),
+			limit: limit.map_or(DEFAULT_PAGE, |limit| (limit.max(0) as i64).min(MAX_PAGE)),
+		}
+	}
+
+	fn all() -> Self {
+		Page { offset: 0, limit: -1 }
+	}
+}
+
+#[derive(SimpleObject)]
+pub struct Member {
+	account: String,
+	member_type: String,
+	/// Block the member was added in.
+	block_number: u32,
+}
+
+#[derive(SimpleObject)]
+pub struct CustodyEvent {
+	/// Name of the runtime event, e.g. `BottleStatusChanged`.
+	event: String,
+	bottle: Option<String>,
+	shipment: Option<String>,
+	account: Option<String>,
+	/// Status the event left the bottle or shipment in, if it changed it.
+	status: Option<String>,
+	block_number: u32,
+	/// Milliseconds since the Unix epoch, as the block records it.
+	timestamp: Option<i64>,
+}
+
+const CUSTODY_COLUMNS: &str = "event, bottle_id, shipment_id, account, status, block_number, timestamp";
+const CUSTODY_ORDER: &str = "block_number, event_index";
+
+fn custody_event(row: &Row) -> rusqlite::Result<CustodyEvent> {
+	Ok(CustodyEvent {
+		event: row.get(0)?,
+		bottle: row.get(1)?,
+		shipment: row.get(2)?,
+		account: row.get(3)?,
+		status: row.get(4)?,
+		block_number: row.get(5)?,
+		timestamp: row.get(6)?,
+	})
+}
+
+pub struct Bottle {
+	id: String,
+	manufacturer: String,
+	status: Option<String>,
+	owner: Option<String>,
+	block_number: u32,
+	timestamp: Option<i64>,
+}
+
+const BOTTLE_COLUMNS: &str = "id, manufacturer, status, owner, block_number, timestamp";
+
+fn bottle(row: &Row) -> rusqlite::Result<Bottle> {
+	Ok(Bottle {
+		id: row.get(0)?,
+		manufacturer: row.get(1)?,
+		status: row.get(2)?,
+		owner: row.get(3)?,
+		block_number: row.get(4)?,
+		timestamp: row.get(5)?,
+	})
+}
+
+#[Object]
+impl Bottle {
+	async fn id(&self) -> &str {
+		&self.id
+	}
+
+	async fn manufacturer(&self) -> &str {
+		&self.manufacturer
+	}
+
+	/// Current status, unknown for bottles of the genesis state that have not moved since.
+	async fn status(&self) -> Option<&str> {
+		self.status.as_deref()
+	}
+
+	/// Current owner, unknown for bottles of the genesis state that have not moved since.
+	async fn owner(&self) -> Option<&str> {
+		self.owner.as_deref()
+	}
+
+	/// Block the bottle was registered in.
+	async fn block_number(&self) -> u32 {
+		self.block_number
+	}
+
+	/// Milliseconds since the Unix epoch the bottle was registered at.
+	async fn timestamp(&self) -> Option<i64> {
+		self.timestamp
+	}
+
+	/// Steps of the bottle through the supply chain, oldest first.
+	async fn custody(&self, ctx: &Context<'_>) -> Result<Vec<CustodyEvent>> {
+		let select = Select::new(CUSTODY_COLUMNS, "custody").eq("bottle_id", Some(self.id.clone()));
+		index(ctx)?.query(select, CUSTODY_ORDER, Page::all(), custody_event)
+	}
+
+	/// Shipments the bottle was part of, oldest first.
+	async fn shipments(&self, ctx: &Context<'_>) -> Result<Vec<Shipment>> {
+		let select = Select::new(SHIPMENT_COLUMNS, "shipment_state").when(
+			"id IN (SELECT shipment_id FROM shipment_bottles WHERE bottle_id = ?)".into(),
+			Some(self.id.clone()),
+		);
+		index(ctx)?.query(select, "block_number, id", Page::all(), shipment)
+	}
+}
+
+pub struct Shipment {
+	id: String,
+	sender: String,
+	carrier: String,
+	receiver: String,
+	customs: Option<String>,
+	status: Option<String>,
+	block_number: u32,
+	timestamp: Option<i64>,
+}
+
+const SHIPMENT_COLUMNS: &str = "id, sender, carrier, receiver, customs, status, block_number, timestamp";
+
+fn shipment(row: &Row) -> rusqlite::Result<Shipment> {
+	Ok(Shipment {
+		id: row.get(0)?,
+		sender: row.get(1)?,
+		carrier: row.get(2)?,
+		receiver: row.get(3)?,
+		customs: row.get(4)?,
+		status: row.get(5)?,
+		block_number: row.get(6)?,
+		timestamp: row.get(7)?,
+	})
+}
+
+#[Object]
+impl Shipment {
+	async fn id(&self) -> &str {
+		&self.id
+	}
+
+	async fn sender(&self) -> &str {
+		&self.sender
+	}
+
+	async fn carrier(&self) -> &str {
+		&self.carrier
+	}
+
+	async fn receiver(&self) -> &str {
+		&self.receiver
+	}
+
+	async fn customs(&self) -> Option<&str> {
+		self.customs.as_deref()
+	}
+
+	/// Current status, unknown for shipments of the genesis state that have not moved since.
+	async fn status(&self) -> Option<&str> {
+		self.status.as_deref()
+	}
+
+	/// Block the shipment was registered in.
+	async fn block_number(&self) -> u32 {
+		self.block_number
+	}
+
+	/// Milliseconds since the Unix epoch the shipment was registered at.
+	async fn timestamp(&self) -> Option<i64> {
+		self.timestamp
+	}
+
+	async fn bottles(&self, ctx: &Context<'_>) -> Result<Vec<Bottle>> {
+		let select = Select::new(BOTTLE_COLUMNS, "bottle_state").when(
+			"id IN (SELECT bottle_id FROM shipment_bottles WHERE shipment_id = ?)".into(),
+			Some(self.id.clone()),
+		);
+		index(ctx)?.query(select, "id", Page::all(), bottle)
+	}
+
+	/// Steps of the shipment through the supply chain, oldest first.
+	async fn custody(&self, ctx: &Context<'_>) -> Result<Vec<CustodyEvent>> {
+		let select = Select::new(CUSTODY_COLUMNS, "custody").eq("shipment_id", Some(self.id.clone()));
+		index(ctx)?.query(select, CUSTODY_ORDER, Page::all(), custody_event)
+	}
+}
+
+pub struct Query;
+
+/// Accounts are SS58 addresses, statuses and member types the names of the runtime enums, and
+/// `from` and `to` milliseconds since the Unix epoch, `to` excluded. Pages hold 100 items
+/// unless `limit` says otherwise, and 500 at most.
+#[Object]
+impl Query {
+	/// Bottles in the order they were registered.
+	#[allow(clippy::too_many_arguments)]
+	async fn bottles(
+		&self,
+		ctx: &Context<'_>,
+		manufacturer: Option<String>,
+		owner: Option<String>,
+		status: Option<String>,
+		from: Option<i64>,
+		to: Option<i64>,
+		offset: Option<i32>,
+		limit: Option<i32>,
+	) -> Result<Vec<Bottle>> {
+		let select = Select::new(BOTTLE_COLUMNS, "bottle_state")
+			.eq("manufacturer", manufacturer)
+			.eq("owner", owner)
+			.eq("status", status)
+			.between("timestamp", from, to);
+		index(ctx)?.query(select, "block_number, id", Page::new(offset, limit), bottle)
+	}
+
+	async fn bottle(&self, ctx: &Context<'_>, id: String) -> Result<Option<Bottle>> {
+		let select = Select::new(BOTTLE_COLUMNS, "bottle_state").eq("id", Some(id));
+		Ok(index(ctx)?.query(select, "id", Page::new(None, Some(1)), bottle)?.pop())
+	}
+
+	/// Shipments in the order they were registered.
+	#[allow(clippy::too_many_arguments)]
+	async fn shipments(
+		&self,
+		ctx: &Context<'_>,
+		sender: Option<String>,
+		carrier: Option<String>,
+		receiver: Option<String>,
+		status: Option<String>,
+		from: Option<i64>,
+		to: Option<i64>,
+		offset: Option<i32>,
+		limit: Option<i32>,
+	) -> Result<Vec<Shipment>> {
+		let select = Select::new(SHIPMENT_COLUMNS, "shipment_state")
+			.eq("sender", sender)
+			.eq("carrier", carrier)
+			.eq("receiver", receiver)
+			.eq("status", status)
+			.between("timestamp", from, to);
+		index(ctx)?.query(select, "block_number, id", Page::new(offset, limit), shipment)
+	}
+
+	async fn shipment(&self, ctx: &Context<'_>, id: String) -> Result<Option<Shipment>> {
+		let select = Select::new(SHIPMENT_COLUMNS, "shipment_state").eq("id", Some(id));
+		Ok(index(ctx)?.query(select, "id", Page::new(None, Some(1)), shipment)?.pop())
+	}
+
+	/// Members in the order they were added.
+	async fn members(
+		&self,
+		ctx: &Context<'_>,
+		member_type: Option<String>,
+		offset: Option<i32>,
+		limit: Option<i32>,
+	) -> Result<Vec<Member>> {
+		let select = Select::new(
+			"m.account, m.member_type, k.number",
+			"members m JOIN blocks k ON k.hash = m.block_hash",
+		).eq("m.member_type", member_type);
+		index(ctx)?.query(select, "k.number, m.account", Page::new(offset, limit), |row| Ok(Member {
+			account: row.get(0)?,
+			member_type: row.get(1)?,
+			block_number: row.get(2)?,
+		}))
+	}
+
+	/// Custody events, oldest first.
+	#[allow(clippy::too_many_arguments)]
+	async fn custody_events(
+		&self,
+		ctx: &Context<'_>,
+		bottle: Option<String>,
+		shipment: Option<String>,
+		account: Option<String>,
+		from: Option<i64>,
+		to: Option<i64>,
+		offset: Option<i32>,
+		limit: Option<i32>,
+	) -> Result<Vec<CustodyEvent>> {
+		let select = Select::new(CUSTODY_COLUMNS, "custody")
+			.eq("bottle_id", bottle)
+			.eq("shipment_id", shipment)
+			.eq("account", account)
+			.between("timestamp", from, to);
+		index(ctx)?.query(select, CUSTODY_ORDER, Page::new(offset, limit), custody_event)
+	}
+}
+
+pub type ProvenanceSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+fn schema(db: Connection) -> ProvenanceSchema {
+	Schema::build(Query, EmptyMutation, EmptySubscription)
+		.data(Index(Mutex::new(db)))
+		.finish()
+}
+
+fn open(path: &std::path::Path) -> rusqlite::Result<Connection> {
+	let db = store::open(path)?;
+	db.execute_batch(VIEWS)?;
+	Ok(db)
+}
+
+/// Binds the server of a full node, answering queries at `/` and serving a playground to
+/// `GET /`. `None` if the node keeps no index.
+pub fn server(
+	params: &GraphQlParams,
+	config: &Configuration,
+) -> std::result::Result<Option<impl Future<Output = ()>>, sc_service::Error> {
+	let path = match indexer::index_path(config) {
+		Some(path) => path,
+		None => {
+			log::warn!("The GraphQL server needs the provenance index on disk, not serving.");
+			return Ok(None);
+		},
+	};
+	let db = open(&path).map_err(|e| sc_service::Error::Other(format!("cannot open provenance index: {}", e)))?;
+
+	let graphql = async_graphql_warp::graphql(schema(db)).and_then(
+		|(schema, request): (ProvenanceSchema, async_graphql::Request)| async move {
+			Ok::<_, Infallible>(async_graphql_warp::Response::from(schema.execute(request).await))
+		},
+	);
+	let playground = warp::path::end().and(warp::get()).map(|| {
+		HttpResponse::builder()
+			.header("content-type", "text/html")
+			.body(playground_source(GraphQLPlaygroundConfig::new("/")))
+	});
+
+	let ip = if params.graphql_external { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+	let (address, server) = warp::serve(playground.or(graphql))
+		.try_bind_ephemeral(SocketAddr::new(ip.into(), params.graphql_port))
+		.map_err(|e| sc_service::Error::Other(format!("cannot bind the GraphQL server: {}", e)))?;
+	log::info!("GraphQL server started at http://{}", address);
+
+	Ok(Some(server))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::{AccountId32, Ss58Codec};
+
+	fn hash(n: u8) -> node_template_runtime::Hash {
+		node_template_runtime::Hash::repeat_byte(n)
+	}
+
+	/// Two bottles of different manufacturers in blocks 1 and 2, the first one sold in block 3.
+	fn db() -> Connection {
+		let db = Connection::open_in_memory().unwrap();
+		db.execute_batch(indexer::SCHEMA).unwrap();
+		db.execute_batch(VIEWS).unwrap();
+
+		let (first, second) = (AccountId32::new([1; 32]), AccountId32::new([2; 32]));
+		let customer = AccountId32::new([3; 32]);
+		store::add_block(&db, &hash(0), 0, &Default::default(), None, true).unwrap();
+		for (number, timestamp) in &[(1u8, 1000), (2, 2000), (3, 3000)] {
+			store::add_block(&db, &hash(*number), *number as u32, &hash(number - 1), Some(*timestamp), false).unwrap();
+		}
+		for (block, id, manufacturer) in &[(1u8, b"bottle-1", &first), (2, b"bottle-2", &second)] {
+			store::add_bottle(&db, &hash(*block), *id, manufacturer).unwrap();
+			store::add_custody_event(&db, &hash(*block), 0, store::CustodyEvent {
+				event: "BottleRegistered",
+				bottle: Some(&id[..]),
+				shipment: None,
+				account: Some(*manufacturer),
+				status: Some("Manufactured".into()),
+			}).unwrap();
+		}
+		store::add_custody_event(&db, &hash(3), 0, store::CustodyEvent {
+			event: "BottleStatusChanged",
+			bottle: Some(&b"bottle-1"[..]),
+			shipment: None,
+			account: Some(&customer),
+			status: Some("SoldToCustomer".into()),
+		}).unwrap();
+		db
+	}
+
+	fn bottles(select: Select, page: Page) -> Vec<(String, Option<String>)> {
+		select.rows(&db(), "block_number, id", page, bottle).unwrap()
+			.into_iter()
+			.map(|bottle| (bottle.id, bottle.status))
+			.collect()
+	}
+
+	fn all() -> Select {
+		Select::new(BOTTLE_COLUMNS, "bottle_state")
+	}
+
+	#[test]
+	fn bottles_are_in_their_latest_status() {
+		assert_eq!(bottles(all(), Page::all()), vec![
+			("bottle-1".into(), Some("SoldToCustomer".into())),
+			("bottle-2".into(), Some("Manufactured".into())),
+		]);
+	}
+
+	#[test]
+	fn filters_bottles() {
+		let second = AccountId32::new([2; 32]).to_ss58check();
+
+		assert_eq!(bottles(all().eq("manufacturer", Some(second)), Page::all()), vec![
+			("bottle-2".into(), Some("Manufactured".into())),
+		]);
+		assert_eq!(bottles(all().eq("status", Some("SoldToCustomer".to_string())), Page::all()), vec![
+			("bottle-1".into(), Some("SoldToCustomer".into())),
+		]);
+		assert_eq!(bottles(all().between("timestamp", Some(1500), None), Page::all()), vec![
+			("bottle-2".into(), Some("Manufactured".into())),
+		]);
+		assert_eq!(bottles(all().between("timestamp", None, Some(1500)), Page::all()), vec![
+			("bottle-1".into(), Some("SoldToCustomer".into())),
+		]);
+	}
+
+	#[test]
+	fn pages_bottles() {
+		assert_eq!(bottles(all(), Page::new(Some(1), Some(1))), vec![
+			("bottle-2".into(), Some("Manufactured".into())),
+		]);
+		assert_eq!(Page::new(None, Some(10_000)).limit, MAX_PAGE);
+	}
+}
@@ -0,0 +1,513 @@
+//! The `import-provenance` subcommand, registering bottles and shipments exported by an ERP.
+//!
+//! Rows are read from a CSV or JSON file, signed and submitted to a running node over its
+//! websocket RPC in batches. Rows of a batch are submitted with consecutive nonces, so a
+//! shipment may ship bottles registered earlier in the same file.
+
+use std::{fs::File, path::PathBuf, time::Duration};
+
+use codec::{Decode, Encode};
+use futures::{future::join_all, StreamExt};
+use jsonrpc_core_client::{transports::ws, RpcError, TypedSubscriptionStream};
+use serde::Deserialize;
+use sp_core::{
+	crypto::{Pair as _, Ss58Codec},
+	sr25519, storage::StorageKey, twox_128, Bytes,
+};
+use sp_rpc::{list::ListOrValue, number::NumberOrHex};
+use sp_runtime::{
+	generic::{Era, SignedPayload},
+	traits::IdentifyAccount,
+	DispatchError, MultiSigner,
+};
+use sp_transaction_pool::TransactionStatus;
+use structopt::StructOpt;
+
+use crate::signer::SignerParams;
+use node_template_runtime::{
+	bottle_tracking, member_fees, registrar, AccountId, BlockNumber, BottleTracking, Call, Event, Hash, Header,
+	Index, PalletInfo, RegistrarModule, Runtime, SignedBlock, SignedExtra, UncheckedExtrinsic,
+};
+
+type AuthorClient = sc_rpc_api::author::gen_client::Client<Hash, Hash>;
+type ChainClient = sc_rpc_api::chain::gen_client::Client<BlockNumber, Hash, Header, SignedBlock>;
+type StateClient = sc_rpc_api::state::gen_client::Client<Hash>;
+type SystemClient = substrate_frame_rpc_system::gen_client::Client<Hash, AccountId, Index>;
+
+/// Format of the file to import.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+	Csv,
+	Json,
+}
+
+impl std::str::FromStr for Format {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"csv" => Ok(Format::Csv),
+			"json" => Ok(Format::Json),
+			_ => Err(format!("unknown format `{}`, expected `csv` or `json`", s)),
+		}
+	}
+}
+
+/// The `import-provenance` command.
+#[derive(Debug, StructOpt)]
+pub struct ImportProvenanceCmd {
+	/// CSV or JSON file with one bottle or shipment per row.
+	///
+	/// CSV files have the header `kind,id,carrier,receiver,customs,deadline,bottles`, where
+	/// `kind` is `bottle` or `shipment` and shipment bottles are separated by `;`. JSON files
+	/// hold an array of objects with the same fields and `bottles` as an array.
+	#[structopt(parse(from_os_str))]
+	pub input: PathBuf,
+
+	/// Format of the input, guessed from its extension by default.
+	#[structopt(long)]
+	pub format: Option<Format>,
+
+	/// Websocket RPC endpoint of the node to submit to.
+	#[structopt(long, default_value = "ws://127.0.0.1:9944")]
+	pub url: String,
+
+	#[structopt(flatten)]
+	pub signer: SignerParams,
+
+	/// Number of rows submitted before waiting for them to be included in a block.
+	#[structopt(long, default_value = "50")]
+	pub batch_size: usize,
+
+	/// Seconds to wait for a row to be included in a block before counting it as failed.
+	#[structopt(long, default_value = "60")]
+	pub timeout: u64,
+}
+
+/// A bottle or shipment to register.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Row {
+	Bottle {
+		id: String,
+	},
+	Shipment {
+		id: String,
+		carrier: String,
+		receiver: String,
+		#[serde(default)]
+		customs: Option<String>,
+		#[serde(default)]
+		deadline: Option<BlockNumber>,
+		bottles: Vec<String>,
+	},
+}
+
+/// A CSV record, which has every column of either kind of row.
+#[derive(Debug, Deserialize)]
+struct CsvRecord {
+	kind: String,
+	id: String,
+	#[serde(default)]
+	carrier: Option<String>,
+	#[serde(default)]
+	receiver: Option<String>,
+	#[serde(default)]
+	customs: Option<String>,
+	#[serde(default)]
+	deadline: Option<BlockNumber>,
+	#[serde(default)]
+	bottles: Option<String>,
+}
+
+impl CsvRecord {
+	fn into_row(self) -> Result<Row, String> {
+		match self.kind.as_str() {
+			"bottle" => Ok(Row::Bottle { id: self.id }),
+			"shipment" => Ok(Row::Shipment {
+				id: self.id,
+				carrier: self.carrier.ok_or("shipment without a carrier")?,
+				receiver: self.receiver.ok_or("shipment without a receiver")?,
+				customs: self.customs.filter(|customs| !customs.is_empty()),
+				deadline: self.deadline,
+				bottles: self.bottles
+					.unwrap_or_default()
+					.split(';')
+					.map(str::trim)
+					.filter(|bottle| !bottle.is_empty())
+					.map(String::from)
+					.collect(),
+			}),
+			kind => Err(format!("unknown kind `{}`, expected `bottle` or `shipment`", kind)),
+		}
+	}
+}
+
+impl Row {
+	fn describe(&self) -> String {
+		match self {
+			Row::Bottle { id } => format!("bottle {}", id),
+			Row::Shipment { id, .. } => format!("shipment {}", id),
+		}
+	}
+
+	fn call(&self) -> Result<Call, String> {
+		Ok(match self {
+			Row::Bottle { id } => Call::RegistrarModule(registrar::Call::register_bottle(id.as_bytes().to_vec(), vec![])),
+			Row::Shipment { id, carrier, receiver, customs, deadline, bottles } => Call::BottleTracking(
+				bottle_tracking::Call::register_shipment(
+					id.as_bytes().to_vec(),
+					parse_account(carrier)?,
+					parse_account(receiver)?,
+					customs.as_deref().map(parse_account).transpose()?,
+					*deadline,
+					bottles.iter().map(|bottle| bottle.as_bytes().to_vec()).collect(),
+					None,
+					vec![],
+				),
+			),
+		})
+	}
+}
+
+fn parse_account(address: &str) -> Result<AccountId, String> {
+	AccountId::from_ss58check(address).map_err(|e| format!("invalid address `{}`: {:?}", address, e))
+}
+
+/// Reads the rows of the file, with the reason of any row that could not be read.
+pub fn read_rows(input: &PathBuf, format: Format) -> Result<Vec<Result<Row, String>>, String> {
+	let file = File::open(input).map_err(|e| format!("cannot open {}: {}", input.display(), e))?;
+
+	match format {
+		Format::Csv => Ok(csv::ReaderBuilder::new()
+			.trim(csv::Trim::All)
+			.from_reader(file)
+			.deserialize::<CsvRecord>()
+			.map(|record| record.map_err(|e| e.to_string()).and_then(CsvRecord::into_row))
+			.collect()),
+		Format::Json => serde_json::from_reader::<_, Vec<Row>>(file)
+			.map(|rows| rows.into_iter().map(Ok).collect())
+			.map_err(|e| format!("cannot read {}: {}", input.display(), e)),
+	}
+}
+
+/// Connection to the node and what signing for it takes.
+struct Submitter {
+	author: AuthorClient,
+	chain: ChainClient,
+	state: StateClient,
+	system: SystemClient,
+	signer: sr25519::Pair,
+	account: AccountId,
+	genesis_hash: Hash,
+	spec_version: u32,
+	transaction_version: u32,
+	nonce: Index,
+}
+
+/// A row waiting to be included in a block.
+struct Pending {
+	row: usize,
+	what: String,
+	extrinsic: Vec<u8>,
+	status: TypedSubscriptionStream<TransactionStatus<Hash, Hash>>,
+}
+
+impl ImportProvenanceCmd {
+	/// Imports the file, printing the outcome of every row.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		let format = match self.format {
+			Some(format) => format,
+			None => match self.input.extension().and_then(|ext| ext.to_str()) {
+				Some(ext) => ext.parse()?,
+				None => return Err("cannot guess the input format, pass --format".into()),
+			},
+		};
+		let rows = read_rows(&self.input, format)?;
+		let signer = self.signer.pair()?;
+
+		let mut runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+		let failed = runtime.block_on(self.import(rows, signer))?;
+
+		if failed > 0 {
+			return Err(format!("{} rows failed", failed).into());
+		}
+		Ok(())
+	}
+
+	async fn import(&self, rows: Vec<Result<Row, String>>, signer: sr25519::Pair) -> Result<usize, String> {
+		let mut submitter = Submitter::connect(&self.url, signer).await?;
+		let timeout = Duration::from_secs(self.timeout);
+		let mut failed = 0;
+
+		let rows: Vec<_> = rows.into_iter().enumerate().map(|(i, row)| (i + 1, row)).collect();
+		for batch in rows.chunks(self.batch_size.max(1)) {
+			let failed_before = failed;
+			let mut pending = Vec::new();
+
+			for (row, result) in batch {
+				let what = result.as_ref().map(Row::describe).unwrap_or_else(|_| "row".into());
+				match result.as_ref().map_err(Clone::clone).and_then(Row::call) {
+					Ok(call) => match submitter.submit(call).await {
+						Ok((extrinsic, status)) => pending.push(Pending { row: *row, what, extrinsic, status }),
+						Err(e) => {
+							failed += 1;
+							println!("row {}: {}: rejected: {}", row, what, e);
+						},
+					},
+					Err(e) => {
+						failed += 1;
+						println!("row {}: {}: skipped: {}", row, what, e);
+					},
+				}
+			}
+
+			let outcomes = join_all(pending.into_iter().map(|pending| submitter.outcome(pending, timeout))).await;
+			for (row, what, outcome) in outcomes {
+				match outcome {
+					Ok(block) => println!("row {}: {}: registered in block {:?}", row, what, block),
+					Err(e) => {
+						failed += 1;
+						println!("row {}: {}: failed: {}", row, what, e);
+					},
+				}
+			}
+
+			// Rows that did not get in leave gaps in the nonces, which the next batch would
+			// wait on forever.
+			if failed > failed_before {
+				submitter.sync_nonce().await?;
+			}
+		}
+
+		println!("{} of {} rows imported", rows.len() - failed, rows.len());
+		Ok(failed)
+	}
+}
+
+impl Submitter {
+	async fn connect(url: &str, signer: sr25519::Pair) -> Result<Self, String> {
+		let url = url.parse().map_err(|e| format!("invalid url `{}`: {}", url, e))?;
+		let author: AuthorClient = ws::connect(&url).await.map_err(rpc_error)?;
+		let chain: ChainClient = ws::connect(&url).await.map_err(rpc_error)?;
+		let state: StateClient = ws::connect(&url).await.map_err(rpc_error)?;
+		let system: SystemClient = ws::connect(&url).await.map_err(rpc_error)?;
+
+		let genesis_hash = match chain.block_hash(Some(ListOrValue::Value(NumberOrHex::Number(0)))).await {
+			Ok(ListOrValue::Value(Some(hash))) => hash,
+			Ok(_) => return Err("node has no genesis block".into()),
+			Err(e) => return Err(rpc_error(e)),
+		};
+		let version = state.runtime_version(None).await.map_err(rpc_error)?;
+		let account = MultiSigner::from(signer.public()).into_account();
+		let nonce = system.nonce(account.clone()).await.map_err(rpc_error)?;
+
+		Ok(Submitter {
+			author,
+			chain,
+			state,
+			system,
+			signer,
+			account,
+			genesis_hash,
+			spec_version: version.spec_version,
+			transaction_version: version.transaction_version,
+			nonce,
+		})
+	}
+
+	/// Signs the call with the next nonce and submits it, waiting until the pool accepted it.
+	async fn submit(
+		&mut self,
+		call: Call,
+	) -> Result<(Vec<u8>, TypedSubscriptionStream<TransactionStatus<Hash, Hash>>), String> {
+		let extra: SignedExtra = (
+			frame_system::CheckSpecVersion::new(),
+			frame_system::CheckTxVersion::new(),
+			frame_system::CheckGenesis::new(),
+			frame_system::CheckEra::from(Era::Immortal),
+			frame_system::CheckNonce::from(self.nonce),
+			frame_system::CheckWeight::new(),
+			member_fees::WaiveMemberFees::<Runtime>::from(0),
+		);
+		let payload = SignedPayload::from_raw(
+			call.clone(),
+			extra.clone(),
+			(self.spec_version, self.transaction_version, self.genesis_hash, self.genesis_hash, (), (), ()),
+		);
+		let signature = payload.using_encoded(|payload| self.signer.sign(payload));
+		let extrinsic = UncheckedExtrinsic::new_signed(call, self.account.clone(), signature.into(), extra).encode();
+
+		let mut status = self.author.watch_extrinsic(Bytes(extrinsic.clone())).map_err(rpc_error)?;
+		match status.next().await {
+			Some(Ok(TransactionStatus::Invalid)) => Err("invalid transaction".into()),
+			Some(Ok(TransactionStatus::Dropped)) => Err("dropped from the pool".into()),
+			Some(Ok(_)) => {
+				self.nonce += 1;
+				Ok((extrinsic, status))
+			},
+			Some(Err(e)) => Err(rpc_error(e)),
+			None => Err("node closed the subscription".into()),
+		}
+	}
+
+	/// Takes the next nonce from the node again, which counts the transactions in its pool.
+	async fn sync_nonce(&mut self) -> Result<(), String> {
+		self.nonce = self.system.nonce(self.account.clone()).await.map_err(rpc_error)?;
+		Ok(())
+	}
+
+	/// Waits for the row to be included in a block and tells whether it was dispatched.
+	async fn outcome(&self, pending: Pending, timeout: Duration) -> (usize, String, Result<Hash, String>) {
+		let Pending { row, what, extrinsic, mut status } = pending;
+		let included = async {
+			loop {
+				match status.next().await {
+					Some(Ok(TransactionStatus::InBlock(block))) | Some(Ok(TransactionStatus::Finalized(block))) => {
+						break match self.dispatch_error(block, &extrinsic).await {
+							Ok(None) => Ok(block),
+							Ok(Some(e)) => Err(e),
+							Err(e) => Err(format!("included in block {:?}, but {}", block, e)),
+						};
+					},
+					Some(Ok(TransactionStatus::Invalid)) => break Err("invalid transaction".into()),
+					Some(Ok(TransactionStatus::Dropped)) => break Err("dropped from the pool".into()),
+					Some(Ok(TransactionStatus::Usurped(_))) => break Err("usurped by another transaction".into()),
+					Some(Ok(_)) => continue,
+					Some(Err(e)) => break Err(rpc_error(e)),
+					None => break Err("node closed the subscription".into()),
+				}
+			}
+		};
+		let result = tokio::time::timeout(timeout, included).await
+			.unwrap_or_else(|_| Err(format!("not included within {} seconds", timeout.as_secs())));
+
+		(row, what, result)
+	}
+
+	/// Looks the extrinsic up in the block and returns its dispatch error, if any.
+	async fn dispatch_error(&self, block: Hash, extrinsic: &[u8]) -> Result<Option<String>, String> {
+		let signed = self.chain.block(Some(block)).await.map_err(rpc_error)?
+			.ok_or("the block is unknown")?;
+		let index = signed.block.extrinsics.iter()
+			.position(|xt| xt.encode() == extrinsic)
+			.ok_or("the extrinsic is not in the block")? as u32;
+
+		let key = StorageKey([twox_128(b"System"), twox_128(b"Events")].concat());
+		let events = match self.state.storage(key, Some(block)).await.map_err(rpc_error)? {
+			Some(data) => Vec::<frame_system::EventRecord<Event, Hash>>::decode(&mut &data.0[..])
+				.map_err(|e| format!("cannot decode the events: {}", e))?,
+			None => Vec::new(),
+		};
+
+		Ok(events.into_iter()
+			.filter(|record| record.phase == frame_system::Phase::ApplyExtrinsic(index))
+			.find_map(|record| match record.event {
+				Event::frame_system(frame_system::RawEvent::ExtrinsicFailed(error, _)) => Some(describe_error(error)),
+				_ => None,
+			}))
+	}
+}
+
+/// Names the pallet error variant behind a dispatch error.
+pub fn describe_error(error: DispatchError) -> String {
+	use frame_support::{error::ModuleErrorMetadata, metadata::DecodeDifferent, traits::PalletInfo as _};
+
+	let (index, code) = match error {
+		DispatchError::Module { index, error, .. } => (index as usize, error as usize),
+		error => return <&'static str>::from(error).into(),
+	};
+
+	let (pallet, errors) = if Some(index) == PalletInfo::index::<RegistrarModule>() {
+		("RegistrarModule", <registrar::Error<Runtime> as ModuleErrorMetadata>::metadata())
+	} else if Some(index) == PalletInfo::index::<BottleTracking>() {
+		("BottleTracking", <bottle_tracking::Error<Runtime> as ModuleErrorMetadata>::metadata())
+	} else {
+		return format!("error {} of pallet {}", code, index);
+	};
+
+	match errors.get(code).map(|error| &error.name) {
+		Some(DecodeDifferent::Encode(name)) => format!("{}::{}", pallet, name),
+		Some(DecodeDifferent::Decoded(name)) => format!("{}::{}", pallet, name),
+		None => format!("{} error {}", pallet, code),
+	}
+}
+
+fn rpc_error(error: RpcError) -> String {
+	format!("rpc: {}", error)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Write;
+
+	fn write(contents: &str, extension: &str) -> PathBuf {
+		let path = std::env::temp_dir().join(format!("provenance-import-{}.{}", std::process::id(), extension));
+		File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+		path
+	}
+
+	const BOB: &str = "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty";
+	const CHARLIE: &str = "5FLSigC9HGRKVhB9FiEo4Y3koPsNmBmLJbpXg2mp1hXcS59Y";
+
+	#[test]
+	fn reads_csv_rows() {
+		let path = write(&format!(
+			"kind,id,carrier,receiver,customs,deadline,bottles\n\
+			bottle,bottle-1,,,,,\n\
+			shipment,shipment-1,{},{},,100,bottle-1; bottle-2\n\
+			crate,crate-1,,,,,\n",
+			BOB, CHARLIE,
+		), "csv");
+
+		let rows = read_rows(&path, Format::Csv).unwrap();
+
+		assert_eq!(rows[0], Ok(Row::Bottle { id: "bottle-1".into() }));
+		assert_eq!(rows[1], Ok(Row::Shipment {
+			id: "shipment-1".into(),
+			carrier: BOB.into(),
+			receiver: CHARLIE.into(),
+			customs: None,
+			deadline: Some(100),
+			bottles: vec!["bottle-1".into(), "bottle-2".into()],
+		}));
+		assert!(rows[2].is_err());
+	}
+
+	#[test]
+	fn reads_json_rows() {
+		let path = write(&format!(
+			r#"[{{"kind": "bottle", "id": "bottle-1"}},
+			{{"kind": "shipment", "id": "shipment-1", "carrier": "{}", "receiver": "{}", "bottles": ["bottle-1"]}}]"#,
+			BOB, CHARLIE,
+		), "json");
+
+		let rows = read_rows(&path, Format::Json).unwrap();
+
+		assert_eq!(rows.len(), 2);
+		assert_eq!(rows[0], Ok(Row::Bottle { id: "bottle-1".into() }));
+		assert!(matches!(rows[1], Ok(Row::Shipment { ref bottles, .. }) if bottles.len() == 1));
+	}
+
+	#[test]
+	fn rows_with_bad_addresses_are_not_submitted() {
+		let row = Row::Shipment {
+			id: "shipment-1".into(),
+			carrier: "not an address".into(),
+			receiver: CHARLIE.into(),
+			customs: None,
+			deadline: None,
+			bottles: vec![],
+		};
+
+		assert!(row.call().is_err());
+	}
+
+	#[test]
+	fn names_pallet_errors() {
+		let error: DispatchError = registrar::Error::<Runtime>::BottleIdExists.into();
+
+		assert_eq!(describe_error(error), "RegistrarModule::BottleIdExists");
+	}
+}
@@ -0,0 +1,574 @@
+//! Offchain index of the provenance events in a SQLite database, built with the `indexer`
+//! feature.
+//!
+//! The index follows the best chain. Every row belongs to the block it was indexed from, so
+//! that retracting a block in a reorganisation deletes everything indexed from it. Blocks are
+//! marked final once finalized, after which the forks next to them are dropped.
+
+use std::{marker::PhantomData, path::Path, sync::Arc};
+
+use codec::Decode;
+use frame_support::{storage::StoragePrefixedMap, StorageMap};
+use futures::{stream, StreamExt};
+use rusqlite::{params, Connection, OptionalExtension};
+use sc_client_api::{Backend, BlockchainEvents, StorageProvider};
+use sp_blockchain::{HeaderBackend, TreeRoute};
+use sp_core::{crypto::Ss58Codec, storage::StorageKey, twox_128};
+use sp_runtime::{generic::BlockId, traits::Header as _};
+
+use node_template_runtime::{
+	bottle_tracking::{self, types::{Shipment, ShipmentStatus}},
+	opaque::Block,
+	registrar::{self, Bottle, MemberType},
+	AccountId, BlockNumber, Event, Hash, Runtime,
+};
+
+type Moment = u64;
+
+const LOG_TARGET: &str = "provenance-indexer";
+
+/// Tables of the index. The current status of a bottle or shipment, and the owner of a
+/// bottle, are those of its latest custody event carrying one.
+pub const SCHEMA: &str = "
+	PRAGMA foreign_keys = ON;
+	CREATE TABLE IF NOT EXISTS blocks (
+		hash BLOB PRIMARY KEY,
+		number INTEGER NOT NULL,
+		parent BLOB NOT NULL,
+		timestamp INTEGER,
+		finalized INTEGER NOT NULL DEFAULT 0
+	);
+	CREATE INDEX IF NOT EXISTS blocks_number ON blocks (number);
+	CREATE TABLE IF NOT EXISTS members (
+		account TEXT NOT NULL,
+		member_type TEXT NOT NULL,
+		block_hash BLOB NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE
+	);
+	CREATE TABLE IF NOT EXISTS bottles (
+		id TEXT NOT NULL,
+		manufacturer TEXT NOT NULL,
+		block_hash BLOB NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE
+	);
+	CREATE INDEX IF NOT EXISTS bottles_manufacturer ON bottles (manufacturer);
+	CREATE TABLE IF NOT EXISTS shipments (
+		id TEXT NOT NULL,
+		sender TEXT NOT NULL,
+		carrier TEXT NOT NULL,
+		receiver TEXT NOT NULL,
+		customs TEXT,
+		block_hash BLOB NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE
+	);
+	CREATE TABLE IF NOT EXISTS shipment_bottles (
+		shipment_id TEXT NOT NULL,
+		bottle_id TEXT NOT NULL,
+		block_hash BLOB NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE
+	);
+	CREATE INDEX IF NOT EXISTS shipment_bottles_bottle ON shipment_bottles (bottle_id);
+	CREATE TABLE IF NOT EXISTS custody_events (
+		block_hash BLOB NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE,
+		event_index INTEGER NOT NULL,
+		event TEXT NOT NULL,
+		bottle_id TEXT,
+		shipment_id TEXT,
+		account TEXT,
+		status TEXT
+	);
+	CREATE INDEX IF NOT EXISTS custody_events_bottle ON custody_events (bottle_id);
+	CREATE INDEX IF NOT EXISTS custody_events_shipment ON custody_events (shipment_id);
+";
+
+#[derive(Debug)]
+pub enum Error {
+	Database(rusqlite::Error),
+	Client(sp_blockchain::Error),
+	Decode(codec::Error),
+	UnknownBlock(Hash),
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Database(e) => write!(f, "database: {}", e),
+			Error::Client(e) => write!(f, "client: {}", e),
+			Error::Decode(e) => write!(f, "cannot decode storage: {}", e),
+			Error::UnknownBlock(hash) => write!(f, "unknown block {:?}", hash),
+		}
+	}
+}
+
+impl From<rusqlite::Error> for Error {
+	fn from(e: rusqlite::Error) -> Self {
+		Error::Database(e)
+	}
+}
+
+impl From<sp_blockchain::Error> for Error {
+	fn from(e: sp_blockchain::Error) -> Self {
+		Error::Client(e)
+	}
+}
+
+impl From<codec::Error> for Error {
+	fn from(e: codec::Error) -> Self {
+		Error::Decode(e)
+	}
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+fn text(bytes: &[u8]) -> String {
+	String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Writes to the index, keeping every row with the block it came from.
+pub mod store {
+	use super::*;
+
+	pub fn open(path: &Path) -> rusqlite::Result<Connection> {
+		let db = Connection::open(path)?;
+		// Readers of the index may hold it while the indexer writes.
+		db.busy_timeout(std::time::Duration::from_secs(5))?;
+		db.execute_batch(SCHEMA)?;
+		Ok(db)
+	}
+
+	/// Whether the block is indexed, along with the rows from it.
+	pub fn has_block(db: &Connection, hash: &Hash) -> rusqlite::Result<bool> {
+		db.query_row("SELECT 1 FROM blocks WHERE hash = ?1", params![hash.as_bytes()], |_| Ok(()))
+			.optional()
+			.map(|found| found.is_some())
+	}
+
+	pub fn add_block(
+		db: &Connection,
+		hash: &Hash,
+		number: BlockNumber,
+		parent: &Hash,
+		timestamp: Option<Moment>,
+		finalized: bool,
+	) -> rusqlite::Result<()> {
+		db.execute(
+			"INSERT INTO blocks (hash, number, parent, timestamp, finalized) VALUES (?1, ?2, ?3, ?4, ?5)",
+			params![hash.as_bytes(), number, parent.as_bytes(), timestamp.map(|t| t as i64), finalized],
+		).map(drop)
+	}
+
+	pub fn add_member(db: &Connection, block: &Hash, account: &AccountId, member_type: &MemberType) -> rusqlite::Result<()> {
+		db.execute(
+			"INSERT INTO members (account, member_type, block_hash) VALUES (?1, ?2, ?3)",
+			params![account.to_ss58check(), format!("{:?}", member_type), block.as_bytes()],
+		).map(drop)
+	}
+
+	pub fn add_bottle(db: &Connection, block: &Hash, id: &[u8], manufacturer: &AccountId) -> rusqlite::Result<()> {
+		db.execute(
+			"INSERT INTO bottles (id, manufacturer, block_hash) VALUES (?1, ?2, ?3)",
+			params![text(id), manufacturer.to_ss58check(), block.as_bytes()],
+		).map(drop)
+	}
+
+	pub fn add_shipment(
+		db: &Connection,
+		block: &Hash,
+		shipment: &Shipment<AccountId, Moment, BlockNumber>,
+	) -> rusqlite::Result<()> {
+		db.execute(
+			"INSERT INTO shipments (id, sender, carrier, receiver, customs, block_hash) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+			params![
+				text(&shipment.id),
+				shipment.sender.to_ss58check(),
+				shipment.carrier.to_ss58check(),
+				shipment.receiver.to_ss58check(),
+				shipment.customs.as_ref().map(Ss58Codec::to_ss58check),
+				block.as_bytes(),
+			],
+		)?;
+		for bottle in &shipment.bottles {
+			db.execute(
+				"INSERT INTO shipment_bottles (shipment_id, bottle_id, block_hash) VALUES (?1, ?2, ?3)",
+				params![text(&shipment.id), text(bottle), block.as_bytes()],
+			)?;
+		}
+		Ok(())
+	}
+
+	/// A step of a bottle or shipment through the supply chain.
+	pub struct CustodyEvent<'a> {
+		pub event: &'a str,
+		pub bottle: Option<&'a [u8]>,
+		pub shipment: Option<&'a [u8]>,
+		pub account: Option<&'a AccountId>,
+		pub status: Option<String>,
+	}
+
+	pub fn add_custody_event(db: &Connection, block: &Hash, index: usize, event: CustodyEvent) -> rusqlite::Result<()> {
+		db.execute(
+			"INSERT INTO custody_events (block_hash, event_index, event, bottle_id, shipment_id, account, status) \
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+			params![
+				block.as_bytes(),
+				index as i64,
+				event.event,
+				event.bottle.map(text),
+				event.shipment.map(text),
+				event.account.map(Ss58Codec::to_ss58check),
+				event.status,
+			],
+		).map(drop)
+	}
+
+	/// Deletes a block retracted from the best chain, unless it is final.
+	pub fn retract(db: &Connection, hash: &Hash) -> rusqlite::Result<()> {
+		db.execute("DELETE FROM blocks WHERE hash = ?1 AND finalized = 0", params![hash.as_bytes()]).map(drop)
+	}
+
+	/// Marks the block and its indexed ancestors final, and deletes the blocks of the forks
+	/// finality ruled out.
+	pub fn finalize(db: &Connection, hash: &Hash, number: BlockNumber) -> rusqlite::Result<()> {
+		let mut next = Some(hash.as_bytes().to_vec());
+		while let Some(hash) = next.take() {
+			let updated = db.execute("UPDATE blocks SET finalized = 1 WHERE hash = ?1 AND finalized = 0", params![hash])?;
+			if updated > 0 {
+				next = db.query_row("SELECT parent FROM blocks WHERE hash = ?1", params![hash], |row| row.get(0)).optional()?;
+			}
+		}
+
+		db.execute("DELETE FROM blocks WHERE finalized = 0 AND number <= ?1", params![number]).map(drop)
+	}
+
+	/// Deletes every block not final yet, as the best chain may have changed meanwhile.
+	pub fn drop_unfinalized(db: &Connection) -> rusqlite::Result<()> {
+		db.execute("DELETE FROM blocks WHERE finalized = 0", params![]).map(drop)
+	}
+
+	pub fn last_block(db: &Connection) -> rusqlite::Result<Option<BlockNumber>> {
+		db.query_row("SELECT MAX(number) FROM blocks", params![], |row| row.get(0))
+	}
+}
+
+/// Where a full node keeps its index: next to its chain database, if that is on disk.
+pub fn index_path(config: &sc_service::Configuration) -> Option<std::path::PathBuf> {
+	config.database.path()
+		.and_then(|path| path.parent())
+		.map(|path| path.join("provenance.sqlite"))
+}
+
+enum Notification {
+	Import { hash: Hash, is_new_best: bool, tree_route: Option<Arc<TreeRoute<Block>>> },
+	Finality { hash: Hash, number: BlockNumber },
+}
+
+/// Indexes the blocks the client imports.
+pub struct Indexer<C, B> {
+	client: Arc<C>,
+	db: Connection,
+	_backend: PhantomData<B>,
+}
+
+fn read<C, B, T>(client: &C, at: &Hash, key: Vec<u8>) -> Result<Option<T>>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B>,
+	T: Decode,
+{
+	match client.storage(&BlockId::Hash(*at), &StorageKey(key))? {
+		Some(data) => Ok(Some(T::decode(&mut &data.0[..])?)),
+		None => Ok(None),
+	}
+}
+
+impl<C, B> Indexer<C, B>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B> + HeaderBackend<Block> + BlockchainEvents<Block> + Send + Sync + 'static,
+{
+	pub fn open(client: Arc<C>, path: &Path) -> rusqlite::Result<Self> {
+		Ok(Indexer { client, db: store::open(path)?, _backend: PhantomData })
+	}
+
+	/// Catches up with the best chain, then follows it.
+	pub async fn run(mut self) {
+		let imports = self.client.import_notification_stream().map(|n| Notification::Import {
+			hash: n.hash,
+			is_new_best: n.is_new_best,
+			tree_route: n.tree_route,
+		});
+		let finality = self.client.finality_notification_stream().map(|n| Notification::Finality {
+			hash: n.hash,
+			number: *n.header.number(),
+		});
+		let mut notifications = stream::select(imports, finality);
+
+		if let Err(e) = self.catch_up() {
+			log::error!(target: LOG_TARGET, "Cannot catch up with the chain: {}", e);
+		}
+
+		while let Some(notification) = notifications.next().await {
+			let result = match notification {
+				Notification::Import { hash, is_new_best: true, tree_route } => self.import_best(hash, tree_route),
+				Notification::Import { .. } => Ok(()),
+				Notification::Finality { hash, number } => store::finalize(&self.db, &hash, number).map_err(Error::from),
+			};
+			if let Err(e) = result {
+				log::warn!(target: LOG_TARGET, "Cannot index the chain: {}", e);
+			}
+		}
+	}
+
+	fn catch_up(&mut self) -> Result<()> {
+		store::drop_unfinalized(&self.db)?;
+
+		let first = match store::last_block(&self.db)? {
+			Some(last) => last + 1,
+			None => {
+				self.index_genesis()?;
+				1
+			},
+		};
+
+		let info = self.client.info();
+		for number in first..=info.best_number {
+			let hash = self.client.hash(number)?.ok_or(Error::Client(sp_blockchain::Error::UnknownBlock(number.to_string())))?;
+			self.index_block(&hash)?;
+		}
+		store::finalize(&self.db, &info.finalized_hash, info.finalized_number)?;
+		Ok(())
+	}
+
+	fn import_best(&mut self, hash: Hash, tree_route: Option<Arc<TreeRoute<Block>>>) -> Result<()> {
+		if let Some(route) = tree_route {
+			for retracted in route.retracted() {
+				store::retract(&self.db, &retracted.hash)?;
+			}
+			for enacted in route.enacted() {
+				self.index_block(&enacted.hash)?;
+			}
+		}
+		self.index_block(&hash)
+	}
+
+	/// Indexes the members, bottles and shipments of the genesis state.
+	fn index_genesis(&mut self) -> Result<()> {
+		let hash = self.client.hash(0)?.ok_or(Error::Client(sp_blockchain::Error::UnknownBlock("0".into())))?;
+		let at = BlockId::Hash(hash);
+		let tx = self.db.transaction()?;
+
+		store::add_block(&tx, &hash, 0, &Hash::default(), None, true)?;
+
+		let members = StorageKey(registrar::Members::<Runtime>::final_prefix().to_vec());
+		for (key, value) in self.client.storage_pairs(&at, &members)? {
+			let member_type = MemberType::decode(&mut &key.0[members.0.len()..])?;
+			for account in Vec::<AccountId>::decode(&mut &value.0[..])? {
+				store::add_member(&tx, &hash, &account, &member_type)?;
+			}
+		}
+
+		let bottles = StorageKey(registrar::Bottles::<Runtime>::final_prefix().to_vec());
+		for (_, value) in self.client.storage_pairs(&at, &bottles)? {
+			let bottle = Bottle::<AccountId, Moment>::decode(&mut &value.0[..])?;
+			store::add_bottle(&tx, &hash, bottle.id(), bottle.manufacturer())?;
+		}
+
+		let shipments = StorageKey(bottle_tracking::Shipments::<Runtime>::final_prefix().to_vec());
+		for (_, value) in self.client.storage_pairs(&at, &shipments)? {
+			store::add_shipment(&tx, &hash, &Shipment::decode(&mut &value.0[..])?)?;
+		}
+
+		tx.commit()?;
+		Ok(())
+	}
+
+	fn index_block(&mut self, hash: &Hash) -> Result<()> {
+		if store::has_block(&self.db, hash)? {
+			return Ok(());
+		}
+
+		let client = &*self.client;
+		let header = client.header(BlockId::Hash(*hash))?.ok_or(Error::UnknownBlock(*hash))?;
+		let timestamp = read::<_, B, Moment>(client, hash, [twox_128(b"Timestamp"), twox_128(b"Now")].concat())?;
+		let events = read::<_, B, Vec<frame_system::EventRecord<Event, Hash>>>(
+			client, hash, [twox_128(b"System"), twox_128(b"Events")].concat(),
+		)?.unwrap_or_default();
+
+		let tx = self.db.transaction()?;
+		store::add_block(&tx, hash, header.number, &header.parent_hash, timestamp, false)?;
+		for (index, record) in events.into_iter().enumerate() {
+			index_event::<_, B>(client, &tx, hash, index, record.event)?;
+		}
+		tx.commit()?;
+		Ok(())
+	}
+}
+
+fn index_event<C, B>(client: &C, db: &Connection, block: &Hash, index: usize, event: Event) -> Result<()>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B>,
+{
+	use bottle_tracking::RawEvent as Tracking;
+	use registrar::RawEvent as Registrar;
+	use store::CustodyEvent;
+
+	let custody = |event: CustodyEvent| store::add_custody_event(db, block, index, event).map_err(Error::from);
+	let of_shipment = |event, shipment: &[u8], account, status: Option<ShipmentStatus>| custody(CustodyEvent {
+		event,
+		bottle: None,
+		shipment: Some(shipment),
+		account: Some(account),
+		status: status.map(|status| format!("{:?}", status)),
+	});
+
+	match event {
+		Event::registrar(event) => match event {
+			Registrar::ManufacturerAdded(account) => store::add_member(db, block, &account, &MemberType::Manufacturer)?,
+			Registrar::CarrierAdded(account) => store::add_member(db, block, &account, &MemberType::Carrier)?,
+			Registrar::RetailerAdded(account) => store::add_member(db, block, &account, &MemberType::Retailer)?,
+			Registrar::CustomerAdded(account) => store::add_member(db, block, &account, &MemberType::Customer)?,
+			Registrar::DistributorAdded(account) => store::add_member(db, block, &account, &MemberType::Distributor)?,
+			Registrar::WarehouseAdded(account) => store::add_member(db, block, &account, &MemberType::Warehouse)?,
+			Registrar::CustomsAdded(account) => store::add_member(db, block, &account, &MemberType::Customs)?,
+			Registrar::BottleRegistered(manufacturer, id) => {
+				store::add_bottle(db, block, &id, &manufacturer)?;
+				custody(CustodyEvent {
+					event: "BottleRegistered",
+					bottle: Some(&id),
+					shipment: None,
+					account: Some(&manufacturer),
+					status: Some(format!("{:?}", registrar::BottleStatus::Manufactured)),
+				})?;
+			},
+			Registrar::BottleStatusChanged(id, status) => {
+				// The owner at the end of the block, as the events do not carry it.
+				let owner = read::<_, B, Bottle<AccountId, Moment>>(client, block, registrar::Bottles::<Runtime>::hashed_key_for(&id))?
+					.map(|bottle| bottle.owner().clone());
+				custody(CustodyEvent {
+					event: "BottleStatusChanged",
+					bottle: Some(&id),
+					shipment: None,
+					account: owner.as_ref(),
+					status: Some(format!("{:?}", status)),
+				})?;
+			},
+		},
+		Event::bottle_tracking(event) => match event {
+			Tracking::ShipmentRegistered(id, sender) => {
+				let key = bottle_tracking::Shipments::<Runtime>::hashed_key_for(&id);
+				if let Some(shipment) = read::<_, B, Shipment<AccountId, Moment, BlockNumber>>(client, block, key)? {
+					store::add_shipment(db, block, &shipment)?;
+				}
+				of_shipment("ShipmentRegistered", &id, &sender, Some(ShipmentStatus::Pending))?;
+			},
+			Tracking::ShipmentStatusUpdated(id, account, status) =>
+				of_shipment("ShipmentStatusUpdated", &id, &account, Some(status))?,
+			Tracking::ShipmentCancelled(id, sender) =>
+				of_shipment("ShipmentCancelled", &id, &sender, Some(ShipmentStatus::Cancelled))?,
+			Tracking::ShipmentRejected(id, receiver) =>
+				of_shipment("ShipmentRejected", &id, &receiver, Some(ShipmentStatus::Rejected))?,
+			Tracking::ShipmentInspected(id, customs) => of_shipment("ShipmentInspected", &id, &customs, None)?,
+			Tracking::ShipmentHeldByCustoms(id, customs) =>
+				of_shipment("ShipmentHeldByCustoms", &id, &customs, Some(ShipmentStatus::Customs))?,
+			Tracking::ShipmentClearedByCustoms(id, customs, _) =>
+				of_shipment("ShipmentClearedByCustoms", &id, &customs, Some(ShipmentStatus::InTransit))?,
+			Tracking::ShipmentTelemetryReported(id, carrier, _) =>
+				of_shipment("ShipmentTelemetryReported", &id, &carrier, None)?,
+			Tracking::ShipmentScanned(id, carrier, _) => of_shipment("ShipmentScanned", &id, &carrier, None)?,
+			Tracking::BottlesLostInTransit(id, carrier, bottles) => {
+				for bottle in &bottles {
+					custody(CustodyEvent {
+						event: "BottleLostInTransit",
+						bottle: Some(bottle),
+						shipment: Some(&id),
+						account: Some(&carrier),
+						status: None,
+					})?;
+				}
+			},
+			Tracking::BottlesSoldToCustomer(customer) => custody(CustodyEvent {
+				event: "BottlesSoldToCustomer",
+				bottle: None,
+				shipment: None,
+				account: Some(&customer),
+				status: None,
+			})?,
+			_ => {},
+		},
+		_ => {},
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::AccountId32;
+
+	fn db() -> Connection {
+		let db = Connection::open_in_memory().unwrap();
+		db.execute_batch(SCHEMA).unwrap();
+		db
+	}
+
+	fn hash(n: u8) -> Hash {
+		Hash::repeat_byte(n)
+	}
+
+	fn count(db: &Connection, table: &str) -> i64 {
+		db.query_row(&format!("SELECT COUNT(*) FROM {}", table), params![], |row| row.get(0)).unwrap()
+	}
+
+	/// Genesis, then blocks 1 and 2a on one fork and 2b on another, each registering a bottle.
+	fn forked() -> Connection {
+		let db = db();
+		let manufacturer = AccountId32::new([1; 32]);
+		store::add_block(&db, &hash(0), 0, &Hash::default(), None, true).unwrap();
+		for (block, number, parent) in &[(hash(1), 1, hash(0)), (hash(0x2a), 2, hash(1)), (hash(0x2b), 2, hash(1))] {
+			store::add_block(&db, block, *number, parent, Some(6000), false).unwrap();
+			store::add_bottle(&db, block, block.as_bytes(), &manufacturer).unwrap();
+		}
+		db
+	}
+
+	#[test]
+	fn retracting_a_block_deletes_its_rows() {
+		let db = forked();
+
+		store::retract(&db, &hash(0x2b)).unwrap();
+
+		assert!(!store::has_block(&db, &hash(0x2b)).unwrap());
+		assert_eq!(count(&db, "bottles"), 2);
+	}
+
+	#[test]
+	fn final_blocks_are_not_retracted() {
+		let db = forked();
+		store::finalize(&db, &hash(1), 1).unwrap();
+
+		store::retract(&db, &hash(1)).unwrap();
+
+		assert!(store::has_block(&db, &hash(1)).unwrap());
+	}
+
+	#[test]
+	fn finality_drops_other_forks() {
+		let db = forked();
+
+		store::finalize(&db, &hash(0x2a), 2).unwrap();
+
+		assert!(store::has_block(&db, &hash(1)).unwrap());
+		assert!(store::has_block(&db, &hash(0x2a)).unwrap());
+		assert!(!store::has_block(&db, &hash(0x2b)).unwrap());
+		assert_eq!(count(&db, "bottles"), 2);
+	}
+
+	#[test]
+	fn catching_up_starts_after_the_last_final_block() {
+		let db = forked();
+		store::finalize(&db, &hash(1), 1).unwrap();
+
+		store::drop_unfinalized(&db).unwrap();
+
+		assert_eq!(store::last_block(&db).unwrap(), Some(1));
+		assert_eq!(count(&db, "bottles"), 1);
+	}
+}
@@ -1,3 +1,8 @@
 pub mod chain_spec;
 pub mod service;
 pub mod rpc;
+mod metrics;
+#[cfg(feature = "indexer")]
+mod indexer;
+#[cfg(feature = "webhooks")]
+mod webhooks;
@@ -7,6 +7,16 @@ mod service;
 mod cli;
 mod command;
 mod rpc;
+mod metrics;
+mod import;
+mod export;
+mod signer;
+#[cfg(feature = "indexer")]
+mod indexer;
+#[cfg(feature = "graphql")]
+mod graphql;
+#[cfg(feature = "webhooks")]
+mod webhooks;
 
 fn main() -> sc_cli::Result<()> {
 	command::run()
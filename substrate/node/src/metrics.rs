@@ -0,0 +1,350 @@
+//! Prometheus metrics of the supply chain, read from the finalized blocks.
+//!
+//! Shipment counts start from the state of the finalized block when the node starts, the
+//! other metrics from zero. Pickup-to-delivery times are measured for the shipments picked
+//! up while the node runs.
+
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+use codec::Decode;
+use frame_support::{error::ModuleErrorMetadata, metadata::DecodeDifferent, storage::StoragePrefixedMap, traits::PalletInfo as _, StorageMap};
+use futures::StreamExt;
+use prometheus_endpoint::{
+	register, Counter, CounterVec, GaugeVec, Histogram, HistogramOpts, Opts, PrometheusError, Registry, U64,
+};
+use sc_client_api::{Backend, BlockchainEvents, StorageProvider};
+use sp_blockchain::HeaderBackend;
+use sp_core::{storage::StorageKey, twox_128};
+use sp_runtime::{generic::BlockId, DispatchError};
+
+use node_template_runtime::{
+	bottle_tracking::{self, types::{Shipment, ShipmentId, ShipmentStatus}},
+	opaque::Block,
+	registrar::{self, BottleStatus},
+	AccountId, BlockNumber, BottleTracking, Event, Hash, PalletInfo, Runtime,
+};
+
+type Moment = u64;
+
+const LOG_TARGET: &str = "provenance-metrics";
+
+/// Metrics of the supply chain.
+pub struct Metrics {
+	bottles_registered: Counter<U64>,
+	shipments: GaugeVec<U64>,
+	delivery_time: Histogram,
+	sales_per_block: Histogram,
+	failed_extrinsics: CounterVec<U64>,
+}
+
+impl Metrics {
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Metrics {
+			bottles_registered: register(
+				Counter::new("provenance_bottles_registered_total", "Bottles registered")?,
+				registry,
+			)?,
+			shipments: register(
+				GaugeVec::new(Opts::new("provenance_shipments", "Shipments by status"), &["status"])?,
+				registry,
+			)?,
+			delivery_time: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"provenance_shipment_delivery_seconds",
+						"Time from the pickup of a shipment to its delivery",
+					).buckets(vec![60.0, 600.0, 3_600.0, 21_600.0, 86_400.0, 259_200.0, 604_800.0, 2_592_000.0]),
+				)?,
+				registry,
+			)?,
+			sales_per_block: register(
+				Histogram::with_opts(
+					HistogramOpts::new("provenance_sales_per_block", "Bottles sold to customers per block")
+						.buckets(vec![0.0, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0]),
+				)?,
+				registry,
+			)?,
+			failed_extrinsics: register(
+				CounterVec::new(
+					Opts::new("provenance_failed_extrinsics_total", "Extrinsics failed with a bottle tracking error"),
+					&["error"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	/// Records a finalized block, with the shipments picked up and not delivered yet.
+	fn record(&self, activity: &Activity, pickups: &mut HashMap<ShipmentId, Moment>) {
+		self.bottles_registered.inc_by(activity.bottles_registered);
+		self.sales_per_block.observe(activity.bottles_sold as f64);
+		for error in &activity.failed {
+			self.failed_extrinsics.with_label_values(&[error.as_str()]).inc();
+		}
+
+		for (id, before, after) in &activity.shipments {
+			if before == after {
+				continue;
+			}
+			if let Some(before) = before {
+				self.shipments.with_label_values(&[&label(before)]).dec();
+			}
+			if let Some(after) = after {
+				self.shipments.with_label_values(&[&label(after)]).inc();
+			}
+
+			match (before, after, activity.timestamp) {
+				(Some(ShipmentStatus::Pending), Some(ShipmentStatus::InTransit), Some(now)) => {
+					pickups.insert(id.clone(), now);
+				},
+				(_, Some(ShipmentStatus::Delivered), Some(now)) | (_, Some(ShipmentStatus::Stored), Some(now)) => {
+					if let Some(pickup) = pickups.remove(id) {
+						self.delivery_time.observe(now.saturating_sub(pickup) as f64 / 1000.0);
+					}
+				},
+				(_, Some(ShipmentStatus::Cancelled), _) | (_, Some(ShipmentStatus::Rejected), _) => {
+					pickups.remove(id);
+				},
+				_ => {},
+			}
+		}
+	}
+}
+
+fn label(status: &ShipmentStatus) -> String {
+	format!("{:?}", status)
+}
+
+/// What a finalized block did to the supply chain.
+#[derive(Default)]
+struct Activity {
+	timestamp: Option<Moment>,
+	bottles_registered: u64,
+	bottles_sold: u64,
+	/// Names of the bottle tracking errors extrinsics failed with.
+	failed: Vec<String>,
+	/// Shipments the events of the block are about, with their status before and after it.
+	shipments: Vec<(ShipmentId, Option<ShipmentStatus>, Option<ShipmentStatus>)>,
+}
+
+/// Names the bottle tracking error behind a dispatch error.
+fn bottle_tracking_error(error: &DispatchError) -> Option<String> {
+	match *error {
+		DispatchError::Module { index, error, .. } if Some(index as usize) == PalletInfo::index::<BottleTracking>() => {
+			let errors = <bottle_tracking::Error<Runtime> as ModuleErrorMetadata>::metadata();
+			Some(match errors.get(error as usize).map(|error| &error.name) {
+				Some(DecodeDifferent::Encode(name)) => name.to_string(),
+				Some(DecodeDifferent::Decoded(name)) => name.clone(),
+				None => format!("Error{}", error),
+			})
+		},
+		_ => None,
+	}
+}
+
+/// The shipment a bottle tracking event is about.
+fn shipment_of(event: &bottle_tracking::Event<Runtime>) -> Option<&ShipmentId> {
+	use bottle_tracking::RawEvent::*;
+
+	match event {
+		ShipmentRegistered(id, ..) | ShipmentStatusUpdated(id, ..) | ShipmentFunded(id, ..) | EscrowHeld(id, ..)
+		| EscrowReleased(id, ..) | ShipmentCancelled(id, ..) | ShipmentRejected(id, ..)
+		| BottlesLostInTransit(id, ..) | ShipmentOverdue(id, ..) | ShipmentInspected(id, ..)
+		| ShipmentHeldByCustoms(id, ..) | ShipmentClearedByCustoms(id, ..) | DisputeOpened(id, ..)
+		| DisputeResponded(id, ..) | DisputeRuled(id, ..) | ShipmentTelemetryReported(id, ..)
+		| ShipmentScanned(id, ..) => Some(id),
+		BottlesSoldToCustomer(..) | DeviceRegistered(..) | DeviceRemoved(..) => None,
+	}
+}
+
+fn read<C, B, T>(client: &C, at: &Hash, key: Vec<u8>) -> sp_blockchain::Result<Option<T>>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B>,
+	T: Decode,
+{
+	match client.storage(&BlockId::Hash(*at), &StorageKey(key))? {
+		Some(data) => T::decode(&mut &data.0[..])
+			.map(Some)
+			.map_err(|e| sp_blockchain::Error::Backend(format!("cannot decode storage: {}", e))),
+		None => Ok(None),
+	}
+}
+
+/// Updates the metrics as blocks are finalized.
+pub struct MetricsWorker<C, B> {
+	client: Arc<C>,
+	metrics: Metrics,
+	pickups: HashMap<ShipmentId, Moment>,
+	_backend: PhantomData<B>,
+}
+
+impl<C, B> MetricsWorker<C, B>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B> + HeaderBackend<Block> + BlockchainEvents<Block> + Send + Sync + 'static,
+{
+	pub fn new(client: Arc<C>, metrics: Metrics) -> Self {
+		MetricsWorker { client, metrics, pickups: HashMap::new(), _backend: PhantomData }
+	}
+
+	pub async fn run(mut self) {
+		let mut finality = self.client.finality_notification_stream();
+		let mut last = self.client.info().finalized_number;
+		let mut counted = self.try_count_shipments(last);
+
+		// Finality notifications may skip blocks finalized together. A block that cannot be
+		// read is tried again on the next notification, so that its activity is not lost.
+		while finality.next().await.is_some() {
+			let finalized = self.client.info().finalized_number;
+			if !counted {
+				counted = self.try_count_shipments(finalized);
+				last = finalized;
+				continue;
+			}
+			while last < finalized {
+				match self.activity(last + 1) {
+					Ok(activity) => {
+						self.metrics.record(&activity, &mut self.pickups);
+						last += 1;
+					},
+					Err(e) => {
+						log::warn!(target: LOG_TARGET, "Cannot read block {}, will try again: {}", last + 1, e);
+						break;
+					},
+				}
+			}
+		}
+	}
+
+	/// Counts the shipments of the block, and tells whether it could.
+	fn try_count_shipments(&self, number: BlockNumber) -> bool {
+		match self.count_shipments(number) {
+			Ok(()) => true,
+			Err(e) => {
+				log::warn!(target: LOG_TARGET, "Cannot count the shipments, will try again: {}", e);
+				false
+			},
+		}
+	}
+
+	fn hash(&self, number: BlockNumber) -> sp_blockchain::Result<Hash> {
+		self.client.hash(number)?.ok_or_else(|| sp_blockchain::Error::UnknownBlock(number.to_string()))
+	}
+
+	/// Sets the shipment counts to those of the block.
+	fn count_shipments(&self, number: BlockNumber) -> sp_blockchain::Result<()> {
+		self.metrics.shipments.reset();
+		let prefix = StorageKey(bottle_tracking::Shipments::<Runtime>::final_prefix().to_vec());
+		for (_, value) in self.client.storage_pairs(&BlockId::Hash(self.hash(number)?), &prefix)? {
+			if let Ok(shipment) = Shipment::<AccountId, Moment, BlockNumber>::decode(&mut &value.0[..]) {
+				self.metrics.shipments.with_label_values(&[&label(&shipment.status)]).inc();
+			}
+		}
+		Ok(())
+	}
+
+	fn activity(&self, number: BlockNumber) -> sp_blockchain::Result<Activity> {
+		let client = &*self.client;
+		let (hash, parent) = (self.hash(number)?, self.hash(number - 1)?);
+		let events = read::<_, B, Vec<frame_system::EventRecord<Event, Hash>>>(
+			client, &hash, [twox_128(b"System"), twox_128(b"Events")].concat(),
+		)?.unwrap_or_default();
+
+		let mut activity = Activity {
+			timestamp: read::<_, B, Moment>(client, &hash, [twox_128(b"Timestamp"), twox_128(b"Now")].concat())?,
+			..Default::default()
+		};
+		let mut shipments = Vec::new();
+		for record in events {
+			match record.event {
+				Event::registrar(registrar::RawEvent::BottleRegistered(..)) => activity.bottles_registered += 1,
+				Event::registrar(registrar::RawEvent::BottleStatusChanged(_, BottleStatus::SoldToCustomer)) =>
+					activity.bottles_sold += 1,
+				Event::bottle_tracking(event) => {
+					if let Some(id) = shipment_of(&event).filter(|id| !shipments.contains(*id)) {
+						shipments.push(id.clone());
+					}
+				},
+				Event::frame_system(frame_system::RawEvent::ExtrinsicFailed(error, _)) =>
+					activity.failed.extend(bottle_tracking_error(&error)),
+				_ => {},
+			}
+		}
+
+		let status = |at: &Hash, id: &ShipmentId| {
+			read::<_, B, Shipment<AccountId, Moment, BlockNumber>>(client, at, bottle_tracking::Shipments::<Runtime>::hashed_key_for(id))
+				.map(|shipment| shipment.map(|shipment| shipment.status))
+		};
+		for id in shipments {
+			let (before, after) = (status(&parent, &id)?, status(&hash, &id)?);
+			activity.shipments.push((id, before, after));
+		}
+		Ok(activity)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn gauge(metrics: &Metrics, status: ShipmentStatus) -> u64 {
+		metrics.shipments.with_label_values(&[&label(&status)]).get()
+	}
+
+	#[test]
+	fn moves_shipments_between_statuses() {
+		let metrics = Metrics::register(&Registry::new()).unwrap();
+		let mut pickups = HashMap::new();
+
+		metrics.record(&Activity {
+			shipments: vec![(b"shipment-1".to_vec(), None, Some(ShipmentStatus::Pending))],
+			..Default::default()
+		}, &mut pickups);
+		metrics.record(&Activity {
+			shipments: vec![(b"shipment-1".to_vec(), Some(ShipmentStatus::Pending), Some(ShipmentStatus::InTransit))],
+			..Default::default()
+		}, &mut pickups);
+
+		assert_eq!(gauge(&metrics, ShipmentStatus::Pending), 0);
+		assert_eq!(gauge(&metrics, ShipmentStatus::InTransit), 1);
+	}
+
+	#[test]
+	fn times_deliveries_from_pickup() {
+		let metrics = Metrics::register(&Registry::new()).unwrap();
+		let mut pickups = HashMap::new();
+		let id = b"shipment-1".to_vec();
+
+		metrics.record(&Activity {
+			timestamp: Some(60_000),
+			shipments: vec![(id.clone(), Some(ShipmentStatus::Pending), Some(ShipmentStatus::InTransit))],
+			..Default::default()
+		}, &mut pickups);
+		metrics.record(&Activity {
+			timestamp: Some(3_660_000),
+			shipments: vec![(id, Some(ShipmentStatus::InTransit), Some(ShipmentStatus::Delivered))],
+			..Default::default()
+		}, &mut pickups);
+
+		assert_eq!(metrics.delivery_time.get_sample_count(), 1);
+		assert_eq!(metrics.delivery_time.get_sample_sum(), 3_600.0);
+		assert!(pickups.is_empty());
+	}
+
+	#[test]
+	fn counts_registrations_sales_and_failures() {
+		let metrics = Metrics::register(&Registry::new()).unwrap();
+
+		metrics.record(&Activity {
+			bottles_registered: 3,
+			bottles_sold: 2,
+			failed: vec!["ShipmentIdMissing".into()],
+			..Default::default()
+		}, &mut HashMap::new());
+
+		assert_eq!(metrics.bottles_registered.get(), 3);
+		assert_eq!(metrics.sales_per_block.get_sample_sum(), 2.0);
+		assert_eq!(metrics.failed_extrinsics.with_label_values(&["ShipmentIdMissing"]).get(), 1);
+	}
+}
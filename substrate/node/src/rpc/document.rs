@@ -0,0 +1,74 @@
+//! RPC serving the documents of bottles and shipments, which the runtime writes into the
+//! offchain index of the nodes started with `--enable-offchain-indexing true`. The chain
+//! only stores their hashes.
+
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use node_template_runtime::{registrar, Hash};
+use sp_core::{offchain::{OffchainStorage, STORAGE_PREFIX}, Bytes};
+use sp_runtime::traits::{BlakeTwo256, Hash as _};
+
+/// Document RPC methods.
+#[rpc]
+pub trait DocumentApi<DocumentHash> {
+	/// Document with the given hash, if this node indexed it.
+	#[rpc(name = "provenance_document")]
+	fn document(&self, hash: DocumentHash) -> Result<Option<Bytes>>;
+}
+
+/// Implements `DocumentApi` from the offchain database of a full node.
+pub struct Documents<S> {
+	storage: Option<S>,
+}
+
+impl<S> Documents<S> {
+	/// Creates the RPC handler reading from the offchain database, if the node has one.
+	pub fn new(storage: Option<S>) -> Self {
+		Documents { storage }
+	}
+}
+
+fn internal_error(message: impl ToString) -> RpcError {
+	RpcError {
+		code: ErrorCode::InternalError,
+		message: message.to_string(),
+		data: None,
+	}
+}
+
+impl<S> DocumentApi<Hash> for Documents<S>
+where
+	S: OffchainStorage + 'static,
+{
+	fn document(&self, hash: Hash) -> Result<Option<Bytes>> {
+		let storage = self.storage.as_ref().ok_or_else(|| internal_error("no offchain database"))?;
+
+		match storage.get(STORAGE_PREFIX, &registrar::document_key(&hash)) {
+			Some(document) if BlakeTwo256::hash(&document) == hash => Ok(Some(Bytes(document))),
+			Some(_) => Err(internal_error("the indexed document does not match its hash")),
+			None => Ok(None),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::offchain::storage::InMemOffchainStorage;
+
+	#[test]
+	fn documents_are_served_when_they_match_their_hash() {
+		let document = b"bill of lading".to_vec();
+		let hash = BlakeTwo256::hash(&document);
+		let other = BlakeTwo256::hash(b"invoice");
+		let mut storage = InMemOffchainStorage::default();
+		storage.set(STORAGE_PREFIX, &registrar::document_key(&hash), &document);
+		storage.set(STORAGE_PREFIX, &registrar::document_key(&other), b"forged invoice");
+		let documents = Documents::new(Some(storage));
+
+		assert_eq!(documents.document(hash).unwrap(), Some(Bytes(document)));
+		assert!(documents.document(other).is_err());
+		assert_eq!(documents.document(Hash::repeat_byte(1)).unwrap(), None);
+		assert!(Documents::<InMemOffchainStorage>::new(None).document(hash).is_err());
+	}
+}
@@ -0,0 +1,76 @@
+//! RPC serving the proofs `provenance-verifier` checks, so that clients need not trust the
+//! node answering them.
+
+use std::sync::Arc;
+
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use node_template_runtime::{opaque::Block, Hash, Header};
+use sc_client_api::{BlockBackend, ProofProvider};
+use serde::{Deserialize, Serialize};
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::generic::BlockId;
+
+/// Proof of a bottle and of its sale against the state root of a header.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BottleProof {
+	/// Header the proof is against.
+	pub header: Header,
+	/// GRANDPA justification finalizing the header, if the node kept one for it.
+	pub justification: Option<Bytes>,
+	/// Trie nodes proving the `registrar::Bottles` and `bottle_tracking::BottleSoldTo` entries.
+	pub proof: Vec<Bytes>,
+}
+
+/// Provenance RPC methods.
+#[rpc]
+pub trait ProvenanceApi<BlockHash> {
+	/// Proof of the bottle at the given block, the last finalized one by default.
+	#[rpc(name = "provenance_bottleProof")]
+	fn bottle_proof(&self, bottle: Bytes, at: Option<BlockHash>) -> Result<BottleProof>;
+}
+
+/// Implements `ProvenanceApi` from the client of a full node.
+pub struct Provenance<C> {
+	client: Arc<C>,
+}
+
+impl<C> Provenance<C> {
+	/// Creates the RPC handler reading from the client.
+	pub fn new(client: Arc<C>) -> Self {
+		Provenance { client }
+	}
+}
+
+fn internal_error(message: impl ToString) -> RpcError {
+	RpcError {
+		code: ErrorCode::InternalError,
+		message: message.to_string(),
+		data: None,
+	}
+}
+
+impl<C> ProvenanceApi<Hash> for Provenance<C>
+where
+	C: ProofProvider<Block> + BlockBackend<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+{
+	fn bottle_proof(&self, bottle: Bytes, at: Option<Hash>) -> Result<BottleProof> {
+		let at = BlockId::Hash(at.unwrap_or_else(|| self.client.info().finalized_hash));
+
+		let header = self.client.header(at).map_err(internal_error)?
+			.ok_or_else(|| internal_error("unknown block"))?;
+		let justification = self.client.justification(&at).map_err(internal_error)?;
+		let keys = provenance_verifier::bottle_keys(&bottle);
+		let proof = self.client
+			.read_proof(&at, &mut keys.iter().map(|key| &key[..]))
+			.map_err(internal_error)?;
+
+		Ok(BottleProof {
+			header,
+			justification: justification.map(Bytes),
+			proof: proof.iter_nodes().map(Bytes).collect(),
+		})
+	}
+}
@@ -2,7 +2,7 @@
 
 use std::sync::Arc;
 use std::time::Duration;
-use sc_client_api::{ExecutorProvider, RemoteBackend};
+use sc_client_api::{Backend, ExecutorProvider, RemoteBackend};
 use node_template_runtime::{self, opaque::Block, RuntimeApi};
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
 use sp_inherents::InherentDataProviders;
@@ -115,15 +115,21 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 	let enable_grandpa = !config.disable_grandpa;
 	let prometheus_registry = config.prometheus_registry().cloned();
 	let telemetry_connection_sinks = sc_service::TelemetryConnectionSinks::default();
+	#[cfg(feature = "indexer")]
+	let indexer_path = crate::indexer::index_path(&config);
+	#[cfg(feature = "webhooks")]
+	let webhook_paths = crate::webhooks::paths(&config);
 
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
+		let offchain_storage = backend.offchain_storage();
 
 		Box::new(move |deny_unsafe, _| {
 			let deps = crate::rpc::FullDeps {
 				client: client.clone(),
 				pool: pool.clone(),
+				offchain_storage: offchain_storage.clone(),
 				deny_unsafe,
 			};
 
@@ -144,6 +150,34 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		backend, network_status_sinks, system_rpc_tx, config,
 	})?;
 
+	#[cfg(feature = "indexer")]
+	match indexer_path {
+		Some(path) => {
+			let indexer = crate::indexer::Indexer::open(client.clone(), &path)
+				.map_err(|e| ServiceError::Other(format!("cannot open provenance index: {}", e)))?;
+			task_manager.spawn_handle().spawn_blocking("provenance-indexer", indexer.run());
+		},
+		None => log::warn!("The provenance index needs a database on disk, not indexing."),
+	}
+
+	#[cfg(feature = "webhooks")]
+	if let Some((hooks, cursors)) = webhook_paths {
+		let dispatcher = crate::webhooks::Dispatcher::open(client.clone(), &hooks, cursors)
+			.map_err(|e| ServiceError::Other(e.to_string()))?;
+		if let Some(dispatcher) = dispatcher {
+			task_manager.spawn_handle().spawn("provenance-webhooks", dispatcher.run());
+		}
+	}
+
+	if let Some(registry) = prometheus_registry.as_ref() {
+		let metrics = crate::metrics::Metrics::register(registry)
+			.map_err(|e| ServiceError::Other(format!("cannot register provenance metrics: {}", e)))?;
+		task_manager.spawn_handle().spawn(
+			"provenance-metrics",
+			crate::metrics::MetricsWorker::new(client.clone(), metrics).run(),
+		);
+	}
+
 	if role.is_authority() {
 		let proposer = sc_basic_authorship::ProposerFactory::new(
 			client.clone(),
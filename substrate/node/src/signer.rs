@@ -0,0 +1,53 @@
+//! Selection of the account signing on behalf of the user of a subcommand.
+
+use std::path::PathBuf;
+
+use sp_core::{
+	crypto::{key_types, Pair as _, Protected, Ss58Codec},
+	sr25519,
+};
+use structopt::StructOpt;
+
+/// The account signing, given by its secret URI or looked up in a keystore.
+#[derive(Debug, StructOpt)]
+pub struct SignerParams {
+	/// Secret URI of the signing account, e.g. `//Alice`.
+	#[structopt(long, conflicts_with_all = &["keystore-path", "account"], required_unless = "keystore-path")]
+	pub suri: Option<String>,
+
+	/// Keystore holding the `acco` key of the signing account.
+	#[structopt(long, parse(from_os_str), requires = "account")]
+	pub keystore_path: Option<PathBuf>,
+
+	/// SS58 address of the signing account in the keystore.
+	#[structopt(long)]
+	pub account: Option<String>,
+
+	/// Password of the secret URI or the keystore.
+	#[structopt(long)]
+	pub password: Option<String>,
+}
+
+impl SignerParams {
+	/// Key pair of the signing account.
+	pub fn pair(&self) -> Result<sr25519::Pair, String> {
+		let password = self.password.as_deref();
+
+		if let Some(suri) = &self.suri {
+			return sr25519::Pair::from_string(suri, password).map_err(|e| format!("invalid secret URI: {:?}", e));
+		}
+
+		let (path, account) = match (&self.keystore_path, &self.account) {
+			(Some(path), Some(account)) => (path, account),
+			_ => return Err("pass --suri, or --keystore-path and --account".into()),
+		};
+		let public = sr25519::Public::from_ss58check(account)
+			.map_err(|e| format!("invalid address `{}`: {:?}", account, e))?;
+		let keystore = sc_keystore::Store::open(path.clone(), password.map(|p| Protected::from(p.to_string())))
+			.map_err(|e| format!("cannot open keystore: {}", e))?;
+		let pair = keystore.read()
+			.key_pair_by_type::<sr25519::Pair>(&public, key_types::ACCOUNT)
+			.map_err(|e| format!("no key for {} in the keystore: {}", account, e))?;
+		Ok(pair)
+	}
+}
@@ -0,0 +1,533 @@
+//! Webhooks telling members about the shipments and sales of finalized blocks, built with the
+//! `webhooks` feature.
+//!
+//! Hooks are read from `webhooks.json` next to the chain database. Every hook follows the
+//! finalized chain on its own from a cursor kept in `webhook-cursors.json`, so that a hook
+//! whose endpoint is down neither loses notifications nor holds up the others. A notification
+//! is retried until its endpoint accepts it, and may be delivered again if the node stops
+//! right after delivering it: receivers tell repeats apart by the `X-Provenance-Delivery`
+//! header.
+
+use std::{collections::HashMap, fs, marker::PhantomData, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::Duration};
+
+use codec::Decode;
+use frame_support::{storage::StoragePrefixedMap, StorageMap};
+use futures::{future, StreamExt};
+use hmac::{Hmac, Mac, NewMac};
+use hyper::{client::{connect::Connect, HttpConnector}, Body, Client, Request};
+use hyper_rustls::HttpsConnector;
+use serde::{Deserialize, Serialize};
+use sc_client_api::{Backend, BlockchainEvents, StorageProvider};
+use sha2::Sha256;
+use sp_blockchain::HeaderBackend;
+use sp_core::{crypto::Ss58Codec, hexdisplay::HexDisplay, storage::StorageKey, twox_128};
+use sp_runtime::generic::BlockId;
+
+use node_template_runtime::{
+	bottle_tracking::{self, types::Shipment},
+	opaque::Block,
+	registrar::{self, MemberType},
+	AccountId, BlockNumber, Event, Hash, Runtime,
+};
+
+type Moment = u64;
+
+const LOG_TARGET: &str = "provenance-webhooks";
+
+/// Events a hook can ask for.
+pub const EVENTS: [&str; 3] = ["ShipmentRegistered", "ShipmentStatusUpdated", "BottlesSoldToCustomer"];
+
+/// Names of the member types a hook can ask for the events of.
+pub const ROLES: [&str; 7] = ["Manufacturer", "Carrier", "Retailer", "Customer", "Distributor", "Warehouse", "Customs"];
+
+#[derive(Debug)]
+pub enum Error {
+	Config(String),
+	Client(sp_blockchain::Error),
+	Decode(codec::Error),
+	Delivery(String),
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Config(e) => write!(f, "invalid webhooks: {}", e),
+			Error::Client(e) => write!(f, "client: {}", e),
+			Error::Decode(e) => write!(f, "cannot decode storage: {}", e),
+			Error::Delivery(e) => write!(f, "cannot deliver: {}", e),
+		}
+	}
+}
+
+impl From<sp_blockchain::Error> for Error {
+	fn from(e: sp_blockchain::Error) -> Self {
+		Error::Client(e)
+	}
+}
+
+impl From<codec::Error> for Error {
+	fn from(e: codec::Error) -> Self {
+		Error::Decode(e)
+	}
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// An endpoint and the notifications it wants.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Hook {
+	/// Names the cursor of the hook, which restarts from the finalized block when renamed.
+	pub name: String,
+	pub url: String,
+	/// Key of the HMAC-SHA256 signature of the payloads.
+	pub secret: String,
+	/// Only the events this account is a party to.
+	#[serde(default)]
+	pub account: Option<String>,
+	/// Only the events a member of this type is a party to.
+	#[serde(default)]
+	pub role: Option<String>,
+	/// Only these events, all of `EVENTS` by default.
+	#[serde(default)]
+	pub events: Option<Vec<String>>,
+}
+
+impl Hook {
+	fn check(&mut self) -> Result<()> {
+		let name = self.name.clone();
+		let invalid = move |what: String| Error::Config(format!("hook {}: {}", name, what));
+		if let Some(account) = &self.account {
+			// Compare addresses in the format the notifications use.
+			let account = AccountId::from_ss58check(account).map_err(|_| invalid(format!("invalid account {}", account)))?;
+			self.account = Some(account.to_ss58check());
+		}
+		if let Some(role) = self.role.as_ref().filter(|role| !ROLES.contains(&role.as_str())) {
+			return Err(invalid(format!("unknown role {}", role)));
+		}
+		if let Some(event) = self.events.iter().flatten().find(|event| !EVENTS.contains(&event.as_str())) {
+			return Err(invalid(format!("unknown event {}", event)));
+		}
+		Ok(())
+	}
+
+	fn wants(&self, notification: &Notification) -> bool {
+		self.events.as_ref().map_or(true, |events| events.iter().any(|event| event == notification.event))
+			&& self.account.as_ref().map_or(true, |account| notification.parties.iter().any(|party| &party.account == account))
+			&& self.role.as_ref().map_or(true, |role| notification.parties.iter().any(|party| party.roles.contains(role)))
+	}
+}
+
+/// Reads the hooks, if the file exists.
+pub fn load_hooks(path: &Path) -> Result<Option<Vec<Hook>>> {
+	if !path.exists() {
+		return Ok(None);
+	}
+	let file = fs::File::open(path).map_err(|e| Error::Config(e.to_string()))?;
+	let mut hooks: Vec<Hook> = serde_json::from_reader(file).map_err(|e| Error::Config(e.to_string()))?;
+	for hook in &mut hooks {
+		hook.check()?;
+	}
+	Ok(Some(hooks))
+}
+
+/// Where a full node keeps its hooks and their cursors: next to its chain database, if that
+/// is on disk.
+pub fn paths(config: &sc_service::Configuration) -> Option<(PathBuf, PathBuf)> {
+	config.database.path()
+		.and_then(|path| path.parent())
+		.map(|path| (path.join("webhooks.json"), path.join("webhook-cursors.json")))
+}
+
+/// An account taking part in an event, and the member types it has at the block.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Party {
+	pub account: String,
+	pub roles: Vec<String>,
+}
+
+/// The payload posted to the hooks.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+	pub block_number: BlockNumber,
+	pub block_hash: Hash,
+	pub event_index: u32,
+	pub event: &'static str,
+	pub shipment: Option<String>,
+	/// Status the event left the shipment in.
+	pub status: Option<String>,
+	/// The sender, carrier, receiver and customs of the shipment, or the customer.
+	pub parties: Vec<Party>,
+}
+
+impl Notification {
+	fn delivery_id(&self) -> String {
+		format!("{:?}-{}", self.block_hash, self.event_index)
+	}
+}
+
+/// Next notification a hook is due: the one of the block from the event on.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+	pub block: BlockNumber,
+	pub event: u32,
+}
+
+/// Cursors of the hooks, saved after every delivery.
+pub struct Cursors {
+	path: PathBuf,
+	cursors: Mutex<HashMap<String, Cursor>>,
+}
+
+impl Cursors {
+	pub fn open(path: PathBuf) -> Result<Self> {
+		let cursors = if path.exists() {
+			let file = fs::File::open(&path).map_err(|e| Error::Config(e.to_string()))?;
+			serde_json::from_reader(file).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?
+		} else {
+			HashMap::new()
+		};
+		Ok(Cursors { path, cursors: Mutex::new(cursors) })
+	}
+
+	fn get(&self, hook: &str) -> Option<Cursor> {
+		self.cursors.lock().expect("cursors are not poisoned").get(hook).copied()
+	}
+
+	fn set(&self, hook: &str, cursor: Cursor) {
+		let mut cursors = self.cursors.lock().expect("cursors are not poisoned");
+		cursors.insert(hook.to_owned(), cursor);
+		// Write then rename, so that a crash leaves the previous cursors rather than none.
+		let saving = self.path.with_extension("json.tmp");
+		let saved = serde_json::to_vec(&*cursors).map_err(|e| e.to_string())
+			.and_then(|json| fs::write(&saving, json).map_err(|e| e.to_string()))
+			.and_then(|_| fs::rename(&saving, &self.path).map_err(|e| e.to_string()));
+		if let Err(e) = saved {
+			log::warn!(target: LOG_TARGET, "Cannot save the webhook cursors: {}", e);
+		}
+	}
+}
+
+/// Waits between the attempts to deliver a notification, doubling up to a maximum.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+	pub first: Duration,
+	pub max: Duration,
+}
+
+impl Default for Backoff {
+	fn default() -> Self {
+		Backoff { first: Duration::from_secs(1), max: Duration::from_secs(300) }
+	}
+}
+
+/// Hex-encoded HMAC-SHA256 of the payload.
+pub fn signature(secret: &str, payload: &[u8]) -> String {
+	let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC takes keys of any size");
+	mac.update(payload);
+	HexDisplay::from(&&mac.finalize().into_bytes()[..]).to_string()
+}
+
+/// Posts the notification once.
+async fn post<T: Connect + Clone + Send + Sync + 'static>(
+	http: &Client<T>,
+	hook: &Hook,
+	notification: &Notification,
+) -> Result<()> {
+	let payload = serde_json::to_vec(notification).expect("notifications serialize");
+	let request = Request::post(&hook.url)
+		.header("Content-Type", "application/json")
+		.header("X-Provenance-Event", notification.event)
+		.header("X-Provenance-Delivery", notification.delivery_id())
+		.header("X-Provenance-Signature", format!("sha256={}", signature(&hook.secret, &payload)))
+		.body(Body::from(payload))
+		.map_err(|e| Error::Delivery(e.to_string()))?;
+
+	let response = tokio::time::timeout(Duration::from_secs(30), http.request(request)).await
+		.map_err(|_| Error::Delivery("timed out".into()))?
+		.map_err(|e| Error::Delivery(e.to_string()))?;
+	if !response.status().is_success() {
+		return Err(Error::Delivery(format!("{} answered {}", hook.url, response.status())));
+	}
+	Ok(())
+}
+
+/// Posts the notification until the endpoint accepts it.
+pub async fn deliver<T: Connect + Clone + Send + Sync + 'static>(
+	http: &Client<T>,
+	hook: &Hook,
+	notification: &Notification,
+	backoff: Backoff,
+) {
+	let mut wait = backoff.first;
+	while let Err(e) = post(http, hook, notification).await {
+		log::warn!(
+			target: LOG_TARGET,
+			"Hook {}: {}, retrying {} in {:?}", hook.name, e, notification.delivery_id(), wait,
+		);
+		tokio::time::delay_for(wait).await;
+		wait = (wait * 2).min(backoff.max);
+	}
+}
+
+fn read<C, B, T>(client: &C, at: &Hash, key: Vec<u8>) -> Result<Option<T>>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B>,
+	T: Decode,
+{
+	match client.storage(&BlockId::Hash(*at), &StorageKey(key))? {
+		Some(data) => Ok(Some(T::decode(&mut &data.0[..])?)),
+		None => Ok(None),
+	}
+}
+
+/// Posts the notifications of finalized blocks to the hooks.
+pub struct Dispatcher<C, B> {
+	client: Arc<C>,
+	hooks: Vec<Hook>,
+	http: Client<HttpsConnector<HttpConnector>>,
+	cursors: Cursors,
+	backoff: Backoff,
+	_backend: PhantomData<B>,
+}
+
+impl<C, B> Dispatcher<C, B>
+where
+	B: Backend<Block>,
+	C: StorageProvider<Block, B> + HeaderBackend<Block> + BlockchainEvents<Block> + Send + Sync + 'static,
+{
+	/// The dispatcher of the hooks of the file, if there is one.
+	pub fn open(client: Arc<C>, hooks: &Path, cursors: PathBuf) -> Result<Option<Self>> {
+		let hooks = match load_hooks(hooks)? {
+			Some(hooks) => hooks,
+			None => return Ok(None),
+		};
+		Ok(Some(Dispatcher {
+			client,
+			hooks,
+			http: Client::builder().build(HttpsConnector::new()),
+			cursors: Cursors::open(cursors)?,
+			backoff: Backoff::default(),
+			_backend: PhantomData,
+		}))
+	}
+
+	/// Follows the finalized chain for every hook.
+	pub async fn run(self) {
+		future::join_all(self.hooks.iter().map(|hook| self.follow(hook))).await;
+	}
+
+	async fn follow(&self, hook: &Hook) {
+		// A new hook is due the notifications of the blocks finalized from now on.
+		let mut cursor = self.cursors.get(&hook.name).unwrap_or_else(|| {
+			let cursor = Cursor { block: self.client.info().finalized_number + 1, event: 0 };
+			self.cursors.set(&hook.name, cursor);
+			cursor
+		});
+		let mut finality = self.client.finality_notification_stream();
+
+		loop {
+			while cursor.block <= self.client.info().finalized_number {
+				let notifications = match self.notifications(cursor.block) {
+					Ok(notifications) => notifications,
+					Err(e) => {
+						log::warn!(target: LOG_TARGET, "Cannot read block {}: {}", cursor.block, e);
+						break;
+					},
+				};
+				for notification in notifications {
+					if notification.event_index < cursor.event || !hook.wants(&notification) {
+						continue;
+					}
+					deliver(&self.http, hook, &notification, self.backoff).await;
+					cursor.event = notification.event_index + 1;
+					self.cursors.set(&hook.name, cursor);
+				}
+				cursor = Cursor { block: cursor.block + 1, event: 0 };
+				self.cursors.set(&hook.name, cursor);
+			}
+
+			if finality.next().await.is_none() {
+				return;
+			}
+		}
+	}
+
+	/// Notifications of the events of a finalized block, in the order of the events.
+	fn notifications(&self, number: BlockNumber) -> Result<Vec<Notification>> {
+		use bottle_tracking::RawEvent as Tracking;
+
+		let client = &*self.client;
+		let hash = client.hash(number)?
+			.ok_or_else(|| Error::Client(sp_blockchain::Error::UnknownBlock(number.to_string())))?;
+		let events = read::<_, B, Vec<frame_system::EventRecord<Event, Hash>>>(
+			client, &hash, [twox_128(b"System"), twox_128(b"Events")].concat(),
+		)?.unwrap_or_default();
+
+		let mut members = None;
+		let mut notifications = Vec::new();
+		for (index, record) in events.into_iter().enumerate() {
+			let (event, id, status, customer) = match record.event {
+				Event::bottle_tracking(Tracking::ShipmentRegistered(id, _)) => ("ShipmentRegistered", Some(id), None, None),
+				Event::bottle_tracking(Tracking::ShipmentStatusUpdated(id, _, status)) =>
+					("ShipmentStatusUpdated", Some(id), Some(format!("{:?}", status)), None),
+				Event::bottle_tracking(Tracking::BottlesSoldToCustomer(customer)) =>
+					("BottlesSoldToCustomer", None, None, Some(customer)),
+				_ => continue,
+			};
+
+			let mut accounts = customer.into_iter().collect::<Vec<_>>();
+			if let Some(id) = &id {
+				let key = bottle_tracking::Shipments::<Runtime>::hashed_key_for(id);
+				if let Some(shipment) = read::<_, B, Shipment<AccountId, Moment, BlockNumber>>(client, &hash, key)? {
+					accounts.extend(vec![shipment.sender, shipment.carrier, shipment.receiver]);
+					accounts.extend(shipment.customs);
+				}
+			}
+
+			if members.is_none() {
+				members = Some(self.members(&hash)?);
+			}
+			let members = members.as_ref().expect("read above");
+			let parties = accounts.into_iter()
+				.map(|account| Party {
+					roles: members.iter()
+						.filter(|(_, accounts)| accounts.contains(&account))
+						.map(|(role, _)| role.clone())
+						.collect(),
+					account: account.to_ss58check(),
+				})
+				.collect();
+
+			notifications.push(Notification {
+				block_number: number,
+				block_hash: hash,
+				event_index: index as u32,
+				event,
+				shipment: id.map(|id| String::from_utf8_lossy(&id).into_owned()),
+				status,
+				parties,
+			});
+		}
+		Ok(notifications)
+	}
+
+	/// The members of every type at the block.
+	fn members(&self, at: &Hash) -> Result<Vec<(String, Vec<AccountId>)>> {
+		let prefix = StorageKey(registrar::Members::<Runtime>::final_prefix().to_vec());
+		self.client.storage_pairs(&BlockId::Hash(*at), &prefix)?
+			.into_iter()
+			.map(|(key, value)| -> Result<_> { Ok((
+				format!("{:?}", MemberType::decode(&mut &key.0[prefix.0.len()..])?),
+				Vec::<AccountId>::decode(&mut &value.0[..])?,
+			)) })
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hyper::{service::{make_service_fn, service_fn}, Response, Server, StatusCode};
+	use sp_core::crypto::AccountId32;
+
+	fn hook(account: Option<&AccountId32>, role: Option<&str>, events: Option<&[&str]>) -> Hook {
+		Hook {
+			name: "retailer".into(),
+			url: String::new(),
+			secret: "secret".into(),
+			account: account.map(Ss58Codec::to_ss58check),
+			role: role.map(Into::into),
+			events: events.map(|events| events.iter().map(|&event| event.into()).collect()),
+		}
+	}
+
+	fn picked_up(receiver: &AccountId32) -> Notification {
+		Notification {
+			block_number: 7,
+			block_hash: Hash::repeat_byte(7),
+			event_index: 2,
+			event: "ShipmentStatusUpdated",
+			shipment: Some("shipment-1".into()),
+			status: Some("InTransit".into()),
+			parties: vec![Party { account: receiver.to_ss58check(), roles: vec!["Retailer".into()] }],
+		}
+	}
+
+	#[test]
+	fn hooks_want_the_events_of_their_account_or_role() {
+		let (retailer, other) = (AccountId32::new([1; 32]), AccountId32::new([2; 32]));
+		let notification = picked_up(&retailer);
+
+		assert!(hook(None, None, None).wants(&notification));
+		assert!(hook(Some(&retailer), None, None).wants(&notification));
+		assert!(!hook(Some(&other), None, None).wants(&notification));
+		assert!(hook(None, Some("Retailer"), Some(&["ShipmentStatusUpdated"])).wants(&notification));
+		assert!(!hook(None, Some("Carrier"), None).wants(&notification));
+		assert!(!hook(None, None, Some(&["ShipmentRegistered"])).wants(&notification));
+	}
+
+	#[test]
+	fn rejects_unknown_roles_and_events() {
+		assert!(hook(None, Some("Sommelier"), None).check().is_err());
+		assert!(hook(None, None, Some(&["BottleRegistered"])).check().is_err());
+	}
+
+	#[test]
+	fn cursors_survive_restarts() {
+		let dir = std::env::temp_dir().join(format!("provenance-webhooks-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("webhook-cursors.json");
+
+		Cursors::open(path.clone()).unwrap().set("retailer", Cursor { block: 8, event: 3 });
+
+		assert_eq!(Cursors::open(path).unwrap().get("retailer"), Some(Cursor { block: 8, event: 3 }));
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn retries_until_the_endpoint_accepts_signed_payload() {
+		let mut runtime = tokio::runtime::Runtime::new().unwrap();
+		let received = Arc::new(Mutex::new(Vec::new()));
+
+		runtime.block_on(async {
+			// A stub failing the first request, then recording the signature and payload.
+			let stub = {
+				let received = received.clone();
+				make_service_fn(move |_| {
+					let received = received.clone();
+					async move {
+						Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+							let received = received.clone();
+							async move {
+								let signature = request.headers()["X-Provenance-Signature"].to_str().unwrap().to_owned();
+								let payload = hyper::body::to_bytes(request.into_body()).await?.to_vec();
+								let mut received = received.lock().unwrap();
+								received.push((signature, payload));
+								let status = if received.len() == 1 { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK };
+								Ok::<_, hyper::Error>(Response::builder().status(status).body(Body::empty()).unwrap())
+							}
+						}))
+					}
+				})
+			};
+			let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(stub);
+			let mut hook = hook(None, None, None);
+			hook.url = format!("http://{}/", server.local_addr());
+			tokio::spawn(server);
+
+			let backoff = Backoff { first: Duration::from_millis(10), max: Duration::from_millis(10) };
+			deliver(&Client::new(), &hook, &picked_up(&AccountId32::new([1; 32])), backoff).await;
+		});
+
+		let received = received.lock().unwrap();
+		assert_eq!(received.len(), 2);
+		let (signature, payload) = &received[1];
+		assert_eq!(signature, &format!("sha256={}", super::signature("secret", payload)));
+		let payload: serde_json::Value = serde_json::from_slice(payload).unwrap();
+		assert_eq!(payload["event"], "ShipmentStatusUpdated");
+		assert_eq!(payload["status"], "InTransit");
+	}
+}
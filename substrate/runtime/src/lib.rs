@@ -11,11 +11,13 @@ use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
 use sp_io::hashing::blake2_128;
 use sp_runtime::{
 	ApplyExtrinsicResult, generic, create_runtime_str, impl_opaque_keys, MultiSignature,
-	transaction_validity::{TransactionValidity, TransactionSource},
+	transaction_validity::{TransactionValidity, TransactionSource, TransactionPriority},
 };
 use sp_runtime::traits::{
 	BlakeTwo256, Block as BlockT, IdentityLookup, Verify, IdentifyAccount, NumberFor, Saturating,
+	SaturatedConversion, Extrinsic as ExtrinsicT,
 };
+use codec::Encode;
 use sp_api::impl_runtime_apis;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use pallet_grandpa::{AuthorityId as GrandpaId, AuthorityList as GrandpaAuthorityList};
@@ -45,6 +47,9 @@ pub use registrar;
 /// Import the bottle-trackinig pallet.
 pub use bottle_tracking;
 
+/// Import the fee waiver of the members.
+pub use member_fees;
+
 /// An index to a block.
 pub type BlockNumber = u32;
 
@@ -99,10 +104,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("node-template"),
 	impl_name: create_runtime_str!("node-template"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 15,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 8,
 };
 
 pub const MILLISECS_PER_BLOCK: u64 = 6000;
@@ -302,13 +307,80 @@ impl example::Trait for Runtime {
 
 
 /// Configure the registrar pallet in pallets/registrar.
-impl registrar::Trait for Runtime {
+impl registrar::Config for Runtime {
 	type Event = Event;
+	type WeightInfo = registrar::weights::SubstrateWeight<Runtime>;
+}
+
+parameter_types! {
+	pub const DisputePeriod: BlockNumber = 7 * DAYS;
+	pub const FeedPollInterval: BlockNumber = MINUTES;
+	pub const MaxDeviceScans: u32 = 20;
+	pub const DeviceScanWindow: BlockNumber = MINUTES;
+	pub const UnsignedScanPriority: TransactionPriority = 1 << 20;
 }
 
 /// Configure the bottle-tracking pallet in pallets/bottle-tracking.
-impl bottle_tracking::Trait for Runtime {
+impl bottle_tracking::Config for Runtime {
 	type Event = Event;
+	type Currency = Balances;
+	type ArbitratorOrigin = frame_system::EnsureRoot<AccountId>;
+	type DisputePeriod = DisputePeriod;
+	type DeviceId = bottle_tracking::offchain::crypto::DeviceId;
+	type FeedPollInterval = FeedPollInterval;
+	type MaxDeviceScans = MaxDeviceScans;
+	type DeviceScanWindow = DeviceScanWindow;
+	type UnsignedScanPriority = UnsignedScanPriority;
+	type WeightInfo = bottle_tracking::weights::SubstrateWeight<Runtime>;
+}
+
+parameter_types! {
+	pub const FeelessQuota: u32 = 100;
+	pub const FeelessPeriod: BlockNumber = DAYS;
+}
+
+/// Configure the fee waiver in pallets/member-fees.
+impl member_fees::Config for Runtime {
+	type FeelessQuota = FeelessQuota;
+	type FeelessPeriod = FeelessPeriod;
+}
+
+impl frame_system::offchain::SigningTypes for Runtime {
+	type Public = <Signature as Verify>::Signer;
+	type Signature = Signature;
+}
+
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Runtime where Call: From<C> {
+	type OverarchingCall = Call;
+	type Extrinsic = UncheckedExtrinsic;
+}
+
+/// Signs the transactions of offchain workers, such as the telemetry reports of carrier devices.
+impl<C> frame_system::offchain::CreateSignedTransaction<C> for Runtime where Call: From<C> {
+	fn create_transaction<S: frame_system::offchain::AppCrypto<Self::Public, Self::Signature>>(
+		call: Call,
+		public: <Signature as Verify>::Signer,
+		account: AccountId,
+		nonce: Index,
+	) -> Option<(Call, <UncheckedExtrinsic as ExtrinsicT>::SignaturePayload)> {
+		let period = BlockHashCount::get().checked_next_power_of_two().map(|c| c / 2).unwrap_or(2) as u64;
+		let current_block = System::block_number().saturated_into::<u64>().saturating_sub(1);
+		let extra: SignedExtra = (
+			frame_system::CheckSpecVersion::<Runtime>::new(),
+			frame_system::CheckTxVersion::<Runtime>::new(),
+			frame_system::CheckGenesis::<Runtime>::new(),
+			frame_system::CheckEra::<Runtime>::from(generic::Era::mortal(period, current_block)),
+			frame_system::CheckNonce::<Runtime>::from(nonce),
+			frame_system::CheckWeight::<Runtime>::new(),
+			member_fees::WaiveMemberFees::<Runtime>::from(0),
+		);
+		let raw_payload = SignedPayload::new(call, extra)
+			.map_err(|e| frame_support::debug::warn!("Unable to create signed payload: {:?}", e))
+			.ok()?;
+		let signature = raw_payload.using_encoded(|payload| S::sign(payload, public))?;
+		let (call, extra, _) = raw_payload.deconstruct();
+		Some((call, (account, signature, extra)))
+	}
 }
 
 
@@ -332,8 +404,9 @@ construct_runtime!(
 		ChainBridge: chainbridge::{Module, Call, Storage, Event<T>},
 		Example: example::{Module, Call, Event<T>},
 		Erc721: erc721::{Module, Call, Storage, Event<T>},
-		RegistrarModule: registrar::{Module, Call, Storage, Event<T>},
-		BottleTracking: bottle_tracking::{Module, Call, Storage, Event<T>},
+		RegistrarModule: registrar::{Module, Call, Config<T>, Storage, Event<T>},
+		BottleTracking: bottle_tracking::{Module, Call, Config<T>, Storage, Event<T>, ValidateUnsigned},
+		MemberFees: member_fees::{Module, Storage},
 	}
 );
 
@@ -355,10 +428,12 @@ pub type SignedExtra = (
 	frame_system::CheckEra<Runtime>,
 	frame_system::CheckNonce<Runtime>,
 	frame_system::CheckWeight<Runtime>,
-	pallet_transaction_payment::ChargeTransactionPayment<Runtime>
+	member_fees::WaiveMemberFees<Runtime>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
+/// The payload being signed in transactions.
+pub type SignedPayload = generic::SignedPayload<Call, SignedExtra>;
 /// Extrinsic type that has already been checked.
 pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Call, SignedExtra>;
 /// Executive: handles dispatch to the various modules.
@@ -530,6 +605,8 @@ impl_runtime_apis! {
 			add_benchmark!(params, batches, frame_system, SystemBench::<Runtime>);
 			add_benchmark!(params, batches, pallet_balances, Balances);
 			add_benchmark!(params, batches, pallet_timestamp, Timestamp);
+			add_benchmark!(params, batches, registrar, RegistrarModule);
+			add_benchmark!(params, batches, bottle_tracking, BottleTracking);
 
 			if batches.is_empty() { return Err("Benchmark not found for this pallet.".into()) }
 			Ok(batches)